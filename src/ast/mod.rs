@@ -4,6 +4,5 @@ pub mod scanner;
 pub mod tag;
 
 pub use node::*;
-pub use parser::AST;
 
 pub use tag::{Id, Tag};
@@ -1,8 +1,23 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
+
 #[derive(Debug, Clone)]
-pub struct BooleanNode(pub bool);
+pub struct BooleanNode {
+    value: bool,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
+}
 
 impl BooleanNode {
+    pub fn new(value: bool) -> BooleanNode {
+        BooleanNode {
+            value,
+            location: Location::default(),
+            comments: Comments::default(),
+        }
+    }
+
     pub fn value(&self) -> bool {
-        self.0
+        self.value
     }
 }
@@ -1,22 +1,30 @@
-use crate::ast::node::{Node, ToNode};
+use crate::ast::node::{Comments, Node, ToNode};
+use crate::ast::scanner::Location;
 use crate::ast::tag::Partition;
-use crate::result::runtime::ErrorKind;
-use crate::result::runtime::ErrorKind::General;
+use crate::result::runtime::ErrorKind::InvalidForm;
+use crate::result::Result;
 
 #[derive(Debug, Clone)]
 pub struct DecoratorNode {
     pub mutator: Box<Node>,
     pub target: Box<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl DecoratorNode {}
 
 impl ToNode for DecoratorNode {
-    fn make_node(tags: Vec<Node>) -> Result<Node, ErrorKind> {
-        let (mutator, target, _) = tags.take_2().ok_or(General("Could not parse"))?;
+    fn make_node(tags: Vec<Node>) -> Result<Node> {
+        let (mutator, target, _) = tags.take_2().ok_or(InvalidForm {
+            form: "decorator",
+            reason: "expected a decorator and a target",
+        })?;
         Ok(Node::Decorator(DecoratorNode {
             mutator: Box::new(mutator),
             target: Box::new(target),
+            location: Location::default(),
+            comments: Comments::default(),
         }))
     }
 }
@@ -1,14 +1,16 @@
 use crate::defnode;
 use crate::prelude::*;
 
-use crate::ast::node::{Node, SymbolNode, ToNode};
+use crate::ast::node::{Comments, Node, SymbolNode, ToNode};
+use crate::ast::scanner::Location;
 use crate::ast::tag::Partition;
-use crate::result::runtime::ErrorKind;
 
 #[derive(Debug, Clone)]
 pub struct DefinitionNode {
     ident: SymbolNode,
     value: Box<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl DefinitionNode {
@@ -24,11 +26,22 @@ impl DefinitionNode {
 
 defnode! {
     Node::Definition : DefinitionNode :: nodes => {
-        let (_, ident, value, _) = nodes.take_3().ok_or(CouldNotParseAtom)?;
-        let ident = ident.take_symbol().ok_or(General("Invalid def form"))?;
+        let (_, ident, value, _) = nodes
+            .take_3()
+            .filter(|(.., rest)| rest.is_empty())
+            .ok_or(InvalidForm {
+                form: "def",
+                reason: "expected a name and a value",
+            })?;
+        let ident = ident.take_symbol().ok_or(InvalidForm {
+            form: "def",
+            reason: "the name must be a symbol",
+        })?;
         Ok(DefinitionNode {
             ident,
             value: Box::new(value),
+            location: Location::default(),
+            comments: Comments::default(),
         })
     }
 }
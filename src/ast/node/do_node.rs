@@ -1,11 +1,14 @@
-use crate::ast::node::{Node, ToNode};
+use crate::ast::node::{Comments, Node, ToNode};
+use crate::ast::scanner::Location;
+use crate::ast::tag::Partition;
 use crate::defnode;
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind;
 
 #[derive(Debug, Clone)]
 pub struct DoNode {
     expressions: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl DoNode {
@@ -16,6 +19,14 @@ impl DoNode {
 
 defnode! {
     Node::Do : DoNode :: nodes => {
-        Ok(DoNode { expressions: nodes })
+        let (_, expressions) = nodes.take_1().ok_or(InvalidForm {
+            form: "do",
+            reason: "expected expressions",
+        })?;
+        Ok(DoNode {
+            expressions,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
     }
 }
@@ -1,84 +1,89 @@
-use crate::ast::node::{Node, SymbolNode, ToNode, VectorNode};
+use crate::ast::node::{Comments, Node, SymbolNode, ToNode, VectorNode};
+use crate::ast::scanner::Location;
 use crate::ast::tag::Partition;
-use crate::ast::Tag;
 use crate::defnode;
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind::General;
-
-trait Show {
-    fn show(self) -> Self
-    where
-        Self: Sized + fmt::Debug;
-}
 
 #[derive(Debug, Clone)]
 pub struct FunctionCallNode {
     function: Box<Node>,
     arguments: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 defnode! {
     Node::FunctionCall : FunctionCallNode :: nodes => {
-        let (function, arguments) = nodes.take_1().unwrap();
+        let (function, arguments) = nodes.take_1().ok_or(InvalidForm {
+            form: "call",
+            reason: "an empty list is not a call",
+        })?;
         Ok(FunctionCallNode {
             function: Box::new(function),
             arguments,
+            location: Location::default(),
+            comments: Comments::default(),
         })
     }
 }
 
 impl FunctionCallNode {
-    pub fn function(&self) -> Tag {
-        todo!()
+    pub fn function(&self) -> &Node {
+        self.function.as_ref()
     }
-    pub fn arguments(&self) -> Vec<Tag> {
-        todo!()
+
+    pub fn arguments(&self) -> &Vec<Node> {
+        &self.arguments
     }
 }
 
 #[derive(Debug, Clone)]
-pub enum FunctionNode {
-    Named {
-        name: SymbolNode,
-        parameters: VectorNode,
-        body: Vec<Node>,
-    },
-    Anonymous {
-        parameters: VectorNode,
-        body: Vec<Node>,
-    },
+pub struct FunctionNode {
+    name: Option<SymbolNode>,
+    parameters: VectorNode,
+    body: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 defnode! {
     Node::Function : FunctionNode :: nodes => {
-        let (_, name_or_params, params_or_first_body, body) = nodes.take_3().unwrap();
-        Ok(
-            match (name_or_params, params_or_first_body) {
-                (Node::Symbol(name), Node::Vector(parameters)) => FunctionNode::Named {
-                    name,
-                    parameters,
-                    body,
-                },
-                (Node::Vector(parameters), node) => FunctionNode::Anonymous {
-                    parameters,
-                    body: vec![vec![node], body].concat(),
-                },
-                (_, _) => return Err(General("invalid function call")),
-            },
-        )
+        let (_, name_or_params, params_or_first_body, body) =
+            nodes.take_3().ok_or(InvalidForm {
+                form: "fn",
+                reason: "expected parameters and a body",
+            })?;
+        let (name, parameters, body) = match (name_or_params, params_or_first_body) {
+            (Node::Symbol(name), Node::Vector(parameters)) => (Some(name), parameters, body),
+            (Node::Vector(parameters), node) => (None, parameters, [vec![node], body].concat()),
+            (_, _) => {
+                return Err(InvalidForm {
+                    form: "fn",
+                    reason: "parameters must be a vector",
+                }
+                .into())
+            }
+        };
+        Ok(FunctionNode {
+            name,
+            parameters,
+            body,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
     }
 }
 
 impl FunctionNode {
-    pub fn parameters(&self) -> Tag {
-        todo!()
+    pub fn parameters(&self) -> &VectorNode {
+        &self.parameters
     }
 
-    pub fn body(&self) -> Vec<Tag> {
-        todo!()
+    pub fn body(&self) -> &Vec<Node> {
+        &self.body
     }
 
-    pub fn name(&self) -> Option<Tag> {
-        todo!()
+    pub fn name(&self) -> Option<&SymbolNode> {
+        self.name.as_ref()
     }
 }
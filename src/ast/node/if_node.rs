@@ -1,7 +1,9 @@
+use crate::ast::node::Comments;
 use crate::ast::node::Node;
 use crate::ast::node::ToNode;
+use crate::ast::scanner::Location;
 use crate::ast::tag::Partition;
-use crate::result::runtime::ErrorKind::General;
+use crate::result::runtime::ErrorKind::InvalidForm;
 use crate::result::Result;
 use std::fmt;
 
@@ -10,6 +12,8 @@ pub struct IfNode {
     pub condition: Box<Node>,
     pub true_branch: Box<Node>,
     pub false_branch: Box<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl fmt::Display for IfNode {
@@ -24,11 +28,19 @@ impl fmt::Display for IfNode {
 
 impl ToNode for IfNode {
     fn make_node(tags: Vec<Node>) -> Result<Node> {
-        let (_, condition, true_branch, false_branch, _) = tags.take_4().ok_or(General("Wow"))?;
+        let (_, condition, true_branch, false_branch, _) = tags
+            .take_4()
+            .filter(|(.., rest)| rest.is_empty())
+            .ok_or(InvalidForm {
+                form: "if",
+                reason: "expected a condition and two branches",
+            })?;
         Ok(Node::If(IfNode {
             condition: Box::new(condition),
             true_branch: Box::new(true_branch),
             false_branch: Box::new(false_branch),
+            location: Location::default(),
+            comments: Comments::default(),
         }))
     }
 }
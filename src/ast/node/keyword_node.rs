@@ -1,6 +1,63 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
+use std::fmt;
+
 #[derive(Debug, Clone)]
 pub struct KeywordNode {
     name: String,
     namespace: Option<String>,
     expanding: bool,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
+}
+
+impl KeywordNode {
+    pub fn name(&self) -> &str {
+        &self.name[..]
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_ref().map(|namespace| &namespace[..])
+    }
+
+    /// Whether the keyword was written `::name`, to be qualified with the
+    /// current namespace when it is evaluated.
+    pub fn is_expanding(&self) -> bool {
+        self.expanding
+    }
+
+    /// Reads a keyword lexeme, `:name`, `:namespace/name` or `::name`. Returns
+    /// `None` when the name is missing or an expanding keyword names its own
+    /// namespace.
+    pub fn from(lexeme: &str) -> Option<KeywordNode> {
+        let rest = lexeme.strip_prefix(':')?;
+        let (expanding, rest) = match rest.strip_prefix(':') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (namespace, name) = match rest.find('/') {
+            Some(index) if rest.len() > 1 => (Some(&rest[..index]), &rest[index + 1..]),
+            _ => (None, rest),
+        };
+        if name.is_empty() || namespace == Some("") || (expanding && namespace.is_some()) {
+            return None;
+        }
+        Some(KeywordNode {
+            name: String::from(name),
+            namespace: namespace.map(String::from),
+            expanding,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
+    }
+}
+
+impl fmt::Display for KeywordNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let colons = if self.expanding { "::" } else { ":" };
+        match self.namespace() {
+            Some(namespace) => write!(f, "{}{}/{}", colons, namespace, self.name()),
+            None => write!(f, "{}{}", colons, self.name()),
+        }
+    }
 }
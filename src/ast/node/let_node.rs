@@ -1,25 +1,66 @@
-use crate::ast::node::{Node, ToNode, VectorNode};
+use crate::ast::node::{Comments, Node, ToNode, VectorNode};
+use crate::ast::scanner::Location;
 use crate::ast::tag::Partition;
 use crate::defnode;
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind;
-use crate::result::runtime::ErrorKind::General;
 
 #[derive(Debug, Clone)]
 pub struct LetNode {
+    /// The head the form was written with, `let` or `let*`.
+    head: String,
     bindings: VectorNode,
     body: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
-impl LetNode {}
+impl LetNode {
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// The binding vector as `(pattern, value)` pairs.
+    pub fn bindings(&self) -> impl Iterator<Item = (&Node, &Node)> {
+        self.bindings
+            .items()
+            .chunks(2)
+            .map(|pair| (&pair[0], &pair[1]))
+    }
+
+    /// The binding vector as it was written.
+    pub fn binding_vector(&self) -> &VectorNode {
+        &self.bindings
+    }
+
+    pub fn body(&self) -> &Vec<Node> {
+        &self.body
+    }
+}
 
 defnode! {
     Node::Let : LetNode :: nodes => {
-        let (_, bindings, body) = nodes.take_2().ok_or(General("invalid let node"))?;
-        let bindings = bindings.take_vector().ok_or(General("let bindings must be a vector"))?;
-        Ok(LetNode{
+        let (head, bindings, body) = nodes.take_2().ok_or(InvalidForm {
+            form: "let",
+            reason: "expected a binding vector",
+        })?;
+        let bindings = bindings.take_vector().ok_or(InvalidForm {
+            form: "let",
+            reason: "bindings must be a vector",
+        })?;
+        if bindings.items().len() % 2 != 0 {
+            return Err(InvalidForm {
+                form: "let",
+                reason: "bindings must be pairs",
+            }
+            .into());
+        }
+        let head = head.as_symbol().map_or("let", |head| head.name()).to_string();
+        Ok(LetNode {
+            head,
             bindings,
-            body
+            body,
+            location: Location::default(),
+            comments: Comments::default(),
         })
     }
 }
@@ -1,17 +1,31 @@
 use super::Node;
+use crate::ast::node::Comments;
 use crate::ast::node::Node::List;
 use crate::ast::node::ToNode;
-use crate::result::runtime::ErrorKind;
+use crate::ast::scanner::Location;
+use crate::result::Result;
 
+/// A parenthesized form read as data inside a quote, where it is never a
+/// call or special form.
 #[derive(Debug, Clone)]
 pub struct ListNode {
     items: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
-impl ListNode {}
+impl ListNode {
+    pub fn items(&self) -> &Vec<Node> {
+        &self.items
+    }
+}
 
 impl ToNode for ListNode {
-    fn make_node(tags: Vec<Node>) -> Result<Node, ErrorKind> {
-        Ok(List(ListNode { items: tags }))
+    fn make_node(tags: Vec<Node>) -> Result<Node> {
+        Ok(List(ListNode {
+            items: tags,
+            location: Location::default(),
+            comments: Comments::default(),
+        }))
     }
 }
@@ -1,16 +1,58 @@
-use crate::ast::node::{Node, ToNode, VectorNode};
-use crate::result::runtime::ErrorKind;
+use crate::ast::node::{Comments, Node, ToNode, VectorNode};
+use crate::ast::scanner::Location;
+use crate::ast::tag::Partition;
+use crate::defnode;
+use crate::prelude::*;
 
 #[derive(Debug, Clone)]
 pub struct LoopNode {
     pub bindings: VectorNode,
     pub body: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
-impl LoopNode {}
+impl LoopNode {
+    /// The binding vector as `(pattern, initial value)` pairs.
+    pub fn bindings(&self) -> impl Iterator<Item = (&Node, &Node)> {
+        self.bindings
+            .items()
+            .chunks(2)
+            .map(|pair| (&pair[0], &pair[1]))
+    }
+
+    /// The binding vector as it was written.
+    pub fn binding_vector(&self) -> &VectorNode {
+        &self.bindings
+    }
+
+    pub fn body(&self) -> &Vec<Node> {
+        &self.body
+    }
+}
 
-impl ToNode for LoopNode {
-    fn make_node(_: Vec<Node>) -> Result<Node, ErrorKind> {
-        todo!()
+defnode! {
+    Node::Loop : LoopNode :: nodes => {
+        let (_, bindings, body) = nodes.take_2().ok_or(InvalidForm {
+            form: "loop",
+            reason: "expected a binding vector",
+        })?;
+        let bindings = bindings.take_vector().ok_or(InvalidForm {
+            form: "loop",
+            reason: "bindings must be a vector",
+        })?;
+        if bindings.items().len() % 2 != 0 {
+            return Err(InvalidForm {
+                form: "loop",
+                reason: "bindings must be pairs",
+            }
+            .into());
+        }
+        Ok(LoopNode {
+            bindings,
+            body,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
     }
 }
@@ -1,10 +1,12 @@
-use crate::ast::Tag;
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
 
+/// A `defmacro`. The parser rejects macros for now, so the node gets its
+/// name, parameters and body once they are expanded.
 #[derive(Debug, Clone)]
 pub struct MacroNode {
-    name: Tag,
-    parameters: Tag,
-    body: Vec<Tag>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl MacroNode {}
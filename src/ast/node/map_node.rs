@@ -0,0 +1,50 @@
+use super::Node;
+use crate::ast::node::Comments;
+use crate::ast::node::ToNode;
+use crate::ast::scanner::Location;
+use crate::defnode;
+use crate::prelude::*;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone)]
+pub struct MapNode {
+    items: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
+}
+
+defnode! {
+    Node::Map : MapNode :: nodes => {
+        if !nodes.len().is_multiple_of(2) {
+            return Err(InvalidForm {
+                form: "map",
+                reason: "expected a value for every key",
+            }
+            .into());
+        }
+        Ok(MapNode {
+            items: nodes,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
+    }
+}
+
+impl MapNode {
+    /// The keys and values in the order they were written, alternating.
+    pub fn items(&self) -> &Vec<Node> {
+        self.items.as_ref()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&Node, &Node)> {
+        self.items.chunks(2).map(|entry| (&entry[0], &entry[1]))
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn comments(&self) -> &Comments {
+        &self.comments
+    }
+}
@@ -1,18 +1,21 @@
-use crate::ast::node::{Node, ToNode};
-use crate::ast::Tag;
+use crate::ast::node::{Comments, Node, ToNode};
+use crate::ast::scanner::Location;
 use crate::result::runtime::ErrorKind;
+use crate::result::Result;
 
+/// A `^data target` form. Metadata is not supported yet, so the node gets
+/// its data and target along with it.
 #[derive(Debug, Clone)]
 pub struct MetaNode {
-    data: Tag,
-    target: Tag,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl MetaNode {}
 
 impl ToNode for MetaNode {
-    fn make_node(_: Vec<Node>) -> Result<Node, ErrorKind> {
-        todo!()
+    fn make_node(_: Vec<Node>) -> Result<Node> {
+        Err(ErrorKind::Unsupported("metadata forms").into())
         // let (data, target, _) = tags.take_2().ok_or(General("Failed"))?;
         // Ok(Node::Meta(MetaNode { data, target }))
     }
@@ -10,17 +10,21 @@ pub use let_node::LetNode;
 pub use list_node::ListNode;
 pub use loop_node::LoopNode;
 pub use macro_node::MacroNode;
+pub use map_node::MapNode;
 pub use meta_node::MetaNode;
+pub use nil_node::NilNode;
 pub use number_node::NumberNode;
 pub use program_node::ProgramNode;
-pub use quasi_quote_node::QuasiQuoteNode;
+pub use quasi_quote_node::{Gensyms, QuasiQuoteNode};
 pub use quote_node::QuoteNode;
 pub use recur_node::RecurNode;
 pub use string_node::StringNode;
 pub use symbol_node::SymbolNode;
+pub use unquote_node::UnquoteNode;
 pub use vector_node::VectorNode;
 pub use while_node::WhileNode;
 
+use crate::ast::scanner::Location;
 use crate::prelude::*;
 
 mod boolean_node;
@@ -34,7 +38,9 @@ mod let_node;
 mod list_node;
 mod loop_node;
 mod macro_node;
+mod map_node;
 mod meta_node;
+mod nil_node;
 mod number_node;
 mod program_node;
 mod quasi_quote_node;
@@ -42,12 +48,38 @@ mod quote_node;
 mod recur_node;
 mod string_node;
 mod symbol_node;
+mod unquote_node;
 mod vector_node;
 mod while_node;
 
+/// A `;` comment as it was written, up to the end of its line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub location: Location,
+}
+
+/// The comments the parser read around a node, kept so the source can be
+/// printed back with them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comments {
+    /// Comments on the lines before the node.
+    pub leading: Vec<Comment>,
+    /// A comment after the node on the line it ends.
+    pub trailing: Option<Box<Comment>>,
+    /// Comments after the last item of a form, before it is closed.
+    pub inner: Vec<Comment>,
+}
+
+impl Comments {
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_none() && self.inner.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Node {
-    Nil,
+    Nil(NilNode),
     Boolean(BooleanNode),
     Number(NumberNode),
     String(StringNode),
@@ -63,10 +95,12 @@ pub enum Node {
     Do(DoNode),
     Program(ProgramNode),
     Vector(VectorNode),
+    Map(MapNode),
     Recur(RecurNode),
     Loop(LoopNode),
     Quote(QuoteNode),
     QuasiQuote(QuasiQuoteNode),
+    Unquote(UnquoteNode),
     Meta(MetaNode),
     Macro(MacroNode),
     Decorator(DecoratorNode),
@@ -104,6 +138,7 @@ boilerplate! { Node::Let, LetNode, take_let, as_let }
 boilerplate! { Node::List, ListNode, take_list, as_list }
 boilerplate! { Node::Loop, LoopNode, take_loop, as_loop }
 boilerplate! { Node::Macro, MacroNode, take_macro, as_macro }
+boilerplate! { Node::Map, MapNode, take_map, as_map }
 boilerplate! { Node::Meta, MetaNode, take_meta, as_meta }
 boilerplate! { Node::Number, NumberNode, take_number, as_number }
 boilerplate! { Node::Program, ProgramNode, take_program, as_program }
@@ -112,9 +147,50 @@ boilerplate! { Node::Quote, QuoteNode, take_quote, as_quote }
 boilerplate! { Node::Recur, RecurNode, take_recur, as_recur }
 boilerplate! { Node::String, StringNode, take_string, as_string }
 boilerplate! { Node::Symbol, SymbolNode, take_symbol, as_symbol }
+boilerplate! { Node::Unquote, UnquoteNode, take_unquote, as_unquote }
 boilerplate! { Node::Vector, VectorNode, take_vector, as_vector }
 boilerplate! { Node::While, WhileNode, take_while, as_while }
 
+macro_rules! located {
+    ($($variant:ident),*) => {
+        impl Node {
+            /// Where this node was read from. Nodes built outside the parser
+            /// report the default location.
+            pub fn location(&self) -> Location {
+                match self {
+                    $(Node::$variant(node) => node.location,)*
+                }
+            }
+
+            /// Sets the location of this node, returning it.
+            pub fn at(mut self, location: Location) -> Node {
+                match &mut self {
+                    $(Node::$variant(node) => node.location = location,)*
+                }
+                self
+            }
+
+            pub fn comments(&self) -> &Comments {
+                match self {
+                    $(Node::$variant(node) => &node.comments,)*
+                }
+            }
+
+            pub fn comments_mut(&mut self) -> &mut Comments {
+                match self {
+                    $(Node::$variant(node) => &mut node.comments,)*
+                }
+            }
+        }
+    };
+}
+
+located! {
+    Nil, Boolean, Number, String, Symbol, Keyword, Function, FunctionCall, Definition, If, While,
+    Let, List, Do, Program, Vector, Map, Recur, Loop, Quote, QuasiQuote, Unquote, Meta, Macro,
+    Decorator
+}
+
 pub trait ToNode {
     fn make_node(tags: Vec<Node>) -> Result<Node>;
 }
@@ -0,0 +1,8 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
+
+#[derive(Debug, Clone, Default)]
+pub struct NilNode {
+    pub(super) location: Location,
+    pub(super) comments: Comments,
+}
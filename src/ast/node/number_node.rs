@@ -1,8 +1,30 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
+
 #[derive(Debug, Clone)]
-pub struct NumberNode(pub f64);
+pub struct NumberNode {
+    value: f64,
+    lexeme: String,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
+}
 
 impl NumberNode {
+    pub fn new(value: f64, lexeme: &str) -> NumberNode {
+        NumberNode {
+            value,
+            lexeme: lexeme.to_string(),
+            location: Location::default(),
+            comments: Comments::default(),
+        }
+    }
+
     pub fn value(&self) -> f64 {
-        self.0
+        self.value
+    }
+
+    /// The number as it was written.
+    pub fn lexeme(&self) -> &str {
+        &self.lexeme
     }
 }
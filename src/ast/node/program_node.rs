@@ -1,13 +1,21 @@
+use crate::ast::node::Comments;
 use crate::ast::node::Node;
+use crate::ast::scanner::Location;
 
 #[derive(Debug, Clone)]
 pub struct ProgramNode {
     expressions: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl ProgramNode {
     pub fn new(expressions: Vec<Node>) -> ProgramNode {
-        ProgramNode { expressions }
+        ProgramNode {
+            expressions,
+            location: Location::default(),
+            comments: Comments::default(),
+        }
     }
     pub fn expressions(&self) -> &Vec<Node> {
         &self.expressions
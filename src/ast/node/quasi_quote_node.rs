@@ -1,12 +1,39 @@
-use crate::ast::Tag;
+use crate::ast::node::Comments;
+use crate::ast::node::Node;
+use crate::ast::scanner::Location;
 
+use std::collections::HashMap;
+
+/// The symbol generated for each `name#` in a syntax quote, by `name#`.
+pub type Gensyms = HashMap<String, String>;
+
+/// `` `expression ``, which evaluates to `expression` as data like a quote,
+/// except for the `~` and `~@` forms in it, which are evaluated.
 #[derive(Debug, Clone)]
 pub struct QuasiQuoteNode {
-    expression: Tag,
+    expression: Box<Node>,
+    /// The symbol generated for each `name#` in the expression when it was
+    /// read, so every evaluation builds the same data.
+    gensyms: Gensyms,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl QuasiQuoteNode {
-    pub fn from_tag(expression: Tag) -> QuasiQuoteNode {
-        QuasiQuoteNode { expression }
+    pub fn new(expression: Node, gensyms: Gensyms) -> QuasiQuoteNode {
+        QuasiQuoteNode {
+            expression: Box::new(expression),
+            gensyms,
+            location: Location::default(),
+            comments: Comments::default(),
+        }
+    }
+
+    pub fn expression(&self) -> &Node {
+        &self.expression
+    }
+
+    pub fn gensyms(&self) -> &Gensyms {
+        &self.gensyms
     }
 }
@@ -1,16 +1,25 @@
-use crate::ast::Tag;
+use crate::ast::node::Comments;
+use crate::ast::node::Node;
+use crate::ast::scanner::Location;
 
+/// `'expression`, which evaluates to `expression` as data.
 #[derive(Clone, Debug)]
 pub struct QuoteNode {
-    expression: Tag,
+    expression: Box<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl QuoteNode {
-    pub fn from_tag(tag: Tag) -> QuoteNode {
-        QuoteNode { expression: tag }
+    pub fn new(expression: Node) -> QuoteNode {
+        QuoteNode {
+            expression: Box::new(expression),
+            location: Location::default(),
+            comments: Comments::default(),
+        }
     }
 
-    pub fn expression(&self) -> Tag {
-        self.expression
+    pub fn expression(&self) -> &Node {
+        &self.expression
     }
 }
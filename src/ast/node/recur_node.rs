@@ -1,17 +1,32 @@
-use crate::ast::node::{Node, ToNode};
+use crate::ast::node::{Comments, Node, ToNode};
+use crate::ast::scanner::Location;
+use crate::ast::tag::Partition;
+use crate::defnode;
 use crate::prelude::*;
 
 #[derive(Debug, Clone)]
 pub struct RecurNode {
-    pub bindings: Vec<Tag>,
+    pub arguments: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
-impl RecurNode {}
+impl RecurNode {
+    pub fn arguments(&self) -> &Vec<Node> {
+        &self.arguments
+    }
+}
 
-impl ToNode for RecurNode {
-    fn make_node(_: Vec<Node>) -> Result<Node> {
-        // let (_, bindings) = tags.take_1().ok_or(General("Failed"))?;
-        todo!()
-        // Ok(Node::Recur(RecurNode { bindings }))
+defnode! {
+    Node::Recur : RecurNode :: nodes => {
+        let (_, arguments) = nodes.take_1().ok_or(InvalidForm {
+            form: "recur",
+            reason: "expected arguments",
+        })?;
+        Ok(RecurNode {
+            arguments,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
     }
 }
@@ -1,6 +1,11 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
+
 #[derive(Debug, Clone)]
 pub struct StringNode {
     literal: String,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl StringNode {
@@ -8,8 +13,9 @@ impl StringNode {
         // we need to do some string escaping here
         let mut literal = String::new();
         let mut escape = false;
+        let last = lexeme.chars().count() - 1;
         for (i, c) in lexeme.chars().enumerate() {
-            if i == 0 || i == lexeme.len() - 1 {
+            if i == 0 || i == last {
                 continue;
             }
             if escape {
@@ -29,7 +35,11 @@ impl StringNode {
             }
             literal.push(c);
         }
-        StringNode { literal }
+        StringNode {
+            literal,
+            location: Location::default(),
+            comments: Comments::default(),
+        }
     }
 
     pub fn value(&self) -> &str {
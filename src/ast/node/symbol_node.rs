@@ -1,7 +1,12 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
+
 #[derive(Debug, Clone)]
 pub struct SymbolNode {
     name: String,
     namespace: Option<String>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 impl SymbolNode {
@@ -13,6 +18,14 @@ impl SymbolNode {
         self.namespace.as_ref().map(|namespace| &namespace[..])
     }
 
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn comments(&self) -> &Comments {
+        &self.comments
+    }
+
     pub fn is_qualified(&self) -> bool {
         self.namespace.is_some()
     }
@@ -22,16 +35,22 @@ impl SymbolNode {
             SymbolNode {
                 name: String::from(value),
                 namespace: None,
+                location: Location::default(),
+                comments: Comments::default(),
             }
         } else if let Some(index) = value.find('/') {
             SymbolNode {
                 name: String::from(&value[index + 1..]),
                 namespace: Some(String::from(&value[..index])),
+                location: Location::default(),
+                comments: Comments::default(),
             }
         } else {
             SymbolNode {
                 name: String::from(value),
                 namespace: None,
+                location: Location::default(),
+                comments: Comments::default(),
             }
         }
     }
@@ -0,0 +1,33 @@
+use crate::ast::node::Comments;
+use crate::ast::node::Node;
+use crate::ast::scanner::Location;
+
+/// `~expression` inside a syntax quote, which is replaced by the value of
+/// `expression`. For `~@expression` the items of the value are spliced into
+/// the enclosing list or vector instead.
+#[derive(Debug, Clone)]
+pub struct UnquoteNode {
+    expression: Box<Node>,
+    splicing: bool,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
+}
+
+impl UnquoteNode {
+    pub fn new(expression: Node, splicing: bool) -> UnquoteNode {
+        UnquoteNode {
+            expression: Box::new(expression),
+            splicing,
+            location: Location::default(),
+            comments: Comments::default(),
+        }
+    }
+
+    pub fn expression(&self) -> &Node {
+        &self.expression
+    }
+
+    pub fn is_splicing(&self) -> bool {
+        self.splicing
+    }
+}
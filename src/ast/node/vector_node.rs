@@ -1,28 +1,46 @@
 use super::Node;
+use crate::ast::node::Comments;
 use crate::ast::node::ToNode;
+use crate::ast::scanner::Location;
 use crate::defnode;
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind;
 use std::convert::TryFrom;
 
 #[derive(Debug, Clone)]
 pub struct VectorNode {
     items: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 defnode! {
     Node::Vector : VectorNode :: nodes => {
-        Ok(VectorNode { items: nodes })
+        Ok(VectorNode {
+            items: nodes,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
     }
 }
 
 impl VectorNode {
     pub fn new(items: Vec<Node>) -> VectorNode {
-        println!("vecotr.items {:?}", items);
-        VectorNode { items }
+        VectorNode {
+            items,
+            location: Location::default(),
+            comments: Comments::default(),
+        }
     }
 
     pub fn items(&self) -> &Vec<Node> {
         self.items.as_ref()
     }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn comments(&self) -> &Comments {
+        &self.comments
+    }
 }
@@ -1,3 +1,5 @@
+use crate::ast::node::Comments;
+use crate::ast::scanner::Location;
 use crate::ast::{node::Node, node::ToNode, tag::Partition};
 use crate::defnode;
 use crate::prelude::*;
@@ -6,12 +8,22 @@ use crate::prelude::*;
 pub struct WhileNode {
     condition: Box<Node>,
     body: Vec<Node>,
+    pub(super) location: Location,
+    pub(super) comments: Comments,
 }
 
 defnode! {
     Node::While : WhileNode :: nodes => {
-        let (_, condition, body) = nodes.take_2().ok_or(CouldNotParseAtom)?;
-        Ok(WhileNode { condition: Box::new(condition), body })
+        let (_, condition, body) = nodes.take_2().ok_or(InvalidForm {
+            form: "while",
+            reason: "expected a condition",
+        })?;
+        Ok(WhileNode {
+            condition: Box::new(condition),
+            body,
+            location: Location::default(),
+            comments: Comments::default(),
+        })
     }
 }
 
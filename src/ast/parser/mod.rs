@@ -1,8 +1,8 @@
-use crate::prelude::*;
-use std::cell::Cell;
-use std::collections::HashMap;
+mod recur;
+
+use std::cell::{Cell, RefCell};
 use std::convert::TryFrom;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::result::runtime::ErrorKind as Error;
 use crate::result::Result;
@@ -10,14 +10,20 @@ use crate::result::Result;
 use super::node as n;
 use super::node::ToNode;
 use super::scanner::token::{Kind, Token};
-use super::Tag;
+use super::scanner::Location;
+
+/// How deep forms may nest. Everything after the parser walks forms by
+/// recursion, so deeper ones are refused here rather than overflowing the
+/// native stack later.
+pub const MAX_NESTING: usize = 1024;
+
+/// Numbers the symbols generated for `name#`, across every parse.
+static GENSYMS: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 enum Form {
     Call,
-    Index,
     Macro,
-    Special,
     While,
     If,
     Do,
@@ -26,57 +32,25 @@ enum Form {
     Fn,
     Loop,
     Recur,
-    QuasiQuote,
-}
-
-#[derive(Debug)]
-pub struct AST {
-    nodes: HashMap<Tag, n::Node>,
-    pub root: Option<Tag>,
-}
-
-impl AST {
-    fn new() -> AST {
-        AST {
-            nodes: HashMap::new(),
-            root: None,
-        }
-    }
-
-    pub fn get(&self, tag: &Tag) -> Option<&n::Node> {
-        self.nodes.get(tag)
-    }
-
-    fn len(&self) -> usize {
-        self.nodes.len()
-    }
-
-    fn insert(&mut self, id: Tag, node: n::Node) {
-        self.root = Some(id);
-        self.nodes.insert(id, node);
-    }
 }
 
 #[derive(Debug)]
 pub struct Parser {
-    ast: Mutex<AST>,
     position: Cell<usize>,
     tokens: Vec<Token>,
+    /// How many quotes enclose the expression being read.
+    quoted: Cell<usize>,
+    /// How many of those quotes are syntax quotes.
+    syntax: Cell<usize>,
+    /// The symbols generated for `name#` in the innermost syntax quote.
+    gensyms: RefCell<n::Gensyms>,
+    /// How many forms enclose the expression being read.
+    depth: Cell<usize>,
 }
 
 impl Parser {
-    fn eof(&self) -> bool {
-        let position = self.position.get();
-        position >= self.tokens.len() || self.tokens[position].kind == Kind::Eof
-    }
-
-    pub fn get_node(&self, tag: Tag) -> Option<n::Node> {
-        let ast = self.ast.lock().ok()?;
-        ast.get(&tag).cloned()
-    }
-
-    fn submit(&self, node: n::Node) -> Result<n::Node> {
-        return Ok(node);
+    fn submit(&self, node: n::Node, location: Location) -> Result<n::Node> {
+        Ok(node.at(location))
     }
 
     fn next(&self) {
@@ -104,7 +78,7 @@ impl Parser {
                 "if" if !node.is_qualified() => Form::If,
                 "do" if !node.is_qualified() => Form::Do,
                 "fn" if !node.is_qualified() => Form::Fn,
-                "let*" if !node.is_qualified() => Form::Let,
+                "let*" | "let" if !node.is_qualified() => Form::Let,
                 "defmacro" if !node.is_qualified() => Form::Macro,
                 _ => Form::Call,
             }
@@ -113,23 +87,80 @@ impl Parser {
         }
     }
 
-    fn take_until(&self, kind: Kind) -> Result<Vec<n::Node>> {
+    /// Takes the comments in front of the next token.
+    fn comments(&self) -> Vec<n::Comment> {
+        let mut comments = Vec::new();
+        while let Some(token) = self.peek().filter(|token| token.kind == Kind::Comment) {
+            comments.push(n::Comment {
+                text: token.lexeme.trim_end().to_string(),
+                location: token.location(),
+            });
+            self.next();
+        }
+        comments
+    }
+
+    /// Takes a comment on the line `location` ends on.
+    fn trailing(&self, location: Location) -> Option<n::Comment> {
+        let token = self.peek()?;
+        if token.kind != Kind::Comment || token.location().start.line != location.end.line {
+            return None;
+        }
+        self.next();
+        Some(n::Comment {
+            text: token.lexeme.trim_end().to_string(),
+            location: token.location(),
+        })
+    }
+
+    /// Collects expressions up to the closing `kind`, returning them with the
+    /// comments before the closing token and its location.
+    fn take_until(&self, kind: Kind) -> Result<(Vec<n::Node>, Vec<n::Comment>, Location)> {
         let mut nodes = Vec::new();
         loop {
+            let comments = self.comments();
             let token = self.peek().ok_or(Error::UnexpectedEof)?;
-            if token.kind == kind || token.kind == Kind::Eof {
-                self.take();
-                break;
+            if token.kind == Kind::Eof {
+                return Err(Error::UnexpectedEof.at(token.location()));
             }
-            nodes.push(self.expression()?);
+            if token.kind == kind {
+                self.next();
+                return Ok((nodes, comments, token.location()));
+            }
+            nodes.push(self.expression(comments)?);
         }
-        Ok(nodes)
     }
 
-    fn nested(&self) -> Result<n::Node> {
-        let mut tags = self.take_until(Kind::RightParen)?;
+    fn nested(&self, open: Location) -> Result<n::Node> {
+        let (mut tags, inner, close) = self.take_until(Kind::RightParen)?;
+        let location = open.to(close);
+        if self.quoted.get() > 0 {
+            let mut node = n::ListNode::make_node(tags)?;
+            node.comments_mut().inner = inner;
+            return self.submit(node, location);
+        }
 
-        self.submit(match self.special_form(&tags[0]) {
+        let head = tags.first().ok_or_else(|| {
+            Error::InvalidForm {
+                form: "call",
+                reason: "an empty list is not a call",
+            }
+            .at(location)
+        })?;
+        let form = self.special_form(head);
+        // The head of a special form is not kept, so its comments move to
+        // the form.
+        let mut comments = n::Comments::default();
+        if form != Form::Call {
+            let head = std::mem::take(tags[0].comments_mut());
+            comments.leading = head
+                .leading
+                .into_iter()
+                .chain(head.trailing.map(|comment| *comment))
+                .collect();
+        }
+        comments.inner = inner;
+        let node = match form {
             Form::Call => n::FunctionCallNode::make_node(tags),
             Form::While => n::WhileNode::make_node(tags),
             Form::If => n::IfNode::make_node(tags),
@@ -139,33 +170,108 @@ impl Parser {
             Form::Loop => n::LoopNode::make_node(tags),
             Form::Recur => n::RecurNode::make_node(tags),
             Form::Let => n::LetNode::make_node(tags),
-            Form::Macro => todo!("Macro node not implemented"),
-            Form::Index => todo!("implement index"),
-            Form::Special => todo!("special form"),
-            Form::QuasiQuote => todo!("quasi quote"),
-        }?)
+            Form::Macro => Err(Error::Unsupported("macros").into()),
+        };
+        let mut node = node.map_err(|error| error.at(location))?;
+        *node.comments_mut() = comments;
+        self.submit(node, location)
     }
 
-    fn vector(&self) -> Result<n::Node> {
-        Ok(n::Node::Vector(n::VectorNode::try_from(
-            self.take_until(Kind::RightBracket)?,
-        )?))
+    fn vector(&self, open: Location) -> Result<n::Node> {
+        let (items, inner, close) = self.take_until(Kind::RightBracket)?;
+        let location = open.to(close);
+        let vector = n::VectorNode::try_from(items).map_err(|error| error.at(location))?;
+        let mut node = n::Node::Vector(vector);
+        node.comments_mut().inner = inner;
+        self.submit(node, location)
     }
 
-    // fn escape_list(&self) -> Result<Tag> {
-    //     let item = self.expression()?;
-    //     self.submit(n::Node::QuasiQuote(n::QuasiQuoteNode::from_tag(item)))
-    // }
+    fn map(&self, open: Location) -> Result<n::Node> {
+        let (items, inner, close) = self.take_until(Kind::RightBrace)?;
+        let location = open.to(close);
+        let map = n::MapNode::try_from(items).map_err(|error| error.at(location))?;
+        let mut node = n::Node::Map(map);
+        node.comments_mut().inner = inner;
+        self.submit(node, location)
+    }
 
-    // fn unquote(&self) -> Result<Tag> {
-    //     let tag = self.expression()?;
-    //     self.submit(n::Node::Quote(QuoteNode::from_tag(tag)))
-    // }
+    /// Reads the next expression with `quoted` and `syntax` quotes around
+    /// it, restoring the depths afterwards.
+    fn datum_at(&self, quoted: usize, syntax: usize) -> Result<n::Node> {
+        let outer = (self.quoted.replace(quoted), self.syntax.replace(syntax));
+        let datum = self.datum();
+        self.quoted.set(outer.0);
+        self.syntax.set(outer.1);
+        datum
+    }
 
-    // fn quote(&self) -> Result<Tag> {
-    //     let expression = self.expression()?;
-    //     self.submit(n::Node::Quote(n::QuoteNode::from_tag(expression)))
-    // }
+    /// Wraps the expression read after a prefix like `'`, keeping the
+    /// comments between the two.
+    fn prefixed(
+        &self,
+        open: Location,
+        comments: Vec<n::Comment>,
+        expression: n::Node,
+        wrap: impl FnOnce(n::Node) -> n::Node,
+    ) -> Result<n::Node> {
+        let location = open.to(expression.location());
+        let mut node = wrap(expression);
+        node.comments_mut().leading = comments;
+        self.submit(node, location)
+    }
+
+    /// Reads the expression after `'` as data, so the lists in it are not
+    /// calls.
+    fn quote(&self, open: Location) -> Result<n::Node> {
+        let comments = self.comments();
+        let expression = self.datum_at(self.quoted.get() + 1, self.syntax.get())?;
+        self.prefixed(open, comments, expression, |expression| {
+            n::Node::Quote(n::QuoteNode::new(expression))
+        })
+    }
+
+    /// Reads the expression after `` ` `` as data like `quote`, except for
+    /// the `~` and `~@` forms in it. The symbols generated for the `name#`
+    /// in it are kept with the node.
+    fn quasi_quote(&self, open: Location) -> Result<n::Node> {
+        let comments = self.comments();
+        let outer = self.gensyms.take();
+        let expression = self.datum_at(self.quoted.get() + 1, self.syntax.get() + 1);
+        let gensyms = self.gensyms.replace(outer);
+        self.prefixed(open, comments, expression?, |expression| {
+            n::Node::QuasiQuote(n::QuasiQuoteNode::new(expression, gensyms))
+        })
+    }
+
+    /// Reads the expression after `~` or `~@` as code again.
+    fn unquote(&self, open: Location, splicing: bool) -> Result<n::Node> {
+        if self.syntax.get() == 0 {
+            let reason = "`~` and `~@` are only valid inside a syntax quote";
+            return Err(Error::InvalidForm {
+                form: "unquote",
+                reason,
+            }
+            .at(open));
+        }
+        let comments = self.comments();
+        let expression = self.datum_at(0, 0)?;
+        self.prefixed(open, comments, expression, |expression| {
+            n::Node::Unquote(n::UnquoteNode::new(expression, splicing))
+        })
+    }
+
+    /// Generates the symbol `name#` stands for in the current syntax quote,
+    /// once for each name.
+    fn gensym(&self, lexeme: &str) {
+        let name = &lexeme[..lexeme.len() - 1];
+        self.gensyms
+            .borrow_mut()
+            .entry(lexeme.to_string())
+            .or_insert_with(|| {
+                let id = GENSYMS.fetch_add(1, Ordering::Relaxed);
+                format!("{}__{}__auto__", name, id)
+            });
+    }
 
     // fn carrot(&self) -> Result<Tag> {
     //     self.submit(n::MetaNode::make_node(vec![
@@ -181,65 +287,124 @@ impl Parser {
     //     ])?)
     // }
 
-    fn expression(&self) -> Result<n::Node> {
+    /// Reads the next expression, with the comments before it and a comment
+    /// following it on the same line.
+    fn expression(&self, leading: Vec<n::Comment>) -> Result<n::Node> {
+        let mut node = self.datum()?;
+        let trailing = self.trailing(node.location());
+        let comments = node.comments_mut();
+        comments.leading = leading
+            .into_iter()
+            .chain(comments.leading.drain(..))
+            .collect();
+        comments.trailing = trailing.map(Box::new);
+        Ok(node)
+    }
+
+    fn datum(&self) -> Result<n::Node> {
+        let depth = self.depth.get();
+        if depth >= MAX_NESTING {
+            let location = self.peek().map(Token::location).unwrap_or_default();
+            return Err(Error::InvalidForm {
+                form: "expression",
+                reason: "forms nest too deep",
+            }
+            .at(location));
+        }
+        self.depth.set(depth + 1);
+        let datum = self.form();
+        self.depth.set(depth);
+        datum
+    }
+
+    fn form(&self) -> Result<n::Node> {
         let token = self.take()?;
+        let location = token.location();
         match token.kind {
             Kind::Symbol => match &token.lexeme[..] {
-                "nil" => self.submit(n::Node::Nil),
-                "true" => self.submit(n::Node::Boolean(n::BooleanNode(true))),
-                "false" => self.submit(n::Node::Boolean(n::BooleanNode(false))),
-                lexeme => self.submit(n::Node::Symbol(n::SymbolNode::from(lexeme))),
+                "nil" => self.submit(n::Node::Nil(n::NilNode::default()), location),
+                "true" => self.submit(n::Node::Boolean(n::BooleanNode::new(true)), location),
+                "false" => self.submit(n::Node::Boolean(n::BooleanNode::new(false)), location),
+                lexeme => {
+                    if self.syntax.get() > 0 && is_gensym(lexeme) {
+                        self.gensym(lexeme);
+                    }
+                    self.submit(n::Node::Symbol(n::SymbolNode::from(lexeme)), location)
+                }
             },
+            Kind::Keyword => {
+                let keyword = n::KeywordNode::from(&token.lexeme[..])
+                    .ok_or_else(|| Error::UnexpectedToken(token.lexeme.clone()).at(location))?;
+                self.submit(n::Node::Keyword(keyword), location)
+            }
             Kind::Number => {
-                let number: f64 = (&token.lexeme[..]).parse().expect("Failed to parse number");
-                self.submit(n::Node::Number(n::NumberNode(number)))
+                let number: f64 = token.lexeme[..]
+                    .parse()
+                    .map_err(|_| Error::InvalidNumber(token.lexeme.clone()).at(location))?;
+                self.submit(
+                    n::Node::Number(n::NumberNode::new(number, &token.lexeme)),
+                    location,
+                )
             }
             Kind::String => {
                 let lexeme = &token.lexeme[..];
-                self.submit(n::Node::String(n::StringNode::from(lexeme)))
+                self.submit(n::Node::String(n::StringNode::from(lexeme)), location)
             }
             // Kind::Carrot => self.carrot(),
-            // Kind::Quote => self.quote(),
+            Kind::Quote => self.quote(location),
             // Kind::Hash => self.decorator(),
-            Kind::LeftParen => self.nested(),
-            Kind::LeftBracket => self.vector(),
-            // Kind::BackTick => self.escape_list(),
-            // Kind::Unquote => self.unquote(),
-            kind => {
-                todo!("{:?}", kind);
-            }
+            Kind::LeftParen => self.nested(location),
+            Kind::LeftBracket => self.vector(location),
+            Kind::LeftBrace => self.map(location),
+            Kind::BackTick => self.quasi_quote(location),
+            Kind::Unquote => self.unquote(location, false),
+            Kind::UnquoteSplice => self.unquote(location, true),
+            Kind::Eof => Err(Error::UnexpectedEof.at(location)),
+            _ => Err(Error::UnexpectedToken(token.lexeme.clone()).at(location)),
         }
     }
 
     fn program(&self) -> Result<n::Node> {
         let mut expressions = Vec::new();
+        let mut location = Location::default();
+        let mut comments = Vec::new();
         while let Some(token) = self.peek() {
             match token.kind {
                 Kind::Comment => {
-                    self.take();
+                    comments = self.comments();
                 }
                 Kind::Eof => {
+                    location = location.to(token.location());
                     break;
                 }
                 _kind => {
-                    let e = self.expression()?;
+                    let e = self.expression(std::mem::take(&mut comments))?;
                     expressions.push(e);
                 }
             }
         }
-        self.submit(n::Node::Program(n::ProgramNode::new(expressions)))
+        let mut program = n::Node::Program(n::ProgramNode::new(expressions));
+        program.comments_mut().inner = comments;
+        self.submit(program, location)
     }
 }
 
+/// Whether a symbol asks a syntax quote for a generated symbol, `name#`.
+fn is_gensym(lexeme: &str) -> bool {
+    lexeme.len() > 1 && lexeme.ends_with('#') && !lexeme.contains('/')
+}
+
 pub fn parse(tokens: Vec<Token>) -> Result<n::Node> {
-    let mut parser = Parser {
-        ast: Mutex::new(AST::new()),
+    let parser = Parser {
         position: Cell::new(0),
-        tokens: tokens
-            .into_iter()
-            .filter(|token| token.kind != Kind::Comment)
-            .collect(),
+        tokens,
+        quoted: Cell::new(0),
+        syntax: Cell::new(0),
+        gensyms: RefCell::default(),
+        depth: Cell::new(0),
     };
 
-    return parser.program();
+    let program = parser.program()?;
+    recur::check(&program)?;
+    Ok(program)
 }
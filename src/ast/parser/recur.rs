@@ -0,0 +1,92 @@
+use crate::ast::node::*;
+use crate::result::runtime::ErrorKind as Error;
+use crate::result::Result;
+
+/// Checks that every `recur` appears in tail position of an enclosing `loop`
+/// or `fn` and passes as many values as that target binds. `recur` targets the
+/// innermost `loop` or `fn`, so each of those starts a new target.
+pub fn check(node: &Node) -> Result<()> {
+    walk(node, None, false)
+}
+
+fn function_arity(node: &FunctionNode) -> usize {
+    // A rest parameter is rebound from a single collection, so `[a & more]`
+    // recurs with two values.
+    node.parameters()
+        .items()
+        .iter()
+        .filter(|item| {
+            item.as_symbol()
+                .map(|symbol| symbol.is_qualified() || symbol.name() != "&")
+                .unwrap_or(true)
+        })
+        .count()
+}
+
+fn body(nodes: &[Node], target: Option<usize>, tail: bool) -> Result<()> {
+    let last = nodes.len().saturating_sub(1);
+    for (i, node) in nodes.iter().enumerate() {
+        walk(node, target, tail && i == last)?;
+    }
+    Ok(())
+}
+
+fn walk(node: &Node, target: Option<usize>, tail: bool) -> Result<()> {
+    match node {
+        Node::Recur(recur) => {
+            let expected = target
+                .ok_or(Error::InvalidRecur("recur must be inside a loop or fn"))
+                .map_err(|error| error.at(node.location()))?;
+            if !tail {
+                return Err(
+                    Error::InvalidRecur("recur must be in tail position").at(node.location())
+                );
+            }
+            if recur.arguments().len() != expected {
+                return Err(Error::InvalidRecurArity {
+                    expected,
+                    actual: recur.arguments().len(),
+                }
+                .at(node.location()));
+            }
+            body(recur.arguments(), target, false)
+        }
+        Node::If(node) => {
+            walk(&node.condition, target, false)?;
+            walk(&node.true_branch, target, tail)?;
+            walk(&node.false_branch, target, tail)
+        }
+        Node::Do(node) => body(node.expressions(), target, tail),
+        Node::Let(node) => {
+            for (_, value) in node.bindings() {
+                walk(value, target, false)?;
+            }
+            body(node.body(), target, tail)
+        }
+        Node::Loop(node) => {
+            for (_, value) in node.bindings() {
+                walk(value, target, false)?;
+            }
+            body(node.body(), Some(node.bindings().count()), true)
+        }
+        Node::Function(node) => body(node.body(), Some(function_arity(node)), true),
+        Node::While(node) => {
+            walk(node.condition(), target, false)?;
+            body(node.body(), target, false)
+        }
+        Node::FunctionCall(node) => {
+            walk(node.function(), target, false)?;
+            body(node.arguments(), target, false)
+        }
+        Node::Definition(node) => walk(node.value(), target, false),
+        Node::Program(node) => body(node.expressions(), target, false),
+        Node::Vector(node) => body(node.items(), target, false),
+        Node::Map(node) => body(node.items(), target, false),
+        // Quoted data only holds code in the `~` forms of a syntax quote.
+        Node::Quote(node) => walk(node.expression(), target, false),
+        Node::QuasiQuote(node) => walk(node.expression(), target, false),
+        Node::List(node) => body(node.items(), target, false),
+        Node::Unquote(node) => walk(node.expression(), target, false),
+        _ => Ok(()),
+    }
+}
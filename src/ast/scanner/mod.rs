@@ -2,8 +2,12 @@ mod span;
 pub mod token;
 
 use span::Span;
+pub use span::{Location, Position};
 use token::{Kind, Token};
 
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
 const EOF: char = '\0';
 const NL: char = '\n';
 
@@ -16,11 +20,10 @@ fn is_symbol_start(c: char) -> bool {
 }
 
 fn is_symbol_char(c: char) -> bool {
-    c.is_alphabetic() || c.is_digit(10) || "!$%&*_-+=|<>?".contains(c)
+    c.is_alphabetic() || c.is_ascii_digit() || "!$%&*_-+=|<>?".contains(c)
 }
 
 trait SymbolMatcher {
-    fn is_symbol_start(&self) -> bool;
     fn is_symbol_char(&self) -> bool;
     fn is_obj_delim(&self) -> bool;
     fn is_ns_delim(&self) -> bool;
@@ -37,10 +40,6 @@ pub struct Scanner {
 }
 
 impl SymbolMatcher for Scanner {
-    fn is_symbol_start(&self) -> bool {
-        self.check(is_symbol_start)
-    }
-
     fn is_symbol_char(&self) -> bool {
         self.check(is_symbol_char)
     }
@@ -60,12 +59,12 @@ impl SymbolMatcher for Scanner {
 
 impl NumberMatcher for Scanner {
     fn is_digit(&self) -> bool {
-        self.check(|char| char.is_digit(10))
+        self.check(|char| char.is_ascii_digit())
     }
 
     fn is_dot_digit(&self) -> bool {
         self.peek_2()
-            .map(|(dot, digit)| dot == '.' && digit.is_digit(10))
+            .map(|(dot, digit)| dot == '.' && digit.is_ascii_digit())
             .unwrap_or(false)
     }
 }
@@ -83,13 +82,13 @@ impl Scanner {
         self.span.view(&self.source[..]);
     }
 
-    fn make_token(&self, kind: Kind) -> Option<Token> {
+    fn make_token(&self, kind: Kind) -> Result<Token> {
         let span = self.span.clone();
-        self.span.view(&self.source[..]).map(|lexeme| Token {
-            kind,
-            lexeme: String::from(lexeme),
-            span,
-        })
+        let lexeme = self
+            .span
+            .view(&self.source[..])
+            .ok_or_else(|| ErrorKind::UnexpectedEof.at(span.location()))?;
+        Ok(Token { kind, lexeme, span })
     }
 
     fn eat(&self) {
@@ -97,7 +96,7 @@ impl Scanner {
     }
 
     fn peek_2(&self) -> Option<(char, char)> {
-        let ref source = &self.source[..];
+        let source = &self.source[..];
         let one = self.span.peek_n(source, 0)?;
         let two = self.span.peek_n(source, 1)?;
         Some((one, two))
@@ -120,19 +119,19 @@ impl Scanner {
             .unwrap_or(false)
     }
 
-    fn scan_comment(&self) -> Option<Token> {
+    fn scan_comment(&self) -> Result<Token> {
         while self.check_not(is_newline) {
             self.eat();
         }
         self.make_token(Kind::Comment)
     }
 
-    fn scan_delimiter(&self, kind: Kind) -> Option<Token> {
+    fn scan_delimiter(&self, kind: Kind) -> Result<Token> {
         self.eat();
         self.make_token(kind)
     }
 
-    fn scan_keyword(&self) -> Option<Token> {
+    fn scan_keyword(&self) -> Result<Token> {
         self.eat();
         if self.check(|c| ':' == c) {
             self.eat();
@@ -150,10 +149,10 @@ impl Scanner {
             self.eat();
             name();
         }
-        self.make_token(Kind::Symbol)
+        self.make_token(Kind::Keyword)
     }
 
-    fn scan_symbol(&self) -> Option<Token> {
+    fn scan_symbol(&self) -> Result<Token> {
         let name = || {
             while self.is_symbol_char() {
                 self.eat();
@@ -167,10 +166,14 @@ impl Scanner {
             self.eat();
             name();
         }
+        // `name#` asks a syntax quote for a generated symbol.
+        if self.check(|c| c == '#') {
+            self.eat();
+        }
         self.make_token(Kind::Symbol)
     }
 
-    fn scan_number(&self) -> Option<Token> {
+    fn scan_number(&self) -> Result<Token> {
         let digits = || {
             while self.is_digit() {
                 self.eat();
@@ -189,10 +192,12 @@ impl Scanner {
         self.span.peek(source)
     }
 
-    fn scan_string(&self) -> Option<Token> {
+    fn scan_string(&self) -> Result<Token> {
         self.eat();
         loop {
-            let c = self.peek()?;
+            let c = self
+                .peek()
+                .ok_or_else(|| ErrorKind::UnterminatedString.at(self.span.location()))?;
             match c {
                 '"' => {
                     break;
@@ -210,7 +215,7 @@ impl Scanner {
         self.make_token(Kind::String)
     }
 
-    pub fn scan(source: String) -> Option<Vec<Token>> {
+    pub fn scan(source: String) -> Result<Vec<Token>> {
         use Kind::*;
         let mut tokens = vec![];
         let scanner = Scanner::new(source);
@@ -228,7 +233,12 @@ impl Scanner {
                 }
                 '~' => {
                     scanner.eat();
-                    tokens.push(scanner.make_token(Kind::Unquote)?);
+                    if scanner.check(|c| c == '@') {
+                        scanner.eat();
+                        tokens.push(scanner.make_token(Kind::UnquoteSplice)?);
+                    } else {
+                        tokens.push(scanner.make_token(Kind::Unquote)?);
+                    }
                 }
                 '^' => {
                     scanner.eat();
@@ -257,13 +267,14 @@ impl Scanner {
                 '[' => tokens.push(scanner.scan_delimiter(LeftBracket)?),
                 ']' => tokens.push(scanner.scan_delimiter(RightBracket)?),
                 '{' => tokens.push(scanner.scan_delimiter(LeftBrace)?),
-                '}' => tokens.push(scanner.scan_delimiter(RightBracket)?),
+                '}' => tokens.push(scanner.scan_delimiter(RightBrace)?),
                 '"' => tokens.push(scanner.scan_string()?),
                 ':' => tokens.push(scanner.scan_keyword()?),
                 c if is_symbol_start(c) => tokens.push(scanner.scan_symbol()?),
-                c if c.is_digit(10) => tokens.push(scanner.scan_number()?),
+                c if c.is_ascii_digit() => tokens.push(scanner.scan_number()?),
                 c => {
-                    panic!("we should handle every character {:?} {:?}", c, c as u32);
+                    scanner.eat();
+                    return Err(ErrorKind::UnexpectedCharacter(c).at(scanner.span.location()));
                 }
             }
         }
@@ -274,6 +285,6 @@ impl Scanner {
             span: scanner.span.clone(),
         });
 
-        Some(tokens)
+        Ok(tokens)
     }
 }
@@ -1,11 +1,11 @@
 use std::cell::Cell;
 use std::fmt;
 
-#[derive(Debug, Copy, Clone)]
-struct Position {
-    offset: usize,
-    line: usize,
-    column: usize,
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
 impl fmt::Display for Position {
@@ -14,6 +14,12 @@ impl fmt::Display for Position {
     }
 }
 
+impl Default for Position {
+    fn default() -> Self {
+        Position::new()
+    }
+}
+
 impl Position {
     fn new() -> Position {
         Position {
@@ -40,6 +46,30 @@ impl Position {
     }
 }
 
+/// A plain copy of where a token or node sits in the source. Unlike `Span`
+/// it holds no cells, so it can be stored in nodes and errors.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Location {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Location {
+    /// The smallest location covering both `self` and `other`.
+    pub fn to(self, other: Location) -> Location {
+        Location {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start.line, self.start.column)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Span {
     start: Cell<Position>,
@@ -66,13 +96,17 @@ impl Span {
         (self.start.get().offset, self.end.get().offset)
     }
 
-    fn set(&self, (start, end): (usize, usize)) {
-        let mut pstart = self.start.get();
-        pstart.offset = start;
-        self.start.set(pstart);
-        let mut pend = self.end.get();
-        pend.offset = end;
-        self.start.set(pend);
+    /// Moves the start up to the end, keeping the line and column the end
+    /// has tracked.
+    fn shift(&self) {
+        self.start.set(self.end.get());
+    }
+
+    pub fn location(&self) -> Location {
+        Location {
+            start: self.start.get(),
+            end: self.end.get(),
+        }
     }
 
     pub fn left_grow(&self) -> &Self {
@@ -80,10 +114,10 @@ impl Span {
         self
     }
 
-    pub fn view<'s>(&self, source: &'s [char]) -> Option<String> {
+    pub fn view(&self, source: &[char]) -> Option<String> {
         let (start, end) = self.get();
-        if start != end && start >= 0 && end <= source.len() {
-            self.set((end, end));
+        if start != end && end <= source.len() {
+            self.shift();
             let mut result = String::new();
             for c in &source[start..end] {
                 result.push(*c);
@@ -109,12 +143,11 @@ impl Span {
     }
 
     pub fn advance(&self, source: &'_ [char]) -> Option<char> {
-        self.peek(source).map(|char| {
+        self.peek(source).inspect(|&char| {
             self.left_grow();
             if char == '\n' {
                 self.end.set(self.end.get().newline())
             }
-            char
         })
     }
 }
@@ -196,6 +229,20 @@ mod test {
         assert_eq!(span.view(&source[..]), Some(String::from("alex")));
     }
 
+    #[test]
+    fn it_should_track_lines_and_columns_across_views() {
+        let span = Span::new();
+        let source: Vec<_> = "ab\ncd".chars().collect();
+        span.advance(&source[..]);
+        span.advance(&source[..]);
+        span.advance(&source[..]);
+        span.view(&source[..]);
+        span.advance(&source[..]);
+        let location = span.location();
+        assert_eq!((location.start.line, location.start.column), (2, 1));
+        assert_eq!((location.end.line, location.end.column), (2, 2));
+    }
+
     #[test]
     fn is_should_handle_clonding() {
         let span = Span::new();
@@ -1,10 +1,11 @@
-use super::span::Span;
+use super::span::{Location, Span};
 use std::fmt;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Kind {
     Comment,
     Symbol,
+    Keyword,
     String,
     Number,
     Eof,
@@ -16,6 +17,7 @@ pub enum Kind {
     RightBrace,
     BackTick,
     Unquote,
+    UnquoteSplice,
     Carrot,
     Quote,
     Hash,
@@ -28,6 +30,12 @@ pub struct Token {
     pub span: Span,
 }
 
+impl Token {
+    pub fn location(&self) -> Location {
+        self.span.location()
+    }
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}\t\t{}\t\t{:?}", self.lexeme, self.span, self.kind)
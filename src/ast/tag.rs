@@ -49,6 +49,7 @@ pub struct Tag {
     id: Id,
 }
 
+#[allow(clippy::type_complexity)]
 pub trait Partition {
     type Item;
     fn take_1(self) -> Option<(Self::Item, Vec<Self::Item>)>;
@@ -67,25 +68,25 @@ pub trait Partition {
 
 impl<T> Partition for Vec<T> {
     type Item = T;
-    fn take_1(mut self) -> Option<(T, Vec<T>)> {
+    fn take_1(self) -> Option<(T, Vec<T>)> {
         let mut tags = self.into_iter();
         let one = tags.next()?;
         Some((one, tags.collect()))
     }
-    fn take_2(mut self) -> Option<(T, T, Vec<T>)> {
+    fn take_2(self) -> Option<(T, T, Vec<T>)> {
         let mut tags = self.into_iter();
         let one = tags.next()?;
         let two = tags.next()?;
         Some((one, two, tags.collect()))
     }
-    fn take_3(mut self) -> Option<(T, T, T, Vec<T>)> {
+    fn take_3(self) -> Option<(T, T, T, Vec<T>)> {
         let mut tags = self.into_iter();
         let one = tags.next()?;
         let two = tags.next()?;
         let three = tags.next()?;
         Some((one, two, three, tags.collect()))
     }
-    fn take_4(mut self) -> Option<(T, T, T, T, Vec<T>)> {
+    fn take_4(self) -> Option<(T, T, T, T, Vec<T>)> {
         let mut tags = self.into_iter();
         let one = tags.next()?;
         let two = tags.next()?;
@@ -97,10 +98,7 @@ impl<T> Partition for Vec<T> {
 
 impl Tag {
     pub fn is_vector(&self) -> bool {
-        match self.kind {
-            TagKind::Vector => true,
-            _ => false,
-        }
+        matches!(self.kind, TagKind::Vector)
     }
 
     pub fn on_symbol(&self) -> Option<&Tag> {
@@ -118,10 +116,7 @@ impl Tag {
     }
 
     pub fn is_symbol(&self) -> bool {
-        match self.kind {
-            TagKind::Symbol => true,
-            _ => false,
-        }
+        matches!(self.kind, TagKind::Symbol)
     }
 
     pub fn take_vector(self) -> Option<Self> {
@@ -132,13 +127,9 @@ impl Tag {
     }
 
     pub fn is_atom(&self) -> bool {
-        match self.kind {
-            TagKind::Nil => true,
-            TagKind::Boolean => true,
-            TagKind::Number => true,
-            TagKind::String => true,
-            TagKind::Symbol => true,
-            _ => false,
-        }
+        matches!(
+            self.kind,
+            TagKind::Nil | TagKind::Boolean | TagKind::Number | TagKind::String | TagKind::Symbol
+        )
     }
 }
@@ -0,0 +1,773 @@
+use super::{short, Chunk, Constant, LocalName, Op, Prototype, Upvalue};
+use crate::ast::node::*;
+use crate::ast::scanner::Location;
+use crate::interpreter::pattern::{Pattern, Patterns};
+use crate::interpreter::{Keyword, Symbol, Value};
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+use std::sync::Arc;
+
+/// Compiles `node` into the prototype of a script: a function of no
+/// arguments whose result is the value of the last top level expression.
+pub fn compile(node: &Node) -> Result<Arc<Prototype>> {
+    let mut compiler = Compiler {
+        states: vec![State::new(Some(String::from("script")))],
+    };
+    compiler.node(node, false)?;
+    compiler.emit(Op::Return, &[], node.location())?;
+    let mut state = compiler.states.pop().expect("the script state");
+    state.release(0);
+    Ok(Arc::new(state.prototype))
+}
+
+struct Local {
+    name: String,
+    slot: u16,
+    /// The index of its entry in the prototype's debug names.
+    debug: usize,
+}
+
+/// Where a `recur` jumps to: the values it passes replace the slots starting
+/// at `base` and execution resumes at `start`, which rebinds the patterns.
+struct Target {
+    base: u16,
+    count: usize,
+    start: usize,
+}
+
+/// The function being compiled. `height` mirrors the number of values the
+/// frame will hold at the current instruction, which is what gives every
+/// local its slot.
+struct State {
+    prototype: Prototype,
+    locals: Vec<Local>,
+    height: usize,
+    targets: Vec<Target>,
+}
+
+impl State {
+    fn new(name: Option<String>) -> State {
+        State {
+            prototype: Prototype {
+                name,
+                ..Prototype::default()
+            },
+            locals: Vec::new(),
+            // Slot 0 holds the closure being called.
+            height: 1,
+            targets: Vec::new(),
+        }
+    }
+
+    fn declare(&mut self, name: String, slot: u16) {
+        let offset = self.prototype.chunk.code.len();
+        self.locals.push(Local {
+            name: name.clone(),
+            slot,
+            debug: self.prototype.locals.len(),
+        });
+        self.prototype.locals.push(LocalName {
+            name,
+            slot,
+            start: offset,
+            end: offset,
+        });
+    }
+
+    /// Drops the locals declared after the first `count`, closing their
+    /// debug ranges at the current offset.
+    fn release(&mut self, count: usize) {
+        let offset = self.prototype.chunk.code.len();
+        for local in self.locals.drain(count..) {
+            self.prototype.locals[local.debug].end = offset;
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<u16> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|local| local.name == name)
+            .map(|local| local.slot)
+    }
+}
+
+struct Compiler {
+    states: Vec<State>,
+}
+
+impl Compiler {
+    fn state(&mut self) -> &mut State {
+        self.states.last_mut().expect("a function state")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state().prototype.chunk
+    }
+
+    fn emit(&mut self, op: Op, operands: &[u16], location: Location) -> Result<usize> {
+        let state = self.state();
+        let height = state.height as isize
+            + match (op, operands) {
+                (Op::Constant, _)
+                | (Op::Nil, _)
+                | (Op::True, _)
+                | (Op::False, _)
+                | (Op::GetLocal, _)
+                | (Op::GetUpvalue, _)
+                | (Op::GetGlobal, _)
+                | (Op::Qualify, _)
+                | (Op::Closure, _) => 1,
+                (Op::Pop, _)
+                | (Op::SetLocal, _)
+                | (Op::JumpIfFalse, _)
+                | (Op::Entry, _)
+                | (Op::Return, _) => -1,
+                (Op::Call, [count]) | (Op::TailCall, [count]) | (Op::Slide, [count]) => {
+                    -(*count as isize)
+                }
+                (Op::Map, [count]) | (Op::Vector, [count]) | (Op::List, [count]) => {
+                    1 - *count as isize
+                }
+                (Op::Splice, [count, _]) => 1 - *count as isize,
+                (Op::Destructure, [count, rest]) => *count as isize + *rest as isize - 1,
+                (Op::Rebind, [base, count]) => {
+                    *base as isize + *count as isize - state.height as isize
+                }
+                _ => 0,
+            };
+        state.height = height as usize;
+        short(state.height, "too many values on the stack")?;
+        Ok(state.prototype.chunk.write(op, operands, location))
+    }
+
+    fn constant(&mut self, value: Value, location: Location) -> Result<()> {
+        let index = self.chunk().add_constant(Constant::Value(value))?;
+        self.emit(Op::Constant, &[index], location)?;
+        Ok(())
+    }
+
+    fn jump(&mut self, op: Op, location: Location) -> Result<usize> {
+        self.emit(op, &[0], location)
+    }
+
+    /// Points the jump at `offset` to the next instruction.
+    fn patch(&mut self, offset: usize) -> Result<()> {
+        let distance = short(self.chunk().code.len() - offset - 3, "the jump is too long")?;
+        self.chunk().patch(offset, distance);
+        Ok(())
+    }
+
+    fn loop_to(&mut self, start: usize, location: Location) -> Result<()> {
+        let distance = short(self.chunk().code.len() + 3 - start, "the loop is too long")?;
+        self.emit(Op::Loop, &[distance], location)?;
+        Ok(())
+    }
+
+    /// Compiles `nodes` leaving the value of the last one on the stack.
+    fn sequence(&mut self, nodes: &[Node], tail: bool, location: Location) -> Result<()> {
+        match nodes.split_last() {
+            Some((last, init)) => {
+                for node in init {
+                    self.node(node, false)?;
+                    self.emit(Op::Pop, &[], node.location())?;
+                }
+                self.node(last, tail)
+            }
+            None => {
+                self.emit(Op::Nil, &[], location)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Compiles `node` leaving its value on the stack. In `tail` position
+    /// the value is returned straight from the function, so calls reuse the
+    /// frame.
+    fn node(&mut self, node: &Node, tail: bool) -> Result<()> {
+        let location = node.location();
+        let result = match node {
+            Node::Nil(..) => self.emit(Op::Nil, &[], location).map(drop),
+            Node::Boolean(node) if node.value() => self.emit(Op::True, &[], location).map(drop),
+            Node::Boolean(..) => self.emit(Op::False, &[], location).map(drop),
+            Node::Number(node) => self.constant(Value::make_number(node.value()), location),
+            Node::String(node) => self.constant(Value::make_string(node.value()), location),
+            Node::Symbol(node) => self.symbol(node, location),
+            Node::Program(node) => self.sequence(node.expressions(), false, location),
+            Node::Do(node) => self.sequence(node.expressions(), tail, location),
+            Node::If(node) => self.if_(node, tail, location),
+            Node::While(node) => self.while_(node, location),
+            Node::Definition(node) => self.definition(node, location),
+            Node::Function(node) => self.function(node, None, location),
+            Node::FunctionCall(node) => self.call(node, tail, location),
+            Node::Let(node) => self.let_(node, tail, location),
+            Node::Loop(node) => self.loop_(node, tail, location),
+            Node::Recur(node) => self.recur(node, location),
+            Node::Keyword(node) => self.keyword(node, location),
+            Node::List(..) => Err(ErrorKind::Unsupported("lists").into()),
+            Node::Vector(node) => self.vector(node, location),
+            Node::Map(node) => self.map(node, location),
+            Node::Quote(node) => self.quote(node.expression(), &Gensyms::new(), location),
+            Node::QuasiQuote(node) => self.quote(node.expression(), node.gensyms(), location),
+            Node::Unquote(..) => {
+                Err(ErrorKind::Internal("an unquote outside a syntax quote").into())
+            }
+            Node::Meta(..) => Err(ErrorKind::Unsupported("metadata forms").into()),
+            Node::Macro(..) => Err(ErrorKind::Unsupported("macros").into()),
+            Node::Decorator(..) => Err(ErrorKind::Unsupported("decorators").into()),
+        };
+        result.map_err(|error| error.at(location))
+    }
+
+    fn symbol(&mut self, node: &SymbolNode, location: Location) -> Result<()> {
+        if !node.is_qualified() {
+            let depth = self.states.len() - 1;
+            if let Some(slot) = self.states[depth].local(node.name()) {
+                self.emit(Op::GetLocal, &[slot], location)?;
+                return Ok(());
+            }
+            if let Some(index) = self.upvalue(depth, node.name())? {
+                self.emit(Op::GetUpvalue, &[index], location)?;
+                return Ok(());
+            }
+        }
+        let symbol = Value::Symbol(Symbol::from_node(node.clone()));
+        let index = self.chunk().add_constant(Constant::Value(symbol))?;
+        self.emit(Op::GetGlobal, &[index], location)?;
+        Ok(())
+    }
+
+    fn vector(&mut self, node: &VectorNode, location: Location) -> Result<()> {
+        for item in node.items() {
+            self.node(item, false)?;
+        }
+        let count = short(node.items().len(), "too many vector items")?;
+        self.emit(Op::Vector, &[count], location)?;
+        Ok(())
+    }
+
+    fn map(&mut self, node: &MapNode, location: Location) -> Result<()> {
+        for item in node.items() {
+            self.node(item, false)?;
+        }
+        let count = short(node.items().len(), "too many map entries")?;
+        self.emit(Op::Map, &[count], location)?;
+        Ok(())
+    }
+
+    /// Builds the data `node` reads as, like the tree walker's quote: symbols
+    /// become constants, collections are built from their quoted items and
+    /// unquotes are compiled as code.
+    fn quote(&mut self, node: &Node, gensyms: &Gensyms, location: Location) -> Result<()> {
+        match node {
+            Node::Symbol(node) => {
+                let symbol = match gensyms.get(node.name()) {
+                    Some(name) => Symbol::from(&name[..]),
+                    None => Symbol::from_node(node.clone()),
+                };
+                self.constant(Value::Symbol(symbol), location)
+            }
+            Node::List(node) => self.quoted(Op::List, node.items(), gensyms, location),
+            Node::Vector(node) => self.quoted(Op::Vector, node.items(), gensyms, location),
+            Node::Map(node) => self.quoted(Op::Map, node.items(), gensyms, location),
+            Node::Quote(node) => {
+                self.constant(Value::Symbol(Symbol::from("quote")), location)?;
+                self.quote(node.expression(), gensyms, node.expression().location())?;
+                self.emit(Op::List, &[2], location)?;
+                Ok(())
+            }
+            Node::Unquote(node) if node.is_splicing() => Err(ErrorKind::InvalidForm {
+                form: "unquote-splicing",
+                reason: "`~@` can only splice into a list or vector",
+            }
+            .into()),
+            Node::Unquote(node) => self.node(node.expression(), false),
+            node => self.node(node, false),
+        }
+    }
+
+    fn quoted(
+        &mut self,
+        op: Op,
+        items: &[Node],
+        gensyms: &Gensyms,
+        location: Location,
+    ) -> Result<()> {
+        let count = short(items.len(), "too many quoted items")?;
+        let splicing = op != Op::Map
+            && items
+                .iter()
+                .any(|item| item.as_unquote().is_some_and(UnquoteNode::is_splicing));
+        if !splicing {
+            for item in items {
+                self.quote(item, gensyms, item.location())?;
+            }
+            self.emit(op, &[count], location)?;
+            return Ok(());
+        }
+        // Each item becomes a sequence to join, a list of one unless it is
+        // spliced.
+        for item in items {
+            match item.as_unquote() {
+                Some(unquote) if unquote.is_splicing() => self.node(unquote.expression(), false)?,
+                _ => {
+                    self.quote(item, gensyms, item.location())?;
+                    self.emit(Op::List, &[1], item.location())?;
+                }
+            }
+        }
+        self.emit(Op::Splice, &[count, (op == Op::Vector) as u16], location)?;
+        Ok(())
+    }
+
+    fn keyword(&mut self, node: &KeywordNode, location: Location) -> Result<()> {
+        let keyword = Value::Keyword(Keyword::from_node(node));
+        if !node.is_expanding() {
+            return self.constant(keyword, location);
+        }
+        let index = self.chunk().add_constant(Constant::Value(keyword))?;
+        self.emit(Op::Qualify, &[index], location)?;
+        Ok(())
+    }
+
+    /// Resolves `name` in the functions enclosing `depth`, threading it
+    /// through each of them as an upvalue.
+    fn upvalue(&mut self, depth: usize, name: &str) -> Result<Option<u16>> {
+        if depth == 0 {
+            return Ok(None);
+        }
+        let upvalue = match self.states[depth - 1].local(name) {
+            Some(slot) => Upvalue {
+                local: true,
+                index: slot,
+            },
+            None => match self.upvalue(depth - 1, name)? {
+                Some(index) => Upvalue {
+                    local: false,
+                    index,
+                },
+                None => return Ok(None),
+            },
+        };
+        let upvalues = &mut self.states[depth].prototype.upvalues;
+        let index = match upvalues.iter().position(|existing| *existing == upvalue) {
+            Some(index) => index,
+            None => {
+                upvalues.push(upvalue);
+                upvalues.len() - 1
+            }
+        };
+        short(index, "too many captured values").map(Some)
+    }
+
+    fn if_(&mut self, node: &IfNode, tail: bool, location: Location) -> Result<()> {
+        self.node(&node.condition, false)?;
+        let height = self.state().height - 1;
+        let otherwise = self.jump(Op::JumpIfFalse, location)?;
+        self.node(&node.true_branch, tail)?;
+        let end = self.jump(Op::Jump, location)?;
+        self.patch(otherwise)?;
+        self.state().height = height;
+        self.node(&node.false_branch, tail)?;
+        self.patch(end)?;
+        self.state().height = height + 1;
+        Ok(())
+    }
+
+    fn while_(&mut self, node: &WhileNode, location: Location) -> Result<()> {
+        let start = self.chunk().code.len();
+        self.node(node.condition(), false)?;
+        let exit = self.jump(Op::JumpIfFalse, location)?;
+        for expression in node.body() {
+            self.node(expression, false)?;
+            self.emit(Op::Pop, &[], expression.location())?;
+        }
+        self.loop_to(start, location)?;
+        self.patch(exit)?;
+        self.emit(Op::Nil, &[], location)?;
+        Ok(())
+    }
+
+    fn definition(&mut self, node: &DefinitionNode, location: Location) -> Result<()> {
+        if node.ident().is_qualified() {
+            return Err(ErrorKind::InvalidForm {
+                form: "def",
+                reason: "the name can not be qualified",
+            }
+            .into());
+        }
+        match node.value() {
+            Node::Function(function) if function.name().is_none() => {
+                let label = node.ident().name().to_string();
+                self.function(function, Some(label), node.value().location())?
+            }
+            value => self.node(value, false)?,
+        }
+        let symbol = Value::Symbol(Symbol::from_node(node.ident().clone()));
+        let index = self.chunk().add_constant(Constant::Value(symbol))?;
+        self.emit(Op::DefineGlobal, &[index], location)?;
+        Ok(())
+    }
+
+    /// Compiles `node` into its own prototype and emits the closure over it.
+    /// Anonymous functions bound with `def` are labelled with the var name,
+    /// but only `(fn name [..] ..)` binds its own name inside the body.
+    fn function(
+        &mut self,
+        node: &FunctionNode,
+        label: Option<String>,
+        location: Location,
+    ) -> Result<()> {
+        let name = node.name().map(|name| name.name().to_string());
+        let parameters = Patterns::from_nodes(node.parameters().items())?;
+        self.states.push(State::new(name.clone().or(label)));
+        let result = self.function_body(node, name, &parameters, location);
+        let mut state = self.states.pop().expect("the function state");
+        result?;
+        state.release(0);
+        let index = self
+            .chunk()
+            .add_constant(Constant::Function(Arc::new(state.prototype)))?;
+        self.emit(Op::Closure, &[index], location)?;
+        Ok(())
+    }
+
+    fn function_body(
+        &mut self,
+        node: &FunctionNode,
+        name: Option<String>,
+        parameters: &Patterns,
+        location: Location,
+    ) -> Result<()> {
+        if let Some(name) = name {
+            self.state().declare(name, 0);
+        }
+        let count = parameters.items.len() + parameters.is_variadic() as usize;
+        let state = self.state();
+        state.prototype.arity = parameters.items.len();
+        state.prototype.variadic = parameters.is_variadic();
+        state.height += count;
+        state.targets.push(Target {
+            base: 1,
+            count,
+            start: 0,
+        });
+        let patterns = parameters.items.iter().chain(parameters.rest.as_deref());
+        for (slot, pattern) in patterns.enumerate() {
+            self.bind(pattern, short(slot + 1, "too many parameters")?, location)?;
+        }
+        self.sequence(node.body(), true, location)?;
+        self.emit(Op::Return, &[], location)?;
+        Ok(())
+    }
+
+    /// Binds `pattern` to the value in `slot`. Vector and map patterns push
+    /// the destructured values, which become the slots of their own patterns.
+    fn bind(&mut self, pattern: &Pattern, slot: u16, location: Location) -> Result<()> {
+        match pattern {
+            Pattern::Symbol(symbol) => {
+                self.state().declare(symbol.name().to_string(), slot);
+                Ok(())
+            }
+            Pattern::Vector(patterns) => {
+                let count = short(patterns.items.len(), "too many patterns")?;
+                let variadic = patterns.is_variadic() as u16;
+                let first = self.state().height;
+                self.emit(Op::GetLocal, &[slot], location)?;
+                self.emit(Op::Destructure, &[count, variadic], location)?;
+                let nested = patterns.items.iter().chain(patterns.rest.as_deref());
+                for (i, pattern) in nested.enumerate() {
+                    self.bind(pattern, short(first + i, "too many locals")?, location)?;
+                }
+                Ok(())
+            }
+            Pattern::Map(entries) => {
+                let first = self.state().height;
+                for (_, key) in entries {
+                    self.emit(Op::GetLocal, &[slot], location)?;
+                    self.node(key, false)?;
+                    self.emit(Op::Entry, &[], location)?;
+                }
+                for (i, (pattern, _)) in entries.iter().enumerate() {
+                    self.bind(pattern, short(first + i, "too many locals")?, location)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Evaluates the body with the locals introduced since `height` in
+    /// scope, then drops them from under the result.
+    fn scoped(
+        &mut self,
+        height: usize,
+        locals: usize,
+        body: &[Node],
+        tail: bool,
+        location: Location,
+    ) -> Result<()> {
+        self.sequence(body, tail, location)?;
+        let count = short(self.state().height - 1 - height, "too many locals")?;
+        if count > 0 {
+            self.emit(Op::Slide, &[count], location)?;
+        }
+        self.state().release(locals);
+        Ok(())
+    }
+
+    fn let_(&mut self, node: &LetNode, tail: bool, location: Location) -> Result<()> {
+        let height = self.state().height;
+        let locals = self.state().locals.len();
+        for (pattern, value) in node.bindings() {
+            let pattern = Pattern::from_node(pattern)?;
+            self.node(value, false)?;
+            let slot = short(self.state().height - 1, "too many locals")?;
+            self.bind(&pattern, slot, location)?;
+        }
+        self.scoped(height, locals, node.body(), tail, location)
+    }
+
+    /// A loop reserves one slot per binding for the values `recur` passes,
+    /// and binds the patterns from those slots on every iteration.
+    fn loop_(&mut self, node: &LoopNode, tail: bool, location: Location) -> Result<()> {
+        let height = self.state().height;
+        let locals = self.state().locals.len();
+        let base = short(height, "too many locals")?;
+        let mut patterns = Vec::new();
+        for (pattern, _) in node.bindings() {
+            patterns.push(Pattern::from_node(pattern)?);
+            self.emit(Op::Nil, &[], location)?;
+        }
+        for (i, (pattern, (_, value))) in patterns.iter().zip(node.bindings()).enumerate() {
+            self.node(value, false)?;
+            let slot = base + i as u16;
+            self.emit(Op::SetLocal, &[slot], location)?;
+            self.bind(pattern, slot, location)?;
+        }
+        let destructures = patterns
+            .iter()
+            .any(|pattern| !matches!(pattern, Pattern::Symbol(..)));
+        let start = if destructures {
+            let body = self.jump(Op::Jump, location)?;
+            let start = self.chunk().code.len();
+            let bound = self.state().height;
+            self.state().height = height + patterns.len();
+            for (i, pattern) in patterns.iter().enumerate() {
+                if !matches!(pattern, Pattern::Symbol(..)) {
+                    // The symbols were bound above, only the slots are needed.
+                    let locals = self.state().locals.len();
+                    let names = self.state().prototype.locals.len();
+                    self.bind(pattern, base + i as u16, location)?;
+                    let state = self.state();
+                    state.locals.truncate(locals);
+                    state.prototype.locals.truncate(names);
+                }
+            }
+            self.patch(body)?;
+            self.state().height = bound;
+            start
+        } else {
+            self.chunk().code.len()
+        };
+        self.state().targets.push(Target {
+            base,
+            count: patterns.len(),
+            start,
+        });
+        let result = self.scoped(height, locals, node.body(), tail, location);
+        self.state().targets.pop();
+        result
+    }
+
+    fn recur(&mut self, node: &RecurNode, location: Location) -> Result<()> {
+        let height = self.state().height;
+        let (base, count, start) = match self.state().targets.last() {
+            Some(target) => (target.base, target.count, target.start),
+            None => return Err(ErrorKind::InvalidRecur("recur must be inside a loop or fn").into()),
+        };
+        if node.arguments().len() != count {
+            return Err(ErrorKind::InvalidRecurArity {
+                expected: count,
+                actual: node.arguments().len(),
+            }
+            .into());
+        }
+        for argument in node.arguments() {
+            self.node(argument, false)?;
+        }
+        self.emit(Op::Rebind, &[base, count as u16], location)?;
+        self.loop_to(start, location)?;
+        // Nothing after the jump runs, but the enclosing form expects a value.
+        self.state().height = height + 1;
+        Ok(())
+    }
+
+    fn call(&mut self, node: &FunctionCallNode, tail: bool, location: Location) -> Result<()> {
+        self.node(node.function(), false)?;
+        for argument in node.arguments() {
+            self.node(argument, false)?;
+        }
+        let count = short(node.arguments().len(), "too many arguments")?;
+        let op = if tail { Op::TailCall } else { Op::Call };
+        self.emit(op, &[count], location)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn compile_source(source: &str) -> Result<Arc<Prototype>> {
+        let tokens = Scanner::scan(source.to_string())?;
+        compile(&parse(tokens)?)
+    }
+
+    fn ops(prototype: &Prototype) -> Vec<(Op, Vec<u16>)> {
+        prototype
+            .chunk
+            .instructions()
+            .unwrap()
+            .into_iter()
+            .map(|instruction| {
+                let count = instruction.op.operands();
+                (instruction.op, instruction.operands[..count].to_vec())
+            })
+            .collect()
+    }
+
+    fn function(prototype: &Prototype, index: usize) -> Arc<Prototype> {
+        match &prototype.chunk.constants[index] {
+            Constant::Function(function) => function.clone(),
+            constant => panic!("expected a function, found {:?}", constant),
+        }
+    }
+
+    #[test]
+    fn resolves_locals_to_slots() {
+        let script = compile_source("(let [a 1 [b & c] a] (+ a b))").unwrap();
+        assert_eq!(
+            ops(&script),
+            vec![
+                (Op::Constant, vec![0]),
+                (Op::GetLocal, vec![1]),
+                (Op::GetLocal, vec![2]),
+                (Op::Destructure, vec![1, 1]),
+                (Op::GetGlobal, vec![1]),
+                (Op::GetLocal, vec![1]),
+                (Op::GetLocal, vec![3]),
+                (Op::Call, vec![2]),
+                (Op::Slide, vec![4]),
+                (Op::Return, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn captures_enclosing_locals_as_upvalues() {
+        let script = compile_source("(fn outer [x] (fn [y] (fn [] (+ x y outer))))").unwrap();
+        let outer = function(&script, 0);
+        assert_eq!(outer.name(), "outer");
+        assert!(outer.upvalues.is_empty());
+        let middle = function(&outer, 0);
+        assert_eq!(
+            middle.upvalues,
+            vec![
+                Upvalue {
+                    local: true,
+                    index: 1
+                },
+                Upvalue {
+                    local: true,
+                    index: 0
+                },
+            ]
+        );
+        let inner = function(&middle, 0);
+        assert_eq!(
+            inner.upvalues,
+            vec![
+                Upvalue {
+                    local: false,
+                    index: 0
+                },
+                Upvalue {
+                    local: true,
+                    index: 1
+                },
+                Upvalue {
+                    local: false,
+                    index: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_in_tail_position_reuse_the_frame() {
+        let script = compile_source("(def f (fn [n] (if n (f n) (g n))))").unwrap();
+        let f = function(&script, 0);
+        assert_eq!(f.name(), "f");
+        let calls: Vec<_> = ops(&f)
+            .into_iter()
+            .filter(|(op, _)| matches!(op, Op::Call | Op::TailCall))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![(Op::TailCall, vec![1]), (Op::TailCall, vec![1])]
+        );
+        assert!(ops(&script).iter().all(|(op, _)| *op != Op::TailCall));
+    }
+
+    #[test]
+    fn recur_rebinds_loop_slots() {
+        let script = compile_source("(loop [i 0] (if (< i 3) (recur (+ i 1)) i))").unwrap();
+        let ops = ops(&script);
+        assert_eq!(
+            ops[0..3],
+            [
+                (Op::Nil, vec![]),
+                (Op::Constant, vec![0]),
+                (Op::SetLocal, vec![1])
+            ]
+        );
+        assert!(ops.contains(&(Op::Rebind, vec![1, 1])));
+        let jump = ops.iter().position(|(op, _)| *op == Op::Loop).unwrap();
+        assert_eq!(ops[jump - 1], (Op::Rebind, vec![1, 1]));
+        assert_eq!(ops.last(), Some(&(Op::Return, vec![])));
+    }
+
+    #[test]
+    fn recur_in_a_function_restarts_it() {
+        let script = compile_source("(fn [n & more] (recur n more))").unwrap();
+        let function = function(&script, 0);
+        assert_eq!((function.arity, function.variadic), (1, true));
+        let ops = ops(&function);
+        assert_eq!(ops[2], (Op::Rebind, vec![1, 2]));
+        assert_eq!(ops[3].0, Op::Loop);
+        let instructions = function.chunk.instructions().unwrap();
+        let offset = instructions[3].offset + 3 - instructions[3].operands[0] as usize;
+        assert_eq!(offset, 0);
+    }
+
+    #[test]
+    fn records_source_locations() {
+        let script = compile_source("(def a 1)\n(missing a)").unwrap();
+        let instructions = script.chunk.instructions().unwrap();
+        let call = instructions
+            .iter()
+            .find(|instruction| instruction.op == Op::Call)
+            .unwrap();
+        let location = script.chunk.location(call.offset).unwrap();
+        assert_eq!((location.start.line, location.start.column), (2, 1));
+    }
+
+    #[test]
+    fn rejects_unsupported_forms() {
+        let error = compile_source("(defmacro m [] 1)").unwrap_err();
+        assert!(matches!(error.kind(), Unsupported("macros")));
+        assert!(error.location().is_some());
+    }
+}
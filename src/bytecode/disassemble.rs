@@ -0,0 +1,130 @@
+use super::{Constant, Op, Prototype};
+use crate::result::Result;
+
+use std::fmt::Write;
+
+/// Renders `prototype` and every function nested in it as one instruction
+/// per line. With the `source` the chunk was compiled from, each run of
+/// instructions is preceded by the source line it came from.
+pub fn disassemble(prototype: &Prototype, source: Option<&str>) -> Result<String> {
+    let mut output = String::new();
+    function(&mut output, prototype, source)?;
+    Ok(output)
+}
+
+fn header(prototype: &Prototype) -> String {
+    let rest = if prototype.variadic { " & rest" } else { "" };
+    format!(
+        "== {} ({} parameters{}, {} upvalues) ==",
+        prototype.name(),
+        prototype.arity,
+        rest,
+        prototype.upvalues.len()
+    )
+}
+
+fn function(output: &mut String, prototype: &Prototype, source: Option<&str>) -> Result<()> {
+    let chunk = &prototype.chunk;
+    let lines: Vec<&str> = source
+        .map(|source| source.lines().collect())
+        .unwrap_or_default();
+    let mut current = None;
+    let _ = writeln!(output, "{}", header(prototype));
+    for instruction in chunk.instructions()? {
+        let location = chunk.location(instruction.offset);
+        let line = location.map(|location| location.start.line);
+        if line != current {
+            current = line;
+            if let Some(text) = line.and_then(|line| lines.get(line - 1)) {
+                let _ = writeln!(output, "{:>4} | {}", line.unwrap_or(0), text.trim_end());
+            }
+        }
+        let count = instruction.op.operands();
+        let operands = instruction.operands[..count]
+            .iter()
+            .map(|operand| operand.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let position = location
+            .map(|location| format!("{}:{}", location.start.line, location.start.column))
+            .unwrap_or_default();
+        let mut text = format!(
+            "{:04} {:>7}  {:<13}{}",
+            instruction.offset,
+            position,
+            format!("{:?}", instruction.op),
+            operands
+        );
+        let comment = comment(
+            prototype,
+            instruction.offset,
+            instruction.op,
+            count,
+            instruction.operands,
+        );
+        if let Some(comment) = comment {
+            text = format!("{:<36}; {}", text, comment);
+        }
+        let _ = writeln!(output, "{}", text.trim_end());
+    }
+    for constant in &chunk.constants {
+        if let Constant::Function(nested) = constant {
+            let _ = writeln!(output);
+            function(output, nested, source)?;
+        }
+    }
+    Ok(())
+}
+
+fn comment(
+    prototype: &Prototype,
+    offset: usize,
+    op: Op,
+    count: usize,
+    operands: [u16; 2],
+) -> Option<String> {
+    let [a, _] = operands;
+    let next = offset + 1 + count * 2;
+    match op {
+        Op::Constant | Op::GetGlobal | Op::DefineGlobal | Op::Qualify => {
+            match prototype.chunk.constants.get(a as usize)? {
+                Constant::Value(value) => Some(value.to_string()),
+                Constant::Function(function) => Some(function.name().to_string()),
+            }
+        }
+        Op::Closure => match prototype.chunk.constants.get(a as usize)? {
+            Constant::Function(function) => Some(format!("fn {}", function.name())),
+            Constant::Value(value) => Some(value.to_string()),
+        },
+        Op::GetLocal | Op::SetLocal => prototype.local_name(a, offset).map(String::from),
+        Op::Jump | Op::JumpIfFalse => Some(format!("-> {:04}", next + a as usize)),
+        Op::Loop => Some(format!("-> {:04}", next.saturating_sub(a as usize))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytecode::compile;
+    use crate::prelude::*;
+
+    #[test]
+    fn shows_instructions_under_their_source_lines() {
+        let source = "(def inc\n  (fn [n] (+ n 1)))\n(inc 41)";
+        let program = parse(Scanner::scan(source.to_string()).unwrap()).unwrap();
+        let output = disassemble(&compile(&program).unwrap(), Some(source)).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "== script (0 parameters, 0 upvalues) ==");
+        assert_eq!(lines[1], "   2 |   (fn [n] (+ n 1)))");
+        assert!(lines[2].starts_with("0000     2:3  Closure      0"));
+        assert!(lines[2].ends_with("; fn inc"));
+        assert!(output.contains("   3 | (inc 41)"));
+        assert!(output.contains("== inc (1 parameters, 0 upvalues) =="));
+        let local = output
+            .lines()
+            .find(|line| line.contains("GetLocal"))
+            .unwrap();
+        assert!(local.ends_with("; n"), "{}", local);
+    }
+}
@@ -0,0 +1,250 @@
+//! A compact instruction set for `Node` trees. Each function compiles to a
+//! `Prototype` holding a `Chunk` of bytes: a one byte opcode followed by its
+//! operands, each a little endian `u16`.
+
+mod compiler;
+mod disassemble;
+pub mod module;
+mod vm;
+
+pub use compiler::compile;
+pub use disassemble::disassemble;
+pub use module::Module;
+pub use vm::{run, Closure};
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::ast::scanner::Location;
+use crate::interpreter::Value;
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+macro_rules! opcodes {
+    ($($op:ident = $operands:literal),* $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum Op {
+            $($op),*
+        }
+
+        impl Op {
+            pub const ALL: &'static [Op] = &[$(Op::$op),*];
+
+            /// How many `u16` operands follow the opcode.
+            pub fn operands(self) -> usize {
+                match self {
+                    $(Op::$op => $operands),*
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    // Push `constants[a]`.
+    Constant = 1,
+    Nil = 0,
+    True = 0,
+    False = 0,
+    Pop = 0,
+    // Push the value in frame slot `a`.
+    GetLocal = 1,
+    // Pop into frame slot `a`.
+    SetLocal = 1,
+    // Push the closure's captured value `a`.
+    GetUpvalue = 1,
+    // Push the namespace binding named by the symbol `constants[a]`.
+    GetGlobal = 1,
+    // Pop a value, bind it to the symbol `constants[a]` and push the var.
+    DefineGlobal = 1,
+    // Push the keyword `constants[a]` qualified with the current namespace,
+    // for `::name`.
+    Qualify = 1,
+    // Jump `a` bytes forward.
+    Jump = 1,
+    // Pop a condition and jump `a` bytes forward when it is falsy.
+    JumpIfFalse = 1,
+    // Jump `a` bytes back.
+    Loop = 1,
+    // Call the function under `a` arguments.
+    Call = 1,
+    // Like `Call`, replacing the current frame.
+    TailCall = 1,
+    // Push a closure over the prototype `constants[a]`.
+    Closure = 1,
+    // Pop a vector and push its first `a` items, followed by a vector of
+    // the rest when `b` is 1.
+    Destructure = 2,
+    // Pop a key and a map and push the value of the key, or nil when the
+    // map has none.
+    Entry = 0,
+    // Move the top `b` values into slots `a..a + b` and drop everything
+    // above them.
+    Rebind = 2,
+    // Drop the `a` values under the top of the stack.
+    Slide = 1,
+    // Pop `a` keys and values, alternating, and push a map of them.
+    Map = 1,
+    // Pop `a` values and push a vector of them.
+    Vector = 1,
+    // Pop `a` values and push a list of them.
+    List = 1,
+    // Pop `a` sequences and push a list of all their items, or a vector
+    // when `b` is 1.
+    Splice = 2,
+    Return = 0,
+}
+
+impl TryFrom<u8> for Op {
+    type Error = crate::result::Error;
+
+    fn try_from(byte: u8) -> Result<Op> {
+        Op::ALL
+            .get(byte as usize)
+            .copied()
+            .ok_or_else(|| ErrorKind::Internal("unknown opcode").into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Value(Value),
+    Function(Arc<Prototype>),
+}
+
+/// A value captured when a closure is created: either a slot of the
+/// enclosing frame or one of the enclosing closure's own upvalues.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Upvalue {
+    pub local: bool,
+    pub index: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+    pub offset: usize,
+    pub op: Op,
+    pub operands: [u16; 2],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    /// The offsets at which the source location changes, in code order.
+    pub locations: Vec<(usize, Location)>,
+}
+
+impl Chunk {
+    /// Appends an instruction and returns its offset.
+    pub fn write(&mut self, op: Op, operands: &[u16], location: Location) -> usize {
+        let offset = self.code.len();
+        if self.locations.last().map(|(_, last)| *last) != Some(location) {
+            self.locations.push((offset, location));
+        }
+        self.code.push(op as u8);
+        for operand in operands {
+            self.code.extend_from_slice(&operand.to_le_bytes());
+        }
+        offset
+    }
+
+    /// Overwrites the first operand of the instruction at `offset`.
+    pub fn patch(&mut self, offset: usize, operand: u16) {
+        self.code[offset + 1..offset + 3].copy_from_slice(&operand.to_le_bytes());
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> Result<u16> {
+        self.constants.push(constant);
+        short(
+            self.constants.len() - 1,
+            "too many constants in one function",
+        )
+    }
+
+    pub fn read(&self, offset: usize) -> Result<Instruction> {
+        let op = Op::try_from(
+            *self
+                .code
+                .get(offset)
+                .ok_or(ErrorKind::Internal("read past the end of a chunk"))?,
+        )?;
+        let mut operands = [0; 2];
+        for (i, operand) in operands.iter_mut().take(op.operands()).enumerate() {
+            let at = offset + 1 + i * 2;
+            let bytes = self
+                .code
+                .get(at..at + 2)
+                .ok_or(ErrorKind::Internal("truncated instruction"))?;
+            *operand = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(Instruction {
+            offset,
+            op,
+            operands,
+        })
+    }
+
+    pub fn instructions(&self) -> Result<Vec<Instruction>> {
+        let mut instructions = Vec::new();
+        let mut offset = 0;
+        while offset < self.code.len() {
+            let instruction = self.read(offset)?;
+            offset += 1 + instruction.op.operands() * 2;
+            instructions.push(instruction);
+        }
+        Ok(instructions)
+    }
+
+    /// The source location of the instruction at `offset`.
+    pub fn location(&self, offset: usize) -> Option<Location> {
+        let index = match self.locations.binary_search_by_key(&offset, |(at, _)| *at) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        Some(self.locations[index].1)
+    }
+}
+
+/// The name bound to a frame slot between two code offsets, kept so
+/// disassembly and debuggers can show names instead of slot numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalName {
+    pub name: String,
+    pub slot: u16,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A compiled function. Slot 0 of its frame holds the closure being called,
+/// followed by one slot per parameter, with the rest parameter last.
+#[derive(Debug, Clone, Default)]
+pub struct Prototype {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub variadic: bool,
+    pub upvalues: Vec<Upvalue>,
+    pub chunk: Chunk,
+    pub locals: Vec<LocalName>,
+}
+
+impl Prototype {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("anonymous")
+    }
+
+    /// The name bound to `slot` at the instruction at `offset`.
+    pub fn local_name(&self, slot: u16, offset: usize) -> Option<&str> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|local| local.slot == slot && local.start <= offset && offset < local.end)
+            .map(|local| &local.name[..])
+    }
+}
+
+fn short(value: usize, reason: &'static str) -> Result<u16> {
+    u16::try_from(value).map_err(|_| ErrorKind::InvalidForm { form: "fn", reason }.into())
+}
@@ -0,0 +1,533 @@
+//! The `.ndc` format for precompiled modules. All integers are little
+//! endian. A module is a header followed by the script prototype:
+//!
+//! ```text
+//! module    = "NDC\0" version:u16 source:string prototype
+//! prototype = name:option<string> arity:u16 variadic:u8
+//!             upvalues:u16 (local:u8 index:u16)*
+//!             code:u32 byte*
+//!             constants:u16 constant*
+//!             lines:u32 (offset:u32 location)*
+//!             locals:u16 (name:string slot:u16 start:u32 end:u32)*
+//! constant  = 0 value | 1 prototype
+//! value     = 0                                  ; nil
+//!           | 1 u8                               ; boolean
+//!           | 2 f64                              ; number
+//!           | 3 string                           ; string
+//!           | 4 namespace:option<string> string  ; symbol
+//!           | 5 u32 value*                       ; vector
+//!           | 6 namespace:option<string> string  ; keyword
+//!           | 7 u32 (value value)*               ; map
+//! location  = (offset:u32 line:u32 column:u32){2}
+//! string    = u32 utf8*
+//! option<t> = 0 | 1 t
+//! ```
+
+use super::{Chunk, Constant, LocalName, Op, Prototype, Upvalue};
+use crate::ast::scanner::{Location, Position};
+use crate::interpreter::{Keyword, List, Map, Symbol, Value};
+use crate::result::runtime::ErrorKind;
+use crate::result::{Error, Result};
+
+use std::convert::TryFrom;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+pub const MAGIC: &[u8; 4] = b"NDC\0";
+pub const VERSION: u16 = 7;
+pub const EXTENSION: &str = "ndc";
+
+/// A compiled script along with the name of the file it was compiled from.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub source: String,
+    pub prototype: Arc<Prototype>,
+}
+
+fn invalid(reason: &'static str) -> Error {
+    ErrorKind::InvalidModule(reason).into()
+}
+
+fn narrow<T: TryFrom<usize>>(value: usize) -> Result<T> {
+    T::try_from(value).map_err(|_| invalid("a length does not fit the format"))
+}
+
+impl Module {
+    pub fn write(&self) -> Result<Vec<u8>> {
+        let mut writer = Writer { bytes: Vec::new() };
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u16(VERSION);
+        writer.string(&self.source)?;
+        writer.prototype(&self.prototype)?;
+        Ok(writer.bytes)
+    }
+
+    /// Decodes a module, checking that every instruction is well formed and
+    /// refers to constants of the right kind before anything runs.
+    pub fn read(bytes: &[u8]) -> Result<Module> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a compiled nomad module"));
+        }
+        if reader.u16()? != VERSION {
+            return Err(invalid("unsupported format version"));
+        }
+        let source = reader.string()?;
+        let prototype = reader.prototype()?;
+        if reader.offset != bytes.len() {
+            return Err(invalid("trailing bytes after the module"));
+        }
+        Ok(Module {
+            source,
+            prototype: Arc::new(prototype),
+        })
+    }
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: usize) -> Result<()> {
+        self.bytes
+            .extend_from_slice(&narrow::<u32>(value)?.to_le_bytes());
+        Ok(())
+    }
+
+    fn length(&mut self, value: usize) -> Result<()> {
+        self.u16(narrow(value)?);
+        Ok(())
+    }
+
+    fn string(&mut self, value: &str) -> Result<()> {
+        self.u32(value.len())?;
+        self.bytes.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    fn option(&mut self, value: Option<&str>) -> Result<()> {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.string(value)
+            }
+            None => {
+                self.u8(0);
+                Ok(())
+            }
+        }
+    }
+
+    fn location(&mut self, location: &Location) -> Result<()> {
+        for position in &[location.start, location.end] {
+            self.u32(position.offset)?;
+            self.u32(position.line)?;
+            self.u32(position.column)?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Nil => self.u8(0),
+            Value::Boolean(boolean) => {
+                self.u8(1);
+                self.u8(boolean.value as u8);
+            }
+            Value::Number(number) => {
+                self.u8(2);
+                self.bytes.extend_from_slice(&number.value.to_le_bytes());
+            }
+            Value::String(string) => {
+                self.u8(3);
+                self.string(&string.value)?;
+            }
+            Value::Symbol(symbol) => {
+                self.u8(4);
+                self.option(symbol.namespace())?;
+                self.string(symbol.name())?;
+            }
+            Value::Vector(vector) => {
+                self.u8(5);
+                self.u32(vector.len())?;
+                for item in vector.iter() {
+                    self.value(item)?;
+                }
+            }
+            Value::Keyword(keyword) => {
+                self.u8(6);
+                self.option(keyword.namespace())?;
+                self.string(keyword.name())?;
+            }
+            Value::Map(map) => {
+                self.u8(7);
+                self.u32(map.len())?;
+                for (key, value) in map.iter() {
+                    self.value(key)?;
+                    self.value(value)?;
+                }
+            }
+            Value::List(list) => {
+                self.u8(8);
+                self.u32(list.len())?;
+                for item in list.iter() {
+                    self.value(item)?;
+                }
+            }
+            Value::Lazy(..) => return Err(ErrorKind::Unsupported("lazy sequence constants").into()),
+            Value::Var(..) => return Err(ErrorKind::Unsupported("var constants").into()),
+            Value::Function(..) => {
+                return Err(ErrorKind::Unsupported("native function constants").into())
+            }
+        }
+        Ok(())
+    }
+
+    fn prototype(&mut self, prototype: &Prototype) -> Result<()> {
+        self.option(prototype.name.as_deref())?;
+        self.length(prototype.arity)?;
+        self.u8(prototype.variadic as u8);
+        self.length(prototype.upvalues.len())?;
+        for upvalue in &prototype.upvalues {
+            self.u8(upvalue.local as u8);
+            self.u16(upvalue.index);
+        }
+        let chunk = &prototype.chunk;
+        self.u32(chunk.code.len())?;
+        self.bytes.extend_from_slice(&chunk.code);
+        self.length(chunk.constants.len())?;
+        for constant in &chunk.constants {
+            match constant {
+                Constant::Value(value) => {
+                    self.u8(0);
+                    self.value(value)?;
+                }
+                Constant::Function(function) => {
+                    self.u8(1);
+                    self.prototype(function)?;
+                }
+            }
+        }
+        self.u32(chunk.locations.len())?;
+        for (offset, location) in &chunk.locations {
+            self.u32(*offset)?;
+            self.location(location)?;
+        }
+        self.length(prototype.locals.len())?;
+        for local in &prototype.locals {
+            self.string(&local.name)?;
+            self.u16(local.slot);
+            self.u32(local.start)?;
+            self.u32(local.end)?;
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + count)
+            .ok_or_else(|| invalid("unexpected end of module"))?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn flag(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("a flag must be 0 or 1")),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<usize> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self.u32()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("a string is not valid utf-8"))
+    }
+
+    fn option(&mut self) -> Result<Option<String>> {
+        if self.flag()? {
+            self.string().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn position(&mut self) -> Result<Position> {
+        Ok(Position {
+            offset: self.u32()?,
+            line: self.u32()?,
+            column: self.u32()?,
+        })
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.u8()? {
+            0 => Ok(Value::Nil),
+            1 => Ok(Value::make_bool(self.flag()?)),
+            2 => {
+                let bytes = self.take(8)?;
+                let mut number = [0; 8];
+                number.copy_from_slice(bytes);
+                Ok(Value::make_number(f64::from_le_bytes(number)))
+            }
+            3 => Ok(Value::make_string(&self.string()?)),
+            4 => {
+                let namespace = self.option()?;
+                let name = self.string()?;
+                Ok(Value::Symbol(Symbol { name, namespace }))
+            }
+            5 => {
+                let length = self.u32()?;
+                let mut items = Vec::new();
+                for _ in 0..length {
+                    items.push(self.value()?);
+                }
+                Ok(Value::from_iter(items))
+            }
+            6 => {
+                let namespace = self.option()?;
+                let name = self.string()?;
+                Ok(Value::Keyword(Keyword::intern(namespace.as_deref(), &name)))
+            }
+            7 => {
+                let length = self.u32()?;
+                let mut map = Map::new();
+                for _ in 0..length {
+                    map = map.insert(self.value()?, self.value()?);
+                }
+                Ok(Value::Map(map))
+            }
+            8 => {
+                let length = self.u32()?;
+                let mut items = Vec::new();
+                for _ in 0..length {
+                    items.push(self.value()?);
+                }
+                Ok(Value::List(List::from_iter(items)))
+            }
+            _ => Err(invalid("unknown value tag")),
+        }
+    }
+
+    fn prototype(&mut self) -> Result<Prototype> {
+        let name = self.option()?;
+        let arity = self.u16()? as usize;
+        let variadic = self.flag()?;
+        let mut upvalues = Vec::new();
+        for _ in 0..self.u16()? {
+            upvalues.push(Upvalue {
+                local: self.flag()?,
+                index: self.u16()?,
+            });
+        }
+        let length = self.u32()?;
+        let code = self.take(length)?.to_vec();
+        let mut constants = Vec::new();
+        for _ in 0..self.u16()? {
+            constants.push(match self.u8()? {
+                0 => Constant::Value(self.value()?),
+                1 => Constant::Function(Arc::new(self.prototype()?)),
+                _ => return Err(invalid("unknown constant tag")),
+            });
+        }
+        let mut locations = Vec::new();
+        for _ in 0..self.u32()? {
+            let offset = self.u32()?;
+            let start = self.position()?;
+            let end = self.position()?;
+            locations.push((offset, Location { start, end }));
+        }
+        let mut locals = Vec::new();
+        for _ in 0..self.u16()? {
+            locals.push(LocalName {
+                name: self.string()?,
+                slot: self.u16()?,
+                start: self.u32()?,
+                end: self.u32()?,
+            });
+        }
+        let prototype = Prototype {
+            name,
+            arity,
+            variadic,
+            upvalues,
+            chunk: Chunk {
+                code,
+                constants,
+                locations,
+            },
+            locals,
+        };
+        validate(&prototype)?;
+        Ok(prototype)
+    }
+}
+
+/// Checks that the code decodes, that jumps land on instructions and that
+/// operands refer to constants and upvalues that exist.
+fn validate(prototype: &Prototype) -> Result<()> {
+    let chunk = &prototype.chunk;
+    let instructions = chunk
+        .instructions()
+        .map_err(|_| invalid("the code does not decode"))?;
+    let boundary = |target: Option<usize>| match target {
+        Some(target) if target == chunk.code.len() => true,
+        Some(target) => instructions
+            .binary_search_by_key(&target, |instruction| instruction.offset)
+            .is_ok(),
+        None => false,
+    };
+    for instruction in &instructions {
+        let [a, _] = instruction.operands;
+        let next = instruction.offset + 1 + instruction.op.operands() * 2;
+        let constant = chunk.constants.get(a as usize);
+        let valid = match instruction.op {
+            Op::Constant => matches!(constant, Some(Constant::Value(..))),
+            Op::GetGlobal | Op::DefineGlobal => {
+                matches!(constant, Some(Constant::Value(Value::Symbol(..))))
+            }
+            Op::Qualify => matches!(constant, Some(Constant::Value(Value::Keyword(..)))),
+            Op::Closure => matches!(constant, Some(Constant::Function(..))),
+            Op::GetUpvalue => (a as usize) < prototype.upvalues.len(),
+            Op::Jump | Op::JumpIfFalse => boundary(next.checked_add(a as usize)),
+            Op::Loop => boundary(next.checked_sub(a as usize)),
+            _ => true,
+        };
+        if !valid {
+            return Err(invalid("an instruction has an invalid operand"));
+        }
+    }
+    if instructions.last().map(|instruction| instruction.op) != Some(Op::Return) {
+        return Err(invalid("the code does not end with a return"));
+    }
+    let offsets = chunk.locations.iter().map(|(offset, _)| *offset);
+    if offsets.clone().zip(offsets.skip(1)).any(|(a, b)| a >= b) {
+        return Err(invalid("the line table is out of order"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytecode::compile;
+    use crate::prelude::*;
+
+    fn module(source: &str) -> Module {
+        let program = parse(Scanner::scan(source.to_string()).unwrap()).unwrap();
+        Module {
+            source: "test.nd".to_string(),
+            prototype: compile(&program).unwrap(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let original = module("(def f (fn [x & xs] (let [[a] xs] (+ x a)))) (println \"hi\" nil true 1.5 nomad.core/f)");
+        let bytes = original.write().unwrap();
+        let loaded = Module::read(&bytes).unwrap();
+        assert_eq!(loaded.source, "test.nd");
+        assert_eq!(loaded.write().unwrap(), bytes);
+        let interpreter = Interpreter::boot().unwrap();
+        crate::bytecode::run(loaded.prototype, &interpreter).unwrap();
+        let value = interpreter
+            .eval(&parse(Scanner::scan("(f 1 2)".to_string()).unwrap()).unwrap())
+            .unwrap();
+        assert_eq!(value.to_string(), "3");
+    }
+
+    #[test]
+    fn writes_every_literal_kind() {
+        let values = vec![
+            Value::Nil,
+            Value::make_bool(false),
+            Value::make_number(-0.25),
+            Value::make_string("a \"string\""),
+            Value::Symbol(Symbol::from(("ns", "name"))),
+            Value::Keyword(Keyword::intern(Some("ns"), "name")),
+            Value::Keyword(Keyword::intern(None, "name")),
+            Value::Map(Map::new().insert(Value::make_number(1.0), Value::Nil)),
+            Value::from_iter(vec![Value::make_number(1.0), Value::from_iter(vec![])]),
+            Value::List(List::from_iter(vec![Value::Symbol(Symbol::from("a"))])),
+        ];
+        let mut writer = Writer { bytes: Vec::new() };
+        for value in &values {
+            writer.value(value).unwrap();
+        }
+        let mut reader = Reader {
+            bytes: &writer.bytes,
+            offset: 0,
+        };
+        for value in &values {
+            let read = reader.value().unwrap();
+            assert_eq!(read.to_string(), value.to_string());
+            assert_eq!(read.type_name(), value.type_name());
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_modules() {
+        let bytes = module("(def a (fn [x] x)) (a 1)").write().unwrap();
+        let reason = |bytes: &[u8]| match Module::read(bytes) {
+            Err(error) => match *error.kind {
+                InvalidModule(reason) => reason,
+                kind => panic!("expected an invalid module, found {:?}", kind),
+            },
+            Ok(..) => panic!("expected an error"),
+        };
+        assert_eq!(reason(b"#!/bin/sh"), "not a compiled nomad module");
+        let mut version = bytes.clone();
+        version[4] = 99;
+        assert_eq!(reason(&version), "unsupported format version");
+        assert_eq!(
+            reason(&bytes[..bytes.len() - 1]),
+            "unexpected end of module"
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(reason(&trailing), "trailing bytes after the module");
+
+        let mut prototype = (*module("(a 1)").prototype).clone();
+        prototype.chunk.code[1] = 9;
+        let corrupt = Module {
+            source: String::new(),
+            prototype: Arc::new(prototype),
+        };
+        assert_eq!(
+            reason(&corrupt.write().unwrap()),
+            "an instruction has an invalid operand"
+        );
+    }
+}
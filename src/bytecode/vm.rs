@@ -0,0 +1,529 @@
+use super::{Constant, Op, Prototype};
+use crate::interpreter::frame::Frame;
+use crate::interpreter::pattern::{destructure_lazy, entry};
+use crate::interpreter::{
+    apply, Arity, Function, Interpreter, Introspection, Map, Seq, Symbol, Value, MAX_DEPTH,
+};
+use crate::result::runtime::ErrorKind;
+use crate::result::{Error, Result};
+
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+/// A compiled function along with the values it captured when it was
+/// created. Locals never change once bound, so capturing by value is
+/// indistinguishable from capturing the variable.
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub prototype: Arc<Prototype>,
+    pub upvalues: Arc<Vec<Value>>,
+}
+
+impl Function for Closure {
+    fn arity(&self) -> Arity {
+        if self.prototype.variadic {
+            Arity::AtLeast(self.prototype.arity)
+        } else {
+            Arity::Fixed(self.prototype.arity)
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.prototype.name()
+    }
+
+    fn curried(&self) -> bool {
+        true
+    }
+
+    fn call(&self, parameters: Vec<Value>, interpreter: &Interpreter) -> Result<Value> {
+        let mut vm = Vm::new(interpreter);
+        vm.stack.push(self.clone().into());
+        vm.stack.extend(parameters);
+        vm.enter(self.clone(), 0)?;
+        vm.run()
+    }
+
+    fn closure(&self) -> Option<&Closure> {
+        Some(self)
+    }
+}
+
+/// Runs the script `prototype` against the namespaces of `interpreter`.
+pub fn run(prototype: Arc<Prototype>, interpreter: &Interpreter) -> Result<Value> {
+    let script = Closure {
+        prototype,
+        upvalues: Arc::new(Vec::new()),
+    };
+    script.call(Vec::new(), interpreter)
+}
+
+struct Vm<'a> {
+    interpreter: &'a Interpreter,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+fn internal(reason: &'static str) -> Error {
+    ErrorKind::Internal(reason).into()
+}
+
+impl<'a> Vm<'a> {
+    fn new(interpreter: &'a Interpreter) -> Vm<'a> {
+        Vm {
+            interpreter,
+            stack: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    fn frame(&mut self) -> Result<&mut Frame> {
+        self.frames.last_mut().ok_or_else(|| internal("no frame"))
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        self.stack
+            .pop()
+            .ok_or_else(|| internal("the stack is empty"))
+    }
+
+    fn slot(&self, index: usize) -> Result<&Value> {
+        self.stack
+            .get(index)
+            .ok_or_else(|| internal("no value in slot"))
+    }
+
+    /// Pushes a frame for `closure`, whose callee and arguments start at
+    /// `base`. Arguments past the arity are collected for the rest parameter.
+    fn enter(&mut self, closure: Closure, base: usize) -> Result<()> {
+        if closure.prototype.variadic {
+            let rest = self.stack.split_off(base + 1 + closure.prototype.arity);
+            self.stack.push(Value::from_iter(rest));
+        }
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(ErrorKind::StackOverflow(MAX_DEPTH).into());
+        }
+        self.frames.push(Frame::new(closure, base));
+        Ok(())
+    }
+
+    /// Calls the function under `count` arguments. Closures that accept the
+    /// arguments run in a new frame, everything else goes through `apply`.
+    fn call(&mut self, count: usize) -> Result<()> {
+        let base = self
+            .stack
+            .len()
+            .checked_sub(count + 1)
+            .ok_or_else(|| internal("missing call arguments"))?;
+        let function = self.stack[base].callable()?;
+        match function.closure() {
+            Some(closure) if function.arity().accepts(count) => self.enter(closure.clone(), base),
+            _ => {
+                let arguments = self.stack.split_off(base + 1);
+                self.stack.truncate(base);
+                let value = apply(&function, arguments, self.interpreter)?;
+                self.stack.push(value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Pops the current frame, leaving `value` where its callee was. Returns
+    /// whether that was the outermost frame.
+    fn ret(&mut self, value: Value) -> Result<bool> {
+        let frame = self.frames.pop().ok_or_else(|| internal("no frame"))?;
+        self.stack.truncate(frame.base);
+        self.stack.push(value);
+        Ok(self.frames.is_empty())
+    }
+
+    fn run(&mut self) -> Result<Value> {
+        loop {
+            let (prototype, ip) = {
+                let frame = self.frame()?;
+                (frame.closure.prototype.clone(), frame.ip)
+            };
+            let location = prototype.chunk.location(ip);
+            let result = self.step(&prototype, ip);
+            match result {
+                Ok(true) => return self.pop(),
+                Ok(false) => {}
+                Err(error) => {
+                    return Err(match location {
+                        Some(location) => error.at(location),
+                        None => error,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Executes the instruction at `ip`, returning whether the outermost
+    /// frame returned.
+    fn step(&mut self, prototype: &Prototype, ip: usize) -> Result<bool> {
+        let instruction = prototype.chunk.read(ip)?;
+        let next = ip + 1 + instruction.op.operands() * 2;
+        self.frame()?.ip = next;
+        let [a, b] = instruction.operands;
+        let (a, b) = (a as usize, b as usize);
+        let base = self.frame()?.base;
+        match instruction.op {
+            Op::Constant => {
+                let value = match prototype.chunk.constants.get(a) {
+                    Some(Constant::Value(value)) => value.clone(),
+                    _ => return Err(internal("expected a value constant")),
+                };
+                self.stack.push(value);
+            }
+            Op::Nil => self.stack.push(Value::Nil),
+            Op::True => self.stack.push(Value::make_bool(true)),
+            Op::False => self.stack.push(Value::make_bool(false)),
+            Op::Pop => {
+                self.pop()?;
+            }
+            Op::GetLocal => {
+                let value = self.slot(base + a)?.clone();
+                self.stack.push(value);
+            }
+            Op::SetLocal => {
+                let value = self.pop()?;
+                *self
+                    .stack
+                    .get_mut(base + a)
+                    .ok_or_else(|| internal("no value in slot"))? = value;
+            }
+            Op::GetUpvalue => {
+                let value = self
+                    .frame()?
+                    .closure
+                    .upvalues
+                    .get(a)
+                    .cloned()
+                    .ok_or_else(|| internal("no such upvalue"))?;
+                self.stack.push(value);
+            }
+            Op::GetGlobal => {
+                let value = self.interpreter.resolve(symbol(prototype, a)?)?;
+                self.stack.push(value);
+            }
+            Op::DefineGlobal => {
+                let value = self.pop()?;
+                let var = self
+                    .interpreter
+                    .define(symbol(prototype, a)?.clone(), value)?;
+                self.stack.push(var);
+            }
+            Op::Qualify => {
+                let keyword = match prototype.chunk.constants.get(a) {
+                    Some(Constant::Value(Value::Keyword(keyword))) => keyword,
+                    _ => return Err(internal("expected a keyword constant")),
+                };
+                let keyword = self.interpreter.expand(keyword)?;
+                self.stack.push(Value::Keyword(keyword));
+            }
+            Op::Jump => self.frame()?.ip = next + a,
+            Op::JumpIfFalse => {
+                if self.pop()?.falsy() {
+                    self.frame()?.ip = next + a;
+                }
+            }
+            Op::Loop => self.frame()?.ip = next - a,
+            Op::Call => self.call(a)?,
+            Op::TailCall => {
+                let frames = self.frames.len();
+                let start = self
+                    .stack
+                    .len()
+                    .checked_sub(a + 1)
+                    .ok_or_else(|| internal("missing call arguments"))?;
+                let callee = self.stack.split_off(start);
+                self.stack.truncate(base);
+                self.stack.extend(callee);
+                self.frames.pop();
+                self.call(a)?;
+                // A callee that was not entered has already left its value
+                // in place of the frame, which now returns.
+                if self.frames.len() < frames {
+                    return Ok(self.frames.is_empty());
+                }
+            }
+            Op::Closure => {
+                let function = match prototype.chunk.constants.get(a) {
+                    Some(Constant::Function(function)) => function.clone(),
+                    _ => return Err(internal("expected a function constant")),
+                };
+                let mut upvalues = Vec::with_capacity(function.upvalues.len());
+                for upvalue in &function.upvalues {
+                    let index = upvalue.index as usize;
+                    let value = if upvalue.local {
+                        self.slot(base + index)?.clone()
+                    } else {
+                        self.frame()?
+                            .closure
+                            .upvalues
+                            .get(index)
+                            .cloned()
+                            .ok_or_else(|| internal("no such upvalue"))?
+                    };
+                    upvalues.push(value);
+                }
+                let closure = Closure {
+                    prototype: function,
+                    upvalues: Arc::new(upvalues),
+                };
+                self.stack.push(closure.into());
+            }
+            Op::Destructure => {
+                let items: Vec<Value> = match self.pop()? {
+                    Value::Vector(vector) => vector.iter().cloned().collect(),
+                    Value::List(list) => list.iter().cloned().collect(),
+                    // A rest pattern gets what is left of a lazy sequence
+                    // unrealized.
+                    seq @ Value::Lazy(..) => {
+                        let (items, rest) = destructure_lazy(seq, a, b == 1)?;
+                        self.stack.extend(items);
+                        if b == 1 {
+                            self.stack.push(rest);
+                        }
+                        return Ok(false);
+                    }
+                    _ => {
+                        return Err(ErrorKind::BindingMismatch(
+                            "vector patterns can only destructure sequences",
+                        )
+                        .into())
+                    }
+                };
+                if items.len() < a {
+                    return Err(ErrorKind::BindingMismatch("too few values for pattern").into());
+                }
+                if b == 0 && items.len() > a {
+                    return Err(ErrorKind::BindingMismatch("too many values for pattern").into());
+                }
+                let mut items = items.into_iter();
+                self.stack.extend(items.by_ref().take(a));
+                if b == 1 {
+                    self.stack.push(Value::from_iter(items));
+                }
+            }
+            Op::Entry => {
+                let key = self.pop()?;
+                let map = self.pop()?;
+                self.stack.push(entry(&map, key)?);
+            }
+            Op::Rebind => {
+                let start = self
+                    .stack
+                    .len()
+                    .checked_sub(b)
+                    .ok_or_else(|| internal("missing recur values"))?;
+                let values = self.stack.split_off(start);
+                self.stack.truncate(base + a);
+                self.stack.extend(values);
+            }
+            Op::Slide => {
+                let value = self.pop()?;
+                let length = self
+                    .stack
+                    .len()
+                    .checked_sub(a)
+                    .ok_or_else(|| internal("slid past the stack"))?;
+                self.stack.truncate(length);
+                self.stack.push(value);
+            }
+            Op::Map => {
+                let length = self
+                    .stack
+                    .len()
+                    .checked_sub(a)
+                    .ok_or_else(|| internal("missing map entries"))?;
+                let mut items = self.stack.split_off(length).into_iter();
+                let mut map = Map::new();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    map = map.insert(key.into_key()?, value);
+                }
+                self.stack.push(Value::Map(map));
+            }
+            Op::Vector => {
+                let length = self
+                    .stack
+                    .len()
+                    .checked_sub(a)
+                    .ok_or_else(|| internal("missing vector items"))?;
+                let items = self.stack.split_off(length);
+                self.stack.push(items.into_iter().collect());
+            }
+            Op::List => {
+                let length = self
+                    .stack
+                    .len()
+                    .checked_sub(a)
+                    .ok_or_else(|| internal("missing list items"))?;
+                let items = self.stack.split_off(length);
+                self.stack.push(Value::List(items.into_iter().collect()));
+            }
+            Op::Splice => {
+                let length = self
+                    .stack
+                    .len()
+                    .checked_sub(a)
+                    .ok_or_else(|| internal("missing spliced sequences"))?;
+                let mut items = Vec::new();
+                for sequence in self.stack.split_off(length) {
+                    for item in sequence.items()? {
+                        items.push(item?);
+                    }
+                }
+                if b == 1 {
+                    self.stack.push(items.into_iter().collect());
+                } else {
+                    self.stack.push(Value::List(items.into_iter().collect()));
+                }
+            }
+            Op::Return => {
+                let value = self.pop()?;
+                return self.ret(value);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn symbol(prototype: &Prototype, index: usize) -> Result<&Symbol> {
+    match prototype.chunk.constants.get(index) {
+        Some(Constant::Value(Value::Symbol(symbol))) => Ok(symbol),
+        _ => Err(internal("expected a symbol constant")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytecode::compile;
+    use crate::interpreter::STACK_SIZE;
+    use crate::prelude::*;
+
+    fn eval(source: &str, vm: bool) -> Result<Value> {
+        let program = parse(Scanner::scan(source.to_string())?)?;
+        let interpreter = Interpreter::boot()?;
+        if vm {
+            run(compile(&program)?, &interpreter)
+        } else {
+            interpreter.eval(&program)
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_tree_walker() {
+        let sources = [
+            include_str!("../../fact.nd"),
+            include_str!("../../fib.nd"),
+            "(def a 1) (def b 2) (+ a b)",
+            "(def constantly (fn [x] (fn [y] x))) ((constantly 1) 2)",
+            "((fn me [x] me) 1)",
+            "(def list (fn [& xs] xs)) (let [a 1 [b [c & d]] (list 2 (list 3 4 5))] (conj d a))",
+            "(def pair (fn [& xs] xs)) (let [[a b] (pair 1 2)] (+ a b))",
+            "(loop [i 0 acc 1] (if (< i 10) (recur (+ i 1) (* acc 2)) acc))",
+            "(def sum (fn [acc & xs] (if (= 0 (count xs)) acc (+ acc (get xs 0))))) (sum 1 2 3)",
+            "(def add (fn [a b] (+ a b))) ((add 1) 2)",
+            "(def i 0) (while (< i 5) (def i (+ i 1))) i",
+            "(def f (fn [n] (loop [i n] (let [j (- i 1)] (if (> j 0) (recur j) (fn [] j)))))) ((f 5))",
+            "(do 1 2 (if false 3 nil))",
+            "(def v (fn [& xs] xs)) (v :a ::b (= ::b :nomad.core/b) (:a (v 1)) (:a nil 2))",
+            "(def v (fn [& xs] xs)) (def m {:a 1 :b {:c (+ 1 2)}}) (v (assoc m :d 4) (dissoc m :a) (:c (:b m)) {})",
+            "(def xs '(1 (+ 2 3) [a ::b] {c 'd})) [xs (first xs) (rest xs) (cons 0 xs) (nth xs 1)]",
+            "(reduce (fn [acc x] (conj acc (* x x))) [] (filter (fn [x] (= 0 (mod x 2))) (range 10)))",
+            "(def v [1 [2 3] (+ 1 2)]) [(assoc v 0 :a) (pop v) (get (get v 1) 0) (= v [1 [2 3] 3]) []]",
+            "(def inc (fn [x] (+ x 1))) (let [[a b & more] (map inc (range))] (take 3 (concat [a b] more)))",
+            "(def f (fn [{a :a [b] :b} {c \"c\"}] [a b c])) [(f {:a 1 :b [2]} {\"c\" 3}) (let [{x :x} nil] x)]",
+            "(loop [{i :i} {:i 0}] (if (< i 3) (recur {:i (+ i 1)}) i))",
+            "(def b 2) (def c '(3 4)) [`(a ~b ~@c) `[~@c ~b ~@[]] `{:k ~b} `(a '~b) (let [[x y] `(v# v#)] (= x y))]",
+        ];
+        for source in &sources {
+            let tree = eval(source, false).unwrap().to_string();
+            let vm = eval(source, true).unwrap().to_string();
+            assert_eq!(tree, vm, "{}", source);
+        }
+    }
+
+    #[test]
+    fn both_engines_limit_call_depth() {
+        let f = "(def f (fn [n] (if (= n 0) 0 (+ 1 (f (- n 1))))))";
+        let run = move || {
+            let mut results = Vec::new();
+            for depth in &[5000, MAX_DEPTH + 1000] {
+                let source = format!("{} (f {})", f, depth);
+                for vm in &[false, true] {
+                    let result = eval(&source, *vm);
+                    results.push(result.map(|value| value.to_string()).map_err(|error| {
+                        assert!(matches!(error.kind(), StackOverflow(MAX_DEPTH)));
+                        error.kind().to_string()
+                    }));
+                }
+            }
+            results
+        };
+        let results = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(results[0], Ok("5000".to_string()));
+        assert_eq!(results[0], results[1]);
+        assert!(results[2].is_err());
+        assert_eq!(results[2], results[3]);
+    }
+
+    #[test]
+    fn deeply_nested_lazy_sequences_fail_instead_of_overflowing() {
+        let run = || {
+            let sources = [
+                "(count (loop [s [] i 0] (if (< i 20000) (recur (concat s [i]) (+ i 1)) s)))",
+                "(first (loop [s (range) i 0] (if (< i 50000) (recur (map (fn [x] x) s) (+ i 1)) s)))",
+            ];
+            for source in sources.iter() {
+                for vm in &[false, true] {
+                    let error = eval(source, *vm).unwrap_err();
+                    assert!(matches!(error.kind(), StackOverflow(..)), "{}", source);
+                }
+            }
+            let source =
+                "(count (loop [s [] i 0] (if (< i 2000) (recur (concat s [i]) (+ i 1)) s)))";
+            assert_eq!(eval(source, true).unwrap().to_string(), "2000");
+        };
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn tail_calls_run_in_constant_space() {
+        let source = "
+            (def even? (fn [n] (if (= n 0) true (odd? (- n 1)))))
+            (def odd? (fn [n] (if (= n 0) false (even? (- n 1)))))
+            (even? 100000)";
+        assert_eq!(eval(source, true).unwrap().to_string(), "true");
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let source = "(def f (fn [x] (+ x missing)))\n(f 1)";
+        let error = eval(source, true).unwrap_err();
+        assert!(matches!(error.kind(), NotDefined(..)));
+        let location = error.location().unwrap();
+        assert_eq!((location.start.line, location.start.column), (1, 21));
+        let error = eval("(def f (fn [a b] a))\n(f 1 2 3)", true).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            InvalidArgumentArity { actual: 3, .. }
+        ));
+        assert_eq!(error.location().unwrap().start.line, 2);
+        let error = eval("(def f (fn [{a :a}] a))\n(f [1])", true).unwrap_err();
+        assert!(matches!(error.kind(), BindingMismatch(..)));
+        assert_eq!(error.location().unwrap().start.line, 1);
+    }
+}
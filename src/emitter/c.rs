@@ -0,0 +1,734 @@
+//! Lowers nodes to C that links against a small runtime of tagged values,
+//! for the system C compiler to build into a standalone executable. Every
+//! `fn` is lifted to a top level C function that receives the values it
+//! captures, and forms that need statements become GNU statement
+//! expressions, which both gcc and clang accept.
+
+use super::javascript::{mangle, recurs};
+use super::{binding_pattern, block, parameter_patterns, Emitter};
+use crate::ast::node::*;
+use crate::interpreter::pattern::Pattern;
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+/// A program emitted as a C translation unit with a `main`.
+pub struct Program<'a>(pub &'a Node);
+
+impl Emitter for Program<'_> {
+    fn emit(&self) -> Result<String> {
+        Translation::default().program(self.0)
+    }
+}
+
+/// Emits `program` as C and compiles it with `$CC`, or `cc`, into the
+/// executable `output`.
+pub fn build(program: &Node, output: &Path) -> Result<()> {
+    let source = Program(program).emit()?;
+    let file = output.with_extension("c");
+    fs::write(&file, source)?;
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(["-std=gnu11", "-O2", "-o"])
+        .arg(output)
+        .arg(&file)
+        .arg("-lm")
+        .status();
+    fs::remove_file(&file)?;
+    if !status?.success() {
+        let message = format!("{} could not compile the emitted program", compiler);
+        return Err(io::Error::other(message).into());
+    }
+    Ok(())
+}
+
+fn string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for byte in value.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'?' => literal.push_str("\\?"),
+            b'\n' => literal.push_str("\\n"),
+            b'\r' => literal.push_str("\\r"),
+            b'\t' => literal.push_str("\\t"),
+            b' '..=b'~' => literal.push(byte as char),
+            byte => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Wraps statements ending in an expression into one expression.
+fn statement_expression(lines: &[String]) -> String {
+    format!("({})", block(lines))
+}
+
+/// Only nomad.core exists when compiled, so `::name` lands there.
+fn keyword(node: &KeywordNode) -> String {
+    let text = match node.namespace() {
+        Some(namespace) => format!(":{}/{}", namespace, node.name()),
+        None if node.is_expanding() => format!(":nomad.core/{}", node.name()),
+        None => format!(":{}", node.name()),
+    };
+    format!("nd_keyword({})", string(&text))
+}
+
+fn constant(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Nil(..) | Node::Boolean(..) | Node::Number(..) | Node::String(..) | Node::Keyword(..)
+    )
+}
+
+/// A value from an enclosing function, copied into the closure when it is
+/// created.
+struct Capture {
+    name: String,
+    local: String,
+    outer: String,
+}
+
+/// The bindings visible in the C function being emitted, mapping nomad names
+/// to C variables.
+#[derive(Default)]
+struct Scope {
+    locals: Vec<(String, String)>,
+    captures: Vec<Capture>,
+}
+
+/// What the form in tail position of a body does with its value.
+enum Exit<'a> {
+    Return,
+    /// Assigns the value to the variable and breaks out of the loop.
+    Break(&'a str),
+}
+
+/// How a body in tail position finishes, and the variables a `recur`
+/// reassigns before jumping back to the head of the loop.
+struct Tail<'a> {
+    exit: Exit<'a>,
+    slots: Option<&'a [String]>,
+}
+
+fn finish(value: String, tail: &Tail) -> Vec<String> {
+    match tail.exit {
+        Exit::Return => vec![format!("return {};", value)],
+        Exit::Break(result) => vec![format!("{} = {};", result, value), "break;".to_string()],
+    }
+}
+
+#[derive(Default)]
+struct Translation {
+    /// One scope per function being emitted, the outermost is `main`.
+    scopes: Vec<Scope>,
+    /// Lifted functions in the order they were finished, so a function is
+    /// always defined before the functions creating it.
+    functions: Vec<String>,
+    globals: Vec<String>,
+    counter: usize,
+}
+
+impl Translation {
+    fn fresh(&mut self, prefix: &str, name: &str) -> String {
+        self.counter += 1;
+        format!("{}{}_{}", prefix, self.counter, mangle(name))
+    }
+
+    fn temporary(&mut self) -> String {
+        self.counter += 1;
+        format!("t{}", self.counter)
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes
+            .last_mut()
+            .expect("emitting outside of a function")
+    }
+
+    fn declare(&mut self, name: &str) -> String {
+        let local = self.fresh("l", name);
+        self.scope().locals.push((name.to_string(), local.clone()));
+        local
+    }
+
+    /// Forgets the locals declared after the first `mark`.
+    fn release(&mut self, mark: usize) {
+        self.scope().locals.truncate(mark);
+    }
+
+    fn global(&mut self, name: &str) -> String {
+        if !self.globals.iter().any(|known| known == name) {
+            self.globals.push(name.to_string());
+        }
+        format!("g_{}", mangle(name))
+    }
+
+    /// Finds the C variable bound to `name` in the function at `depth`,
+    /// capturing it from the enclosing functions when it is theirs.
+    fn resolve(&mut self, depth: usize, name: &str) -> Option<String> {
+        let scope = &self.scopes[depth];
+        let local = scope.locals.iter().rev().find(|(local, _)| local == name);
+        if let Some((_, variable)) = local {
+            return Some(variable.clone());
+        }
+        let capture = scope.captures.iter().find(|capture| capture.name == name);
+        if let Some(capture) = capture {
+            return Some(capture.local.clone());
+        }
+        if depth == 0 {
+            return None;
+        }
+        let outer = self.resolve(depth - 1, name)?;
+        let local = self.fresh("c", name);
+        self.scopes[depth].captures.push(Capture {
+            name: name.to_string(),
+            local: local.clone(),
+            outer,
+        });
+        Some(local)
+    }
+
+    fn symbol(&mut self, node: &SymbolNode) -> Result<String> {
+        match node.namespace() {
+            None => {
+                let depth = self.scopes.len() - 1;
+                if let Some(variable) = self.resolve(depth, node.name()) {
+                    return Ok(variable);
+                }
+            }
+            Some("nomad.core") => {}
+            Some(..) => {
+                return Err(ErrorKind::Unsupported("namespaces other than nomad.core").into())
+            }
+        }
+        let global = self.global(node.name());
+        Ok(format!("nd_global({}, {})", global, string(node.name())))
+    }
+
+    /// Emits `nodes` to be evaluated left to right. C leaves the order of
+    /// arguments unspecified, so when any of them could have an effect all
+    /// but the constants go through temporaries.
+    fn sequence(&mut self, nodes: &[Node]) -> Result<(Vec<String>, Vec<String>)> {
+        let effects = nodes
+            .iter()
+            .any(|node| !constant(node) && !matches!(node, Node::Symbol(..)));
+        let ordered = effects && nodes.iter().filter(|node| !constant(node)).count() > 1;
+        let mut lines = Vec::new();
+        let mut values = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let value = self.expression(node)?;
+            if ordered && !constant(node) {
+                let temporary = self.temporary();
+                lines.push(format!("Value *{} = {};", temporary, value));
+                values.push(temporary);
+            } else {
+                values.push(value);
+            }
+        }
+        Ok((lines, values))
+    }
+
+    fn expression(&mut self, node: &Node) -> Result<String> {
+        let result = match node {
+            Node::Nil(..) => Ok("nd_nil".to_string()),
+            Node::Boolean(node) => Ok(format!("nd_bool({})", node.value() as u8)),
+            Node::Number(node) => Ok(format!("nd_number({:?})", node.value())),
+            Node::String(node) => Ok(format!(
+                "nd_string({}, {})",
+                string(node.value()),
+                node.value().len()
+            )),
+            Node::Symbol(node) => self.symbol(node),
+            Node::Keyword(node) => Ok(keyword(node)),
+            Node::Function(node) => self.function(node, None),
+            Node::FunctionCall(node) => self.call(node),
+            Node::Definition(node) => self.definition(node),
+            Node::If(node) => Ok(format!(
+                "(nd_truthy({}) ? {} : {})",
+                self.expression(&node.condition)?,
+                self.expression(&node.true_branch)?,
+                self.expression(&node.false_branch)?
+            )),
+            Node::While(node) => self.while_(node),
+            Node::Let(node) => {
+                let lines = self.let_(node, None)?;
+                Ok(statement_expression(&lines))
+            }
+            Node::List(..) => Err(ErrorKind::Unsupported("lists").into()),
+            Node::Do(node) => self.do_(node),
+            Node::Program(node) => self.do_all(node.expressions()),
+            Node::Vector(node) => self.vector(node),
+            Node::Map(..) => Err(ErrorKind::Unsupported("map literals").into()),
+            Node::Recur(..) => {
+                Err(ErrorKind::InvalidRecur("recur must be in tail position").into())
+            }
+            Node::Loop(node) => self.loop_(node),
+            Node::Quote(..) => Err(ErrorKind::Unsupported("quotes").into()),
+            Node::QuasiQuote(..) | Node::Unquote(..) => {
+                Err(ErrorKind::Unsupported("quasi-quotes").into())
+            }
+            Node::Meta(..) => Err(ErrorKind::Unsupported("metadata forms").into()),
+            Node::Macro(..) => Err(ErrorKind::Unsupported("macros").into()),
+            Node::Decorator(..) => Err(ErrorKind::Unsupported("decorators").into()),
+        };
+        result.map_err(|error| error.at(node.location()))
+    }
+
+    fn do_(&mut self, node: &DoNode) -> Result<String> {
+        self.do_all(node.expressions())
+    }
+
+    fn do_all(&mut self, nodes: &[Node]) -> Result<String> {
+        match nodes.len() {
+            0 => Ok("nd_nil".to_string()),
+            1 => self.expression(&nodes[0]),
+            _ => {
+                let mut expressions = Vec::with_capacity(nodes.len());
+                for node in nodes {
+                    expressions.push(self.expression(node)?);
+                }
+                Ok(format!("({})", expressions.join(", ")))
+            }
+        }
+    }
+
+    fn call(&mut self, node: &FunctionCallNode) -> Result<String> {
+        let mut nodes = vec![node.function().clone()];
+        nodes.extend(node.arguments().iter().cloned());
+        let (mut lines, mut values) = self.sequence(&nodes)?;
+        let function = values.remove(0);
+        let call = if values.is_empty() {
+            format!("nd_call({}, 0, NULL)", function)
+        } else {
+            format!(
+                "nd_call({}, {}, (Value *[]){{{}}})",
+                function,
+                values.len(),
+                values.join(", ")
+            )
+        };
+        if lines.is_empty() {
+            return Ok(call);
+        }
+        lines.push(format!("{};", call));
+        Ok(statement_expression(&lines))
+    }
+
+    fn vector(&mut self, node: &VectorNode) -> Result<String> {
+        let (mut lines, values) = self.sequence(node.items())?;
+        let vector = if values.is_empty() {
+            "nd_vector(0, NULL)".to_string()
+        } else {
+            format!(
+                "nd_vector({}, (Value *[]){{{}}})",
+                values.len(),
+                values.join(", ")
+            )
+        };
+        if lines.is_empty() {
+            return Ok(vector);
+        }
+        lines.push(format!("{};", vector));
+        Ok(statement_expression(&lines))
+    }
+
+    fn definition(&mut self, node: &DefinitionNode) -> Result<String> {
+        if node.ident().is_qualified() {
+            return Err(ErrorKind::InvalidForm {
+                form: "def",
+                reason: "the name can not be qualified",
+            }
+            .into());
+        }
+        let name = node.ident().name();
+        let value = match node.value() {
+            Node::Function(function) if function.name().is_none() => self
+                .function(function, Some(name))
+                .map_err(|error| error.at(node.value().location()))?,
+            value => self.expression(value)?,
+        };
+        Ok(format!(
+            "({} = {}, nd_var(\"nomad.core\", {}))",
+            self.global(name),
+            value,
+            string(name)
+        ))
+    }
+
+    fn while_(&mut self, node: &WhileNode) -> Result<String> {
+        let condition = self.expression(node.condition())?;
+        let mut lines = Vec::with_capacity(node.body().len());
+        for node in node.body() {
+            lines.push(format!("{};", self.expression(node)?));
+        }
+        Ok(statement_expression(&[
+            format!("while (nd_truthy({})) {}", condition, block(&lines)),
+            "nd_nil;".to_string(),
+        ]))
+    }
+
+    /// Declares the variables of `pattern` bound to `value`.
+    fn bind(&mut self, pattern: &Pattern, value: &str) -> Vec<String> {
+        match pattern {
+            Pattern::Symbol(symbol) => {
+                let local = self.declare(symbol.name());
+                vec![format!("Value *{} = {};", local, value)]
+            }
+            Pattern::Vector(patterns) => {
+                let temporary = self.temporary();
+                let count = patterns.items.len();
+                let variadic = patterns.is_variadic();
+                let mut lines = vec![
+                    format!("Value *{}[{}];", temporary, (count + 1)),
+                    format!(
+                        "nd_destructure({}, {}, {}, {});",
+                        value, count, variadic as u8, temporary
+                    ),
+                ];
+                let all = patterns.items.iter().chain(patterns.rest.as_deref());
+                for (i, pattern) in all.enumerate() {
+                    lines.extend(self.bind(pattern, &format!("{}[{}]", temporary, i)));
+                }
+                lines
+            }
+            Pattern::Map(..) => unreachable!("map patterns are rejected by `binding_pattern`"),
+        }
+    }
+
+    /// Emits a body as statements. With a `tail` the last expression
+    /// finishes the body, otherwise it is left as the value of the block.
+    fn body(&mut self, nodes: &[Node], tail: Option<&Tail>) -> Result<Vec<String>> {
+        let (last, init) = match nodes.split_last() {
+            Some(split) => split,
+            None => {
+                return Ok(match tail {
+                    Some(tail) => finish("nd_nil".to_string(), tail),
+                    None => vec!["nd_nil;".to_string()],
+                })
+            }
+        };
+        let mut lines = Vec::with_capacity(nodes.len());
+        for node in init {
+            lines.push(format!("{};", self.expression(node)?));
+        }
+        match tail {
+            Some(tail) => lines.extend(self.tail(last, tail)?),
+            None => lines.push(format!("{};", self.expression(last)?)),
+        }
+        Ok(lines)
+    }
+
+    /// Emits `node` as statements that finish the body with its value, or
+    /// jump back to the head of the loop for a `recur`.
+    fn tail(&mut self, node: &Node, tail: &Tail) -> Result<Vec<String>> {
+        let result = match node {
+            Node::Recur(node) => self.recur(node, tail),
+            Node::If(node) => {
+                let condition = self.expression(&node.condition)?;
+                Ok(vec![
+                    format!(
+                        "if (nd_truthy({})) {}",
+                        condition,
+                        block(&self.tail(&node.true_branch, tail)?)
+                    ),
+                    format!("else {}", block(&self.tail(&node.false_branch, tail)?)),
+                ])
+            }
+            Node::Do(node) => self.body(node.expressions(), Some(tail)),
+            Node::Let(node) => Ok(vec![block(&self.let_(node, Some(tail))?)]),
+            node => Ok(finish(self.expression(node)?, tail)),
+        };
+        result.map_err(|error| error.at(node.location()))
+    }
+
+    fn recur(&mut self, node: &RecurNode, tail: &Tail) -> Result<Vec<String>> {
+        let slots = tail
+            .slots
+            .ok_or(ErrorKind::InvalidRecur("recur must be inside a loop or fn"))?;
+        if node.arguments().len() != slots.len() {
+            return Err(ErrorKind::InvalidRecurArity {
+                expected: slots.len(),
+                actual: node.arguments().len(),
+            }
+            .into());
+        }
+        let mut lines = Vec::with_capacity(slots.len() * 2 + 1);
+        let mut temporaries = Vec::with_capacity(slots.len());
+        for argument in node.arguments() {
+            let value = self.expression(argument)?;
+            let temporary = self.temporary();
+            lines.push(format!("Value *{} = {};", temporary, value));
+            temporaries.push(temporary);
+        }
+        for (slot, temporary) in slots.iter().zip(temporaries) {
+            lines.push(format!("{} = {};", slot, temporary));
+        }
+        lines.push("continue;".to_string());
+        Ok(vec![block(&lines)])
+    }
+
+    fn let_(&mut self, node: &LetNode, tail: Option<&Tail>) -> Result<Vec<String>> {
+        let mark = self.scope().locals.len();
+        let mut lines = Vec::new();
+        for (pattern, value) in node.bindings() {
+            let pattern = binding_pattern(pattern)?;
+            let value = self.expression(value)?;
+            lines.extend(self.bind(&pattern, &value));
+        }
+        lines.extend(self.body(node.body(), tail)?);
+        self.release(mark);
+        Ok(lines)
+    }
+
+    /// The values of the bindings live in slots which `recur` reassigns, and
+    /// the patterns are bound from them on every iteration.
+    fn loop_(&mut self, node: &LoopNode) -> Result<String> {
+        let mark = self.scope().locals.len();
+        let mut patterns = Vec::new();
+        let mut slots = Vec::new();
+        let mut lines = Vec::new();
+        for (pattern, value) in node.bindings() {
+            let pattern = binding_pattern(pattern)?;
+            let slot = self.temporary();
+            lines.push(format!("Value *{} = {};", slot, self.expression(value)?));
+            lines.extend(self.bind(&pattern, &slot));
+            patterns.push(pattern);
+            slots.push(slot);
+        }
+        self.release(mark);
+        let result = self.temporary();
+        lines.push(format!("Value *{};", result));
+        let mut iteration = Vec::new();
+        for (pattern, slot) in patterns.iter().zip(&slots) {
+            iteration.extend(self.bind(pattern, slot));
+        }
+        let tail = Tail {
+            exit: Exit::Break(&result),
+            slots: Some(&slots),
+        };
+        iteration.extend(self.body(node.body(), Some(&tail))?);
+        self.release(mark);
+        lines.push(format!("for (;;) {}", block(&iteration)));
+        lines.push(format!("{};", result));
+        Ok(statement_expression(&lines))
+    }
+
+    /// Lifts a `fn` to a C function and returns the expression creating the
+    /// closure. `label` names anonymous functions bound with `def`.
+    fn function(&mut self, node: &FunctionNode, label: Option<&str>) -> Result<String> {
+        let patterns = parameter_patterns(node.parameters().items())?;
+        let all: Vec<&Pattern> = patterns
+            .items
+            .iter()
+            .chain(patterns.rest.as_deref())
+            .collect();
+        let name = node
+            .name()
+            .map(|name| name.name())
+            .or(label)
+            .unwrap_or("anonymous");
+        let code = self.fresh("f", name);
+        self.scopes.push(Scope::default());
+        let emitted = self.function_body(node, &all);
+        let scope = self.scopes.pop().expect("the scope of the function");
+        let body = emitted?;
+        let mut lines = Vec::new();
+        if !scope.captures.is_empty() {
+            lines.push("Value **captured = self->as.function->captured;".to_string());
+        }
+        for (i, capture) in scope.captures.iter().enumerate() {
+            lines.push(format!("Value *{} = captured[{}];", capture.local, i));
+        }
+        lines.extend(body);
+        self.functions.push(format!(
+            "static Value *{}(Value *self, int argc, Value **argv) {}\n",
+            code,
+            block(&lines)
+        ));
+        let count = patterns.items.len();
+        let maximum = if patterns.is_variadic() {
+            "-1".to_string()
+        } else {
+            count.to_string()
+        };
+        let captured = if scope.captures.is_empty() {
+            "0, NULL".to_string()
+        } else {
+            let outer: Vec<&str> = scope.captures.iter().map(|c| &c.outer[..]).collect();
+            format!("{}, (Value *[]){{{}}}", outer.len(), outer.join(", "))
+        };
+        Ok(format!(
+            "nd_function({}, {}, {}, 1, {}, {}, {})",
+            string(name),
+            count,
+            maximum,
+            patterns.is_variadic() as u8,
+            code,
+            captured
+        ))
+    }
+
+    fn function_body(&mut self, node: &FunctionNode, patterns: &[&Pattern]) -> Result<Vec<String>> {
+        let mut lines = vec!["(void)argc;".to_string()];
+        if let Some(name) = node.name() {
+            let local = self.declare(name.name());
+            lines.push(format!("Value *{} = self;", local));
+        }
+        let slots: Vec<String> = (0..patterns.len()).map(|i| format!("p{}", i)).collect();
+        for (i, slot) in slots.iter().enumerate() {
+            lines.push(format!("Value *{} = argv[{}];", slot, i));
+        }
+        let mark = self.scope().locals.len();
+        let mut iteration = Vec::new();
+        for (pattern, slot) in patterns.iter().zip(&slots) {
+            iteration.extend(self.bind(pattern, slot));
+        }
+        let tail = Tail {
+            exit: Exit::Return,
+            slots: Some(&slots),
+        };
+        iteration.extend(self.body(node.body(), Some(&tail))?);
+        self.release(mark);
+        if node.body().iter().any(recurs) {
+            lines.push(format!("for (;;) {}", block(&iteration)));
+        } else {
+            lines.extend(iteration);
+        }
+        Ok(lines)
+    }
+
+    /// The runtime, a variable for every global, the lifted functions and a
+    /// `main` evaluating each top level form.
+    fn program(mut self, node: &Node) -> Result<String> {
+        self.scopes.push(Scope::default());
+        let forms = match node {
+            Node::Program(program) => program.expressions(),
+            node => std::slice::from_ref(node),
+        };
+        let mut statements = Vec::with_capacity(forms.len() + self.globals.len());
+        for form in forms {
+            statements.push(format!("{};", self.expression(form)?));
+        }
+        let mut main = Vec::with_capacity(statements.len() + self.globals.len() + 1);
+        let mut source = String::from(RUNTIME);
+        source.push('\n');
+        for name in &self.globals {
+            let global = format!("g_{}", mangle(name));
+            source.push_str(&format!("static Value *{};\n", global));
+            main.push(format!("{} = nd_core({});", global, string(name)));
+        }
+        source.push('\n');
+        for function in &self.functions {
+            source.push_str(function);
+            source.push('\n');
+        }
+        main.extend(statements);
+        main.push("return 0;".to_string());
+        source.push_str(&format!("int main(void) {}\n", block(&main)));
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn emit(source: &str) -> Result<String> {
+        Program(&parse(Scanner::scan(source.to_string())?)?).emit()
+    }
+
+    /// Builds and runs the program, when a C compiler is installed.
+    fn run(name: &str, source: &str) -> Option<(bool, String)> {
+        let program = parse(Scanner::scan(source.to_string()).unwrap()).unwrap();
+        let output = env::temp_dir().join(format!("nomad-{}-{}", name, std::process::id()));
+        if Command::new("cc").arg("--version").output().is_err() {
+            return None;
+        }
+        build(&program, &output).unwrap();
+        let result = Command::new(&output).output().unwrap();
+        fs::remove_file(&output).unwrap();
+        let stdout = std::string::String::from_utf8(result.stdout).unwrap();
+        Some((result.status.success(), stdout))
+    }
+
+    #[test]
+    fn emits_expressions() {
+        let call = emit("(if (< a 1) (println \"small?\") nil)").unwrap();
+        assert!(call.contains("static Value *g_a;"));
+        assert!(call.contains("g__L = nd_core(\"<\");"));
+        assert!(call.contains(
+            "(nd_truthy(nd_call(nd_global(g__L, \"<\"), 2, (Value *[]){nd_global(g_a, \"a\"), nd_number(1.0)})) ? nd_call(nd_global(g_println, \"println\"), 1, (Value *[]){nd_string(\"small\\?\", 6)}) : nd_nil);"
+        ));
+        let closure = emit("(def add (fn [a] (fn [b] (+ a b))))").unwrap();
+        assert!(closure.contains("Value *c5_a = captured[0];"));
+        assert!(closure.contains(
+            "nd_function(\"anonymous\", 1, 1, 1, 0, f3_anonymous, 1, (Value *[]){l2_a})"
+        ));
+        assert!(emit("(def k 'x)").is_err());
+        assert!(emit("(fn [n] (recur n n))").is_err());
+        assert!(emit("(let [{a :a} nil] a)").is_err());
+    }
+
+    #[test]
+    fn runs_natively() {
+        let cases = [
+            (include_str!("../../fact.nd"), "3628800\n"),
+            (
+                "(def v [1 2 3]) (println (conj v 4) (get v 1) (get v 9 \"none\") (count v) (count \"abc\"))",
+                "( 1 2 3 4 ) 2 none 3 3\n",
+            ),
+            (
+                "(def f (fn [n & more] (if (> n 0) (recur (- n 1) more) more))) (println (f 3 4 5))",
+                "( 4 5 )\n",
+            ),
+            (
+                "(println (loop [i 0 [a b] [1 2]] (if (< i 3) (recur (+ i 1) [b (+ a b)]) [i a b])))",
+                "( 3 5 8 )\n",
+            ),
+            (
+                "(def add (fn [a b] (+ a b))) (println (add 1) ((add 1) 2) ((fn me [x] me) 1))",
+                "[partial:add 1] 3 [fn:me]\n",
+            ),
+            (
+                "(println (let [a 1 a (+ a 1) [b & c] [a 3 4]] [a b c]))",
+                "( 2 2 ( 3 4 ) )\n",
+            ),
+            (
+                "(println :a ::b :ns/c (= :a :a) (= :a :b) (:a nil) (:a nil 1) (:a [1]) (get [1] :a))",
+                ":a :nomad.core/b :ns/c true false nil 1 nil nil\n",
+            ),
+            (
+                "(def adder (fn [a] (fn [b] (fn [c] (+ a b c))))) (println (((adder 1) 2) 3) (/ 1 3) (* 1000000 1000000 1000000 1000) (/ 1 10000000) (- 0 0.5))",
+                "6 0.3333333333333333 1000000000000000000000 0.0000001 -0.5\n",
+            ),
+        ];
+        for (i, (source, expected)) in cases.iter().enumerate() {
+            match run(&format!("case{}", i), source) {
+                Some((success, output)) => {
+                    assert!(success, "{}", source);
+                    assert_eq!(&output, expected, "{}", source);
+                }
+                None => return,
+            }
+        }
+    }
+
+    #[test]
+    fn fails_like_the_interpreter() {
+        if let Some((success, output)) = run("failure", "(println 1) (1 2) (println 2)") {
+            assert!(!success);
+            assert_eq!(output, "1\n");
+        }
+    }
+}
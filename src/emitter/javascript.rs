@@ -0,0 +1,800 @@
+//! Lowers nodes to standalone JavaScript. Every node is emitted as an
+//! expression, forms that need statements are wrapped in arrow functions
+//! that are called in place. Bodies of `fn` and `loop` are emitted as
+//! statements so `recur` can become a `continue` of a `while (true)`.
+
+use super::{binding_pattern, block, parameter_patterns, Emitter};
+use crate::ast::node::*;
+use crate::interpreter::pattern::Pattern;
+use crate::interpreter::Symbol;
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+const RUNTIME: &str = include_str!("runtime.js");
+
+/// The `nomad.core` bindings the runtime provides.
+const CORE: &[&str] = &[
+    "+",
+    "-",
+    "*",
+    "/",
+    "mod",
+    "=",
+    "<",
+    ">",
+    "not",
+    "and",
+    "or",
+    "print",
+    "println",
+    "conj",
+    "get",
+    "count",
+    "now",
+    "*version*",
+];
+
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Turns a nomad name into a JavaScript identifier. Punctuation is spelled
+/// out after an underscore, `even?` becomes `even_Q`. Names starting with
+/// `$` are left to the emitter and the runtime.
+pub fn mangle(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => mangled.push(c),
+            '-' => mangled.push('_'),
+            '?' => mangled.push_str("_Q"),
+            '!' => mangled.push_str("_B"),
+            '*' => mangled.push_str("_S"),
+            '+' => mangled.push_str("_P"),
+            '/' => mangled.push_str("_D"),
+            '<' => mangled.push_str("_L"),
+            '>' => mangled.push_str("_G"),
+            '=' => mangled.push_str("_E"),
+            '&' => mangled.push_str("_A"),
+            '.' => mangled.push_str("_O"),
+            '\'' => mangled.push_str("_T"),
+            c => mangled.push_str(&format!("_U{:x}_", c as u32)),
+        }
+    }
+    let reserved = RESERVED.contains(&&mangled[..]);
+    if reserved || mangled.starts_with(|c: char| c.is_ascii_digit()) {
+        mangled.insert(0, '_');
+    }
+    mangled
+}
+
+fn string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c < ' ' || c == '\u{2028}' || c == '\u{2029}' => {
+                literal.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn iife(lines: &[String]) -> String {
+    format!("(() => {})()", block(lines))
+}
+
+/// The names a `recur` assigns before jumping back to the loop head.
+struct Target {
+    slots: Vec<String>,
+}
+
+/// Declares `pattern` bound to `value`. Nested vector patterns go through
+/// temporaries named after `temporary`. The declarations open a block that
+/// `body` is emitted into.
+fn bind(pattern: &Pattern, value: &str, temporary: &str, body: Vec<String>) -> Vec<String> {
+    match pattern {
+        Pattern::Symbol(symbol) => {
+            let mut lines = vec![format!("const {} = {};", mangle(symbol.name()), value)];
+            lines.extend(body);
+            vec![block(&lines)]
+        }
+        Pattern::Vector(patterns) => {
+            let all: Vec<&Pattern> = patterns
+                .items
+                .iter()
+                .chain(patterns.rest.as_deref())
+                .collect();
+            let targets: Vec<String> = all
+                .iter()
+                .enumerate()
+                .map(|(i, pattern)| match pattern {
+                    Pattern::Symbol(symbol) => mangle(symbol.name()),
+                    Pattern::Vector(..) | Pattern::Map(..) => format!("{}_{}", temporary, i),
+                })
+                .collect();
+            let mut body = body;
+            for (i, pattern) in all.iter().enumerate().rev() {
+                if let Pattern::Vector(..) = pattern {
+                    let nested = format!("{}_{}", temporary, i);
+                    body = bind(pattern, &nested, &nested, body);
+                }
+            }
+            let mut lines = vec![format!(
+                "const [{}] = $destructure({}, {}, {});",
+                targets.join(", "),
+                value,
+                patterns.items.len(),
+                patterns.is_variadic()
+            )];
+            lines.extend(body);
+            vec![block(&lines)]
+        }
+        Pattern::Map(..) => unreachable!("map patterns are rejected by `binding_pattern`"),
+    }
+}
+
+/// Whether `node` is or ends in a `recur` for the enclosing form.
+pub(super) fn recurs(node: &Node) -> bool {
+    match node {
+        Node::Recur(..) => true,
+        Node::If(node) => recurs(&node.true_branch) || recurs(&node.false_branch),
+        Node::Do(node) => node.expressions().iter().any(recurs),
+        Node::Let(node) => node.body().iter().any(recurs),
+        _ => false,
+    }
+}
+
+/// Emits `node` as statements that return its value, or loop back to
+/// `target` for a `recur`.
+fn tail(node: &Node, target: Option<&Target>) -> Result<Vec<String>> {
+    let result = match node {
+        Node::Recur(node) => {
+            let target =
+                target.ok_or(ErrorKind::InvalidRecur("recur must be inside a loop or fn"))?;
+            let mut values = Vec::with_capacity(node.arguments().len());
+            for argument in node.arguments() {
+                values.push(argument.emit()?);
+            }
+            Ok(vec![
+                format!("[{}] = [{}];", target.slots.join(", "), values.join(", ")),
+                "continue;".to_string(),
+            ])
+        }
+        Node::If(node) => Ok(vec![
+            format!(
+                "if ($truthy({})) {}",
+                node.condition.emit()?,
+                block(&tail(&node.true_branch, target)?)
+            ),
+            format!("else {}", block(&tail(&node.false_branch, target)?)),
+        ]),
+        Node::Do(node) => body(node.expressions(), target),
+        Node::Let(node) => let_(node, target),
+        node => Ok(vec![format!("return {};", node.emit()?)]),
+    };
+    result.map_err(|error| error.at(node.location()))
+}
+
+/// Emits a body as statements, the last expression in tail position.
+fn body(nodes: &[Node], target: Option<&Target>) -> Result<Vec<String>> {
+    match nodes.split_last() {
+        Some((last, init)) => {
+            let mut lines = Vec::with_capacity(nodes.len());
+            for node in init {
+                lines.push(format!("{};", node.emit()?));
+            }
+            lines.extend(tail(last, target)?);
+            Ok(lines)
+        }
+        None => Ok(vec!["return null;".to_string()]),
+    }
+}
+
+fn let_(node: &LetNode, target: Option<&Target>) -> Result<Vec<String>> {
+    let bindings: Vec<_> = node.bindings().collect();
+    let mut lines = body(node.body(), target)?;
+    for (pattern, value) in bindings.into_iter().rev() {
+        let pattern = binding_pattern(pattern)?;
+        let mut binding = vec![format!("const $v = {};", value.emit()?)];
+        binding.extend(bind(&pattern, "$v", "$v", lines));
+        lines = vec![block(&binding)];
+    }
+    Ok(lines)
+}
+
+/// Emits a `fn` as a call to the runtime's `$fn`, which checks the argument
+/// count and curries. `label` names anonymous functions bound with `def`.
+fn function(node: &FunctionNode, label: Option<&str>) -> Result<String> {
+    let patterns = parameter_patterns(node.parameters().items())?;
+    let all: Vec<&Pattern> = patterns
+        .items
+        .iter()
+        .chain(patterns.rest.as_deref())
+        .collect();
+    let recursive = node.body().iter().any(recurs);
+    let parameters: Vec<String> = all
+        .iter()
+        .enumerate()
+        .map(|(i, pattern)| match pattern {
+            Pattern::Symbol(symbol) if !recursive => mangle(symbol.name()),
+            _ => format!("$p{}", i),
+        })
+        .collect();
+    let target = Target {
+        slots: parameters.clone(),
+    };
+    let mut lines = body(node.body(), Some(&target))?;
+    for (i, pattern) in all.iter().enumerate().rev() {
+        if parameters[i].starts_with('$') {
+            lines = bind(pattern, &parameters[i], &parameters[i], lines);
+        }
+    }
+    if recursive {
+        lines = vec![format!("while (true) {}", block(&lines))];
+    }
+    let mut signature = parameters;
+    if patterns.is_variadic() {
+        if let Some(rest) = signature.last_mut() {
+            rest.insert_str(0, "...");
+        }
+    }
+    let name = node
+        .name()
+        .map(|name| name.name())
+        .or(label)
+        .unwrap_or("anonymous");
+    let function = format!(
+        "$fn({}, {}, {}, true, function ({}) {})",
+        string(name),
+        patterns.items.len(),
+        patterns.is_variadic(),
+        signature.join(", "),
+        block(&lines)
+    );
+    match node.name() {
+        Some(name) => {
+            let name = mangle(name.name());
+            Ok(iife(&[
+                format!("const {} = {};", name, function),
+                format!("return {};", name),
+            ]))
+        }
+        None => Ok(function),
+    }
+}
+
+/// Collects the names bound with `def` anywhere in `node`, which become
+/// module level bindings.
+fn definitions(node: &Node, names: &mut Vec<String>) {
+    let all = |nodes: &[Node], names: &mut Vec<String>| {
+        for node in nodes {
+            definitions(node, names);
+        }
+    };
+    match node {
+        Node::Definition(node) => {
+            let name = node.ident().name();
+            if !CORE.contains(&name) && !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+            definitions(node.value(), names);
+        }
+        Node::Program(node) => all(node.expressions(), names),
+        Node::Do(node) => all(node.expressions(), names),
+        Node::If(node) => {
+            definitions(&node.condition, names);
+            definitions(&node.true_branch, names);
+            definitions(&node.false_branch, names);
+        }
+        Node::While(node) => {
+            definitions(node.condition(), names);
+            all(node.body(), names);
+        }
+        Node::Let(node) => {
+            for (_, value) in node.bindings() {
+                definitions(value, names);
+            }
+            all(node.body(), names);
+        }
+        Node::Loop(node) => {
+            for (_, value) in node.bindings() {
+                definitions(value, names);
+            }
+            all(node.body(), names);
+        }
+        Node::Function(node) => all(node.body(), names),
+        Node::FunctionCall(node) => {
+            definitions(node.function(), names);
+            all(node.arguments(), names);
+        }
+        Node::Recur(node) => all(node.arguments(), names),
+        Node::Vector(node) => all(node.items(), names),
+        _ => {}
+    }
+}
+
+/// Adds the names `pattern` binds to `locals`.
+fn bound(pattern: &Node, locals: &mut Vec<String>) {
+    match pattern {
+        Node::Symbol(symbol) if !symbol.is_qualified() && symbol.name() != "&" => {
+            locals.push(symbol.name().to_string())
+        }
+        Node::Vector(node) => node.items().iter().for_each(|item| bound(item, locals)),
+        Node::Map(node) => node.entries().for_each(|(item, _)| bound(item, locals)),
+        _ => {}
+    }
+}
+
+/// Checks that every symbol in `node` names a local in `locals`, a `def` in
+/// `names` or a binding of the runtime. An undefined one is reported here,
+/// as the C backend does, instead of failing the script with a
+/// `ReferenceError`.
+fn resolved(node: &Node, locals: &mut Vec<String>, names: &[String]) -> Result<()> {
+    let all = |nodes: &[Node], locals: &mut Vec<String>| {
+        nodes
+            .iter()
+            .try_for_each(|node| resolved(node, locals, names))
+    };
+    let mark = locals.len();
+    let result = match node {
+        Node::Symbol(symbol) => {
+            let name = symbol.name();
+            let local = !symbol.is_qualified() && locals.iter().any(|local| local == name);
+            let global = names.iter().any(|known| known == name) || CORE.contains(&name);
+            match symbol.namespace() {
+                None | Some("nomad.core") if !local && !global => {
+                    let symbol = Symbol::from_node(symbol.clone());
+                    Err(ErrorKind::NotDefined(symbol).at(node.location()))
+                }
+                _ => Ok(()),
+            }
+        }
+        Node::Definition(node) => resolved(node.value(), locals, names),
+        Node::Program(node) => all(node.expressions(), locals),
+        Node::Do(node) => all(node.expressions(), locals),
+        Node::If(node) => {
+            resolved(&node.condition, locals, names)?;
+            resolved(&node.true_branch, locals, names)?;
+            resolved(&node.false_branch, locals, names)
+        }
+        Node::While(node) => {
+            resolved(node.condition(), locals, names)?;
+            all(node.body(), locals)
+        }
+        Node::Let(node) => {
+            for (pattern, value) in node.bindings() {
+                resolved(value, locals, names)?;
+                bound(pattern, locals);
+            }
+            all(node.body(), locals)
+        }
+        Node::Loop(node) => {
+            for (pattern, value) in node.bindings() {
+                resolved(value, locals, names)?;
+                bound(pattern, locals);
+            }
+            all(node.body(), locals)
+        }
+        Node::Function(node) => {
+            if let Some(name) = node.name() {
+                locals.push(name.name().to_string());
+            }
+            for parameter in node.parameters().items() {
+                bound(parameter, locals);
+            }
+            all(node.body(), locals)
+        }
+        Node::FunctionCall(node) => {
+            resolved(node.function(), locals, names)?;
+            all(node.arguments(), locals)
+        }
+        Node::Recur(node) => all(node.arguments(), locals),
+        Node::Vector(node) => all(node.items(), locals),
+        _ => Ok(()),
+    };
+    locals.truncate(mark);
+    result
+}
+
+impl Emitter for Node {
+    fn emit(&self) -> Result<String> {
+        let result = match self {
+            Node::Nil(node) => node.emit(),
+            Node::Boolean(node) => node.emit(),
+            Node::Number(node) => node.emit(),
+            Node::String(node) => node.emit(),
+            Node::Symbol(node) => node.emit(),
+            Node::Keyword(node) => node.emit(),
+            Node::Function(node) => node.emit(),
+            Node::FunctionCall(node) => node.emit(),
+            Node::Definition(node) => node.emit(),
+            Node::If(node) => node.emit(),
+            Node::While(node) => node.emit(),
+            Node::Let(node) => node.emit(),
+            Node::List(..) => Err(ErrorKind::Unsupported("lists").into()),
+            Node::Do(node) => node.emit(),
+            Node::Program(node) => node.emit(),
+            Node::Vector(node) => node.emit(),
+            Node::Map(..) => Err(ErrorKind::Unsupported("map literals").into()),
+            Node::Recur(..) => {
+                Err(ErrorKind::InvalidRecur("recur must be in tail position").into())
+            }
+            Node::Loop(node) => node.emit(),
+            Node::Quote(..) => Err(ErrorKind::Unsupported("quotes").into()),
+            Node::QuasiQuote(..) | Node::Unquote(..) => {
+                Err(ErrorKind::Unsupported("quasi-quotes").into())
+            }
+            Node::Meta(..) => Err(ErrorKind::Unsupported("metadata forms").into()),
+            Node::Macro(..) => Err(ErrorKind::Unsupported("macros").into()),
+            Node::Decorator(..) => Err(ErrorKind::Unsupported("decorators").into()),
+        };
+        result.map_err(|error| error.at(self.location()))
+    }
+}
+
+impl Emitter for NilNode {
+    fn emit(&self) -> Result<String> {
+        Ok("null".to_string())
+    }
+}
+
+impl Emitter for BooleanNode {
+    fn emit(&self) -> Result<String> {
+        Ok(self.value().to_string())
+    }
+}
+
+impl Emitter for NumberNode {
+    fn emit(&self) -> Result<String> {
+        let value = self.value();
+        if value.is_sign_negative() {
+            Ok(format!("({})", value))
+        } else {
+            Ok(value.to_string())
+        }
+    }
+}
+
+impl Emitter for StringNode {
+    fn emit(&self) -> Result<String> {
+        Ok(string(self.value()))
+    }
+}
+
+impl Emitter for SymbolNode {
+    fn emit(&self) -> Result<String> {
+        match self.namespace() {
+            None | Some("nomad.core") => Ok(mangle(self.name())),
+            Some(..) => Err(ErrorKind::Unsupported("namespaces other than nomad.core").into()),
+        }
+    }
+}
+
+impl Emitter for KeywordNode {
+    fn emit(&self) -> Result<String> {
+        // Only nomad.core exists when compiled, so `::name` lands there.
+        let namespace = match self.namespace() {
+            Some(namespace) => string(namespace),
+            None if self.is_expanding() => string("nomad.core"),
+            None => "null".to_string(),
+        };
+        Ok(format!("$keyword({}, {})", namespace, string(self.name())))
+    }
+}
+
+impl Emitter for VectorNode {
+    fn emit(&self) -> Result<String> {
+        let mut items = Vec::with_capacity(self.items().len());
+        for item in self.items() {
+            items.push(item.emit()?);
+        }
+        Ok(format!("$vector({})", items.join(", ")))
+    }
+}
+
+impl Emitter for FunctionNode {
+    fn emit(&self) -> Result<String> {
+        function(self, None)
+    }
+}
+
+impl Emitter for FunctionCallNode {
+    fn emit(&self) -> Result<String> {
+        let mut arguments = Vec::with_capacity(self.arguments().len());
+        for argument in self.arguments() {
+            arguments.push(argument.emit()?);
+        }
+        let function = match self.function() {
+            Node::Symbol(..) => self.function().emit()?,
+            node => format!("({})", node.emit()?),
+        };
+        Ok(format!("{}({})", function, arguments.join(", ")))
+    }
+}
+
+impl Emitter for DefinitionNode {
+    fn emit(&self) -> Result<String> {
+        if self.ident().is_qualified() {
+            return Err(ErrorKind::InvalidForm {
+                form: "def",
+                reason: "the name can not be qualified",
+            }
+            .into());
+        }
+        let name = self.ident().name();
+        let value = match self.value() {
+            Node::Function(node) if node.name().is_none() => {
+                function(node, Some(name)).map_err(|error| error.at(self.value().location()))?
+            }
+            node => node.emit()?,
+        };
+        Ok(format!(
+            "({} = {}, new $Var(\"nomad.core\", {}))",
+            mangle(name),
+            value,
+            string(name)
+        ))
+    }
+}
+
+impl Emitter for IfNode {
+    fn emit(&self) -> Result<String> {
+        Ok(format!(
+            "($truthy({}) ? {} : {})",
+            self.condition.emit()?,
+            self.true_branch.emit()?,
+            self.false_branch.emit()?
+        ))
+    }
+}
+
+impl Emitter for WhileNode {
+    fn emit(&self) -> Result<String> {
+        let mut lines = Vec::with_capacity(self.body().len());
+        for node in self.body() {
+            lines.push(format!("{};", node.emit()?));
+        }
+        Ok(iife(&[
+            format!(
+                "while ($truthy({})) {}",
+                self.condition().emit()?,
+                block(&lines)
+            ),
+            "return null;".to_string(),
+        ]))
+    }
+}
+
+impl Emitter for DoNode {
+    fn emit(&self) -> Result<String> {
+        match self.expressions().len() {
+            0 => Ok("null".to_string()),
+            1 => self.expressions()[0].emit(),
+            _ => {
+                let mut expressions = Vec::with_capacity(self.expressions().len());
+                for node in self.expressions() {
+                    expressions.push(node.emit()?);
+                }
+                Ok(format!("({})", expressions.join(", ")))
+            }
+        }
+    }
+}
+
+impl Emitter for LetNode {
+    fn emit(&self) -> Result<String> {
+        Ok(iife(&let_(self, None)?))
+    }
+}
+
+impl Emitter for LoopNode {
+    /// The values of the bindings live in `$l0`, `$l1`, .. which `recur`
+    /// reassigns, and the patterns are bound from them on every iteration.
+    fn emit(&self) -> Result<String> {
+        let mut patterns = Vec::new();
+        for (pattern, _) in self.bindings() {
+            patterns.push(binding_pattern(pattern)?);
+        }
+        let slots: Vec<String> = (0..patterns.len()).map(|i| format!("$l{}", i)).collect();
+        let target = Target {
+            slots: slots.clone(),
+        };
+        let mut lines = Vec::new();
+        if !slots.is_empty() {
+            lines.push(format!("let {};", slots.join(", ")));
+        }
+        let values: Vec<&Node> = self.bindings().map(|(_, value)| value).collect();
+        let mut initial = Vec::new();
+        for (i, (pattern, value)) in patterns.iter().zip(values).enumerate().rev() {
+            let mut binding = vec![format!("{} = {};", slots[i], value.emit()?)];
+            binding.extend(bind(pattern, &slots[i], &slots[i], initial));
+            initial = binding;
+        }
+        lines.extend(initial);
+        let mut iteration = body(self.body(), Some(&target))?;
+        for (i, pattern) in patterns.iter().enumerate().rev() {
+            iteration = bind(pattern, &slots[i], &slots[i], iteration);
+        }
+        lines.push(format!("while (true) {}", block(&iteration)));
+        Ok(iife(&lines))
+    }
+}
+
+impl Emitter for ProgramNode {
+    /// A standalone script: the runtime, a binding for every `def`, then
+    /// each top level form as a statement.
+    fn emit(&self) -> Result<String> {
+        let mut names = Vec::new();
+        for node in self.expressions() {
+            definitions(node, &mut names);
+        }
+        for node in self.expressions() {
+            resolved(node, &mut Vec::new(), &names)?;
+        }
+        let mut script = String::from(RUNTIME);
+        script.push('\n');
+        if !names.is_empty() {
+            let names: Vec<String> = names.iter().map(|name| mangle(name)).collect();
+            script.push_str(&format!("let {};\n\n", names.join(", ")));
+        }
+        for node in self.expressions() {
+            script.push_str(&node.emit()?);
+            script.push_str(";\n");
+        }
+        Ok(script)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    use std::process::Command;
+
+    fn emit(source: &str) -> Result<String> {
+        parse(Scanner::scan(source.to_string())?)?.emit()
+    }
+
+    /// Runs the emitted script with node, when it is installed.
+    fn run(source: &str) -> Option<String> {
+        let script = emit(source).unwrap();
+        let output = Command::new("node").arg("-e").arg(&script).output().ok()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            std::string::String::from_utf8_lossy(&output.stderr)
+        );
+        Some(std::string::String::from_utf8(output.stdout).unwrap())
+    }
+
+    #[test]
+    fn mangles_names_into_identifiers() {
+        assert_eq!(mangle("next-number"), "next_number");
+        assert_eq!(mangle("even?"), "even_Q");
+        assert_eq!(mangle("*version*"), "_Sversion_S");
+        assert_eq!(mangle("new"), "_new");
+        assert_eq!(mangle("λ"), "_U3bb_");
+        assert_eq!(string("say \"hi\"\n"), "\"say \\\"hi\\\"\\n\"");
+    }
+
+    #[test]
+    fn emits_expressions() {
+        let call = emit("(def a 0) (if (< a 1) (println \"small\") nil)").unwrap();
+        assert!(call.ends_with("($truthy(_L(a, 1)) ? println(\"small\") : null);\n"));
+        let def = emit("(def next-number (fn [n] (+ n 1)))").unwrap();
+        assert!(def.contains("let next_number;"));
+        assert!(def.contains("$fn(\"next-number\", 1, false, true, function (n) {"));
+        assert!(emit("(def k 'x)").is_err());
+        assert!(emit("(fn [[{a :a}]] a)").is_err());
+    }
+
+    #[test]
+    fn reports_undefined_symbols() {
+        let error = emit("(def f (fn [x]\n  (+ x -y)))").unwrap_err();
+        let location = error.location().expect("a located error");
+        assert_eq!((location.start.line, location.start.column), (2, 8));
+        match error.kind() {
+            ErrorKind::NotDefined(symbol) => assert_eq!(symbol.to_string(), "-y"),
+            other => panic!("expected an undefined symbol, got {:?}", other),
+        }
+        let bound =
+            "(def f (fn g [x & more] (let [[a b] more] (loop [i a] (g i b later))))) (def later 1)";
+        assert!(emit(bound).is_ok());
+        assert!(emit("(let [a 1] a) a").is_err());
+    }
+
+    #[test]
+    fn runs_under_node() {
+        let cases = [
+            (include_str!("../../fact.nd"), "3628800\n"),
+            (
+                "(def v [1 2 3]) (println (conj v 4) (get v 1) (get v 9 \"none\") (count v) (count \"abc\"))",
+                "( 1 2 3 4 ) 2 none 3 3\n",
+            ),
+            (
+                "(def f (fn [n & more] (if (> n 0) (recur (- n 1) more) more))) (println (f 3 4 5))",
+                "( 4 5 )\n",
+            ),
+            (
+                "(println (loop [i 0 [a b] [1 2]] (if (< i 3) (recur (+ i 1) [b (+ a b)]) [i a b])))",
+                "( 3 5 8 )\n",
+            ),
+            (
+                "(def add (fn [a b] (+ a b))) (println (add 1) ((add 1) 2) ((fn me [x] me) 1))",
+                "[partial:add 1] 3 [fn:me]\n",
+            ),
+            (
+                "(println (let [a 1 a (+ a 1) [b & c] [a 3 4]] [a b c]))",
+                "( 2 2 ( 3 4 ) )\n",
+            ),
+            (
+                "(println :a ::b :ns/c (= :a :a) (= :a :b) (:a nil) (:a nil 1) (:a [1]) (get [1] :a))",
+                ":a :nomad.core/b :ns/c true false nil 1 nil nil\n",
+            ),
+        ];
+        for (source, expected) in &cases {
+            match run(source) {
+                Some(output) => assert_eq!(&output, expected, "{}", source),
+                None => return,
+            }
+        }
+    }
+}
@@ -1,6 +1,52 @@
+use crate::ast::node::Node;
+use crate::interpreter::pattern::{Pattern, Patterns};
 use crate::prelude::*;
 
+pub mod c;
+mod javascript;
+
 pub trait Emitter {
     fn emit(&self) -> Result<String>;
 }
 
+/// Indents every line of `lines` one level, for the body of a block.
+fn block(lines: &[String]) -> String {
+    let mut body = String::new();
+    for line in lines.iter().flat_map(|line| line.lines()) {
+        body.push_str("  ");
+        body.push_str(line);
+        body.push('\n');
+    }
+    format!("{{\n{}}}", body)
+}
+
+/// Neither runtime has maps to destructure.
+fn positional(pattern: &Pattern) -> Result<()> {
+    match pattern {
+        Pattern::Symbol(..) => Ok(()),
+        Pattern::Vector(patterns) => positionals(patterns),
+        Pattern::Map(..) => Err(Unsupported("map patterns").into()),
+    }
+}
+
+fn positionals(patterns: &Patterns) -> Result<()> {
+    patterns
+        .items
+        .iter()
+        .chain(patterns.rest.as_deref())
+        .try_for_each(positional)
+}
+
+/// The binding pattern written by `node`, if the backends can bind it.
+fn binding_pattern(node: &Node) -> Result<Pattern> {
+    let pattern = Pattern::from_node(node)?;
+    positional(&pattern)?;
+    Ok(pattern)
+}
+
+/// The parameter patterns written by `nodes`, if the backends can bind them.
+fn parameter_patterns(nodes: &[Node]) -> Result<Patterns> {
+    let patterns = Patterns::from_nodes(nodes)?;
+    positionals(&patterns)?;
+    Ok(patterns)
+}
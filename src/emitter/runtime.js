@@ -0,0 +1,185 @@
+"use strict";
+
+// Runtime shim for code emitted from nomad. Values map onto JavaScript:
+// nil is null, vectors are frozen arrays and functions are functions.
+// Keywords are interned functions that look themselves up in their argument.
+
+class $NomadError extends Error {}
+
+class $Var {
+  constructor(namespace, name) {
+    this.namespace = namespace;
+    this.name = name;
+  }
+}
+
+function $truthy(value) {
+  return value !== null && value !== false;
+}
+
+function $show(value) {
+  if (value === null || value === undefined) return "nil";
+  if (Array.isArray(value)) return "( " + value.map((item) => $show(item) + " ").join("") + ")";
+  if (value instanceof $Var) return "#'" + value.namespace + "/" + value.name;
+  if (typeof value === "function" && value.$keyword) return value.$keyword;
+  if (typeof value === "function") {
+    if (value.$bound.length === 0) return "[fn:" + value.$name + "]";
+    return "[partial:" + value.$name + value.$bound.map((item) => " " + $show(item)).join("") + "]";
+  }
+  if (value === Infinity) return "inf";
+  if (value === -Infinity) return "-inf";
+  return String(value);
+}
+
+function $type(value) {
+  if (value === null || value === undefined) return "nil";
+  if (Array.isArray(value)) return "vector";
+  if (value instanceof $Var) return "var";
+  if (typeof value === "function" && value.$keyword) return "keyword";
+  return typeof value;
+}
+
+function $mismatch(expected, value) {
+  return new $NomadError("expected " + expected + " but found " + $type(value) + " `" + $show(value) + "`");
+}
+
+// Wraps a function with nomad's calling convention: the argument count is
+// checked, and curried functions given too few arguments are partially
+// applied.
+function $fn(name, arity, variadic, curried, body, bound = []) {
+  const f = function (...args) {
+    const all = bound.concat(args);
+    if (curried && args.length > 0 && all.length < arity) {
+      return $fn(name, arity, variadic, curried, body, all);
+    }
+    if (variadic ? all.length < arity : all.length !== arity) {
+      const expected = variadic ? "at least " + arity : String(arity);
+      throw new $NomadError("`" + name + "` expects " + expected + " arguments but was given " + all.length);
+    }
+    return body(...all);
+  };
+  f.$name = name;
+  f.$bound = bound;
+  return f;
+}
+
+// Checks that `value` can be destructured by a vector pattern of `count`
+// items, returning the items with the rest collected last when `variadic`.
+function $destructure(value, count, variadic) {
+  if (!Array.isArray(value)) throw new $NomadError("binding mismatch: vector patterns can only destructure vectors");
+  if (value.length < count) throw new $NomadError("binding mismatch: too few values for pattern");
+  if (!variadic && value.length > count) throw new $NomadError("binding mismatch: too many values for pattern");
+  const items = value.slice(0, count);
+  if (variadic) items.push(Object.freeze(value.slice(count)));
+  return items;
+}
+
+const $keywords = new Map();
+
+function $keyword(namespace, name) {
+  const text = ":" + (namespace === null ? "" : namespace + "/") + name;
+  let keyword = $keywords.get(text);
+  if (keyword === undefined) {
+    keyword = $fn(text, 1, true, false, (collection, fallback = null, ...extra) => {
+      if (extra.length > 0) throw new $NomadError("`" + text + "` expects 1 or 2 arguments but was given " + (2 + extra.length));
+      return get(collection, keyword, fallback);
+    });
+    keyword.$keyword = text;
+    $keywords.set(text, keyword);
+  }
+  return keyword;
+}
+
+function $vector(...items) {
+  return Object.freeze(items);
+}
+
+function $number(value) {
+  if (typeof value !== "number") throw $mismatch("number", value);
+  return value;
+}
+
+function $fold(args, identity, f) {
+  if (args.length === 0) return identity;
+  if (args.length === 1) return f(identity, $number(args[0]));
+  return args.slice(1).reduce((result, value) => f(result, $number(value)), $number(args[0]));
+}
+
+function $chain(args, f) {
+  for (let i = 1; i < args.length; i++) {
+    if (!f($number(args[i - 1]), $number(args[i]))) return false;
+  }
+  return true;
+}
+
+function $equal(left, right) {
+  if (Array.isArray(left) && Array.isArray(right)) {
+    return left.length === right.length && left.every((item, i) => $equal(item, right[i]));
+  }
+  if (left instanceof $Var && right instanceof $Var) {
+    return left.namespace === right.namespace && left.name === right.name;
+  }
+  return left === right;
+}
+
+const _P = $fn("+", 0, true, false, (...args) => $fold(args, 0, (a, b) => a + b));
+const _ = $fn("-", 0, true, false, (...args) => $fold(args, 0, (a, b) => a - b));
+const _S = $fn("*", 0, true, false, (...args) => $fold(args, 1, (a, b) => a * b));
+const _D = $fn("/", 0, true, false, (...args) => $fold(args, 1, (a, b) => a / b));
+const mod = $fn("mod", 2, false, true, (...args) => $fold(args, 1, (a, b) => a % b));
+const _E = $fn("=", 0, true, false, (...args) => args.every((value, i) => i === 0 || $equal(args[i - 1], value)));
+const _L = $fn("<", 0, true, false, (...args) => $chain(args, (a, b) => a < b));
+const _G = $fn(">", 0, true, false, (...args) => $chain(args, (a, b) => a > b));
+const not = $fn("not", 1, false, false, (value) => !$truthy(value));
+
+const and = $fn("and", 0, true, false, (...args) => {
+  let result = true;
+  for (const value of args) {
+    if (!$truthy(value)) return value;
+    result = value;
+  }
+  return result;
+});
+
+const or = $fn("or", 0, true, false, (...args) => {
+  let result = null;
+  for (const value of args) {
+    if ($truthy(value)) return value;
+    result = value;
+  }
+  return result;
+});
+
+const print = $fn("print", 0, true, false, (...args) => {
+  process.stdout.write(args.map($show).join(" "));
+  return null;
+});
+
+const println = $fn("println", 0, true, false, (...args) => {
+  process.stdout.write(args.map($show).join(" ") + "\n");
+  return null;
+});
+
+const conj = $fn("conj", 2, false, true, (collection, value) => {
+  if (!Array.isArray(collection)) throw $mismatch("vector", collection);
+  return Object.freeze(collection.concat([value]));
+});
+
+const get = $fn("get", 2, true, false, (collection, key, fallback = null, ...extra) => {
+  if (extra.length > 0) throw new $NomadError("`get` expects 2 or 3 arguments but was given " + (3 + extra.length));
+  if (collection === null) return fallback;
+  if (!Array.isArray(collection)) throw $mismatch("vector", collection);
+  if (typeof key !== "number") return fallback;
+  return key >= 0 && key < collection.length ? collection[Math.floor(key)] : fallback;
+});
+
+const count = $fn("count", 1, false, false, (collection) => {
+  if (collection === null) return 0;
+  if (typeof collection === "string") return Array.from(collection).length;
+  if (Array.isArray(collection)) return collection.length;
+  throw $mismatch("a collection or string", collection);
+});
+
+const now = $fn("now", 0, false, false, () => Date.now());
+
+const _S_version_S = 0;
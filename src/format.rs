@@ -0,0 +1,558 @@
+//! Prints nomad source back in a canonical layout. A form that fits in the
+//! line stays on one line. Otherwise special forms keep what introduces them
+//! on the first line and indent their body by two, while calls and vectors
+//! line their items up under the first one. Comments are printed where the
+//! parser attached them, and single blank lines between forms are kept.
+
+use crate::ast::node::*;
+use crate::ast::parser::parse;
+use crate::ast::scanner::{Location, Scanner};
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+const WIDTH: usize = 80;
+
+/// Formats a whole source file.
+pub fn format(source: &str) -> Result<String> {
+    let program = parse(Scanner::scan(source.to_string())?)?;
+    let forms = match &program {
+        Node::Program(program) => program.expressions(),
+        _ => return Err(ErrorKind::Internal("the parser returned a program").into()),
+    };
+    let mut items = Vec::with_capacity(forms.len());
+    for form in forms {
+        items.push(item(form)?);
+    }
+    let mut printer = Printer::default();
+    for item in &items {
+        printer.element(item, false, 0, false, 0);
+    }
+    for comment in &program.comments().inner {
+        printer.comment(comment, 0);
+    }
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+    Ok(printer.out)
+}
+
+/// How the items of a form that does not fit on one line are indented.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Indent {
+    /// By two from the opening delimiter, like a body.
+    Body,
+    /// Under the first item.
+    Align,
+}
+
+/// A node reduced to what its layout depends on.
+struct Item<'a> {
+    shape: Shape<'a>,
+    comments: &'a Comments,
+    location: Location,
+}
+
+enum Shape<'a> {
+    Atom(String),
+    Form(Form<'a>),
+}
+
+struct Form<'a> {
+    open: &'static str,
+    close: &'static str,
+    head: Option<String>,
+    items: Vec<Item<'a>>,
+    /// How many items continue the line of the head.
+    inline: usize,
+    indent: Indent,
+    /// Whether the items are pairs that share a line, like bindings.
+    pairs: bool,
+    /// Whether the form is laid out over lines even when it fits, as bodies
+    /// of more than one form are.
+    broken: bool,
+}
+
+fn string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\0' => literal.push_str("\\0"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn name(node: &SymbolNode) -> String {
+    match node.namespace() {
+        Some(namespace) => format!("{}/{}", namespace, node.name()),
+        None => node.name().to_string(),
+    }
+}
+
+fn symbol(node: &SymbolNode) -> Item<'_> {
+    Item {
+        shape: Shape::Atom(name(node)),
+        comments: node.comments(),
+        location: node.location(),
+    }
+}
+
+fn vector(node: &VectorNode, pairs: bool) -> Result<Item<'_>> {
+    Ok(Item {
+        shape: Shape::Form(Form {
+            open: "[",
+            close: "]",
+            head: None,
+            items: items(node.items())?,
+            inline: 1,
+            indent: Indent::Align,
+            pairs,
+            broken: false,
+        }),
+        comments: node.comments(),
+        location: node.location(),
+    })
+}
+
+fn map(node: &MapNode) -> Result<Item<'_>> {
+    Ok(Item {
+        shape: Shape::Form(Form {
+            open: "{",
+            close: "}",
+            head: None,
+            items: items(node.items())?,
+            inline: 1,
+            indent: Indent::Align,
+            pairs: true,
+            broken: false,
+        }),
+        comments: node.comments(),
+        location: node.location(),
+    })
+}
+
+fn items(nodes: &[Node]) -> Result<Vec<Item<'_>>> {
+    let mut items = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        items.push(item(node)?);
+    }
+    Ok(items)
+}
+
+/// A form opened by the special form `head`. With `body` the items after
+/// the `inline` ones are a body.
+fn special<'a>(head: &str, items: Vec<Item<'a>>, inline: usize, body: bool) -> Shape<'a> {
+    Shape::Form(Form {
+        open: "(",
+        close: ")",
+        head: Some(head.to_string()),
+        broken: body && items.len() > inline + 1,
+        items,
+        inline,
+        indent: Indent::Body,
+        pairs: false,
+    })
+}
+
+/// A quote or unquote, which hugs what it wraps. That is laid out as it
+/// would be on its own.
+fn prefixed<'a>(open: &'static str, node: &'a Node) -> Result<Shape<'a>> {
+    Ok(Shape::Form(Form {
+        open,
+        close: "",
+        head: None,
+        items: vec![item(node)?],
+        inline: 1,
+        indent: Indent::Align,
+        pairs: false,
+        broken: false,
+    }))
+}
+
+fn item(node: &Node) -> Result<Item<'_>> {
+    let shape: Result<Shape> = match node {
+        Node::Nil(..) => Ok(Shape::Atom("nil".to_string())),
+        Node::Boolean(node) => Ok(Shape::Atom(node.value().to_string())),
+        Node::Number(node) => Ok(Shape::Atom(node.lexeme().to_string())),
+        Node::String(node) => Ok(Shape::Atom(string(node.value()))),
+        Node::Symbol(node) => return Ok(symbol(node)),
+        Node::Keyword(node) => Ok(Shape::Atom(node.to_string())),
+        Node::Vector(node) => return vector(node, false),
+        Node::Map(node) => return map(node),
+        Node::Definition(node) => Ok(special(
+            "def",
+            vec![symbol(node.ident()), item(node.value())?],
+            1,
+            false,
+        )),
+        Node::Function(node) => {
+            let mut all: Vec<Item> = node.name().map(symbol).into_iter().collect();
+            all.push(vector(node.parameters(), false)?);
+            let inline = all.len();
+            all.extend(items(node.body())?);
+            Ok(special("fn", all, inline, true))
+        }
+        Node::If(node) => Ok(special(
+            "if",
+            vec![
+                item(&node.condition)?,
+                item(&node.true_branch)?,
+                item(&node.false_branch)?,
+            ],
+            1,
+            false,
+        )),
+        Node::While(node) => {
+            let mut all = vec![item(node.condition())?];
+            all.extend(items(node.body())?);
+            Ok(special("while", all, 1, true))
+        }
+        Node::Let(node) => {
+            let mut all = vec![vector(node.binding_vector(), true)?];
+            all.extend(items(node.body())?);
+            Ok(special(node.head(), all, 1, true))
+        }
+        Node::Loop(node) => {
+            let mut all = vec![vector(node.binding_vector(), true)?];
+            all.extend(items(node.body())?);
+            Ok(special("loop", all, 1, true))
+        }
+        Node::Do(node) => Ok(special("do", items(node.expressions())?, 0, true)),
+        Node::Recur(node) => Ok(Shape::Form(Form {
+            open: "(",
+            close: ")",
+            head: Some("recur".to_string()),
+            items: items(node.arguments())?,
+            inline: 1,
+            indent: Indent::Align,
+            pairs: false,
+            broken: false,
+        })),
+        Node::FunctionCall(node) => {
+            let (head, mut all) = match node.function() {
+                Node::Symbol(function) if function.comments().is_empty() => {
+                    (Some(name(function)), Vec::new())
+                }
+                function => (None, vec![item(function)?]),
+            };
+            all.extend(items(node.arguments())?);
+            Ok(Shape::Form(Form {
+                open: "(",
+                close: ")",
+                head,
+                items: all,
+                inline: 1,
+                indent: Indent::Align,
+                pairs: false,
+                broken: false,
+            }))
+        }
+        Node::List(node) => Ok(Shape::Form(Form {
+            open: "(",
+            close: ")",
+            head: None,
+            items: items(node.items())?,
+            inline: 1,
+            indent: Indent::Align,
+            pairs: false,
+            broken: false,
+        })),
+        Node::Program(..) => Err(ErrorKind::Internal("a program is not an expression").into()),
+        Node::Quote(node) => prefixed("'", node.expression()),
+        Node::QuasiQuote(node) => prefixed("`", node.expression()),
+        Node::Unquote(node) if node.is_splicing() => prefixed("~@", node.expression()),
+        Node::Unquote(node) => prefixed("~", node.expression()),
+        Node::Meta(..) => Err(ErrorKind::Unsupported("metadata forms").into()),
+        Node::Macro(..) => Err(ErrorKind::Unsupported("macros").into()),
+        Node::Decorator(..) => Err(ErrorKind::Unsupported("decorators").into()),
+    };
+    Ok(Item {
+        shape: shape.map_err(|error| error.at(node.location()))?,
+        comments: node.comments(),
+        location: node.location(),
+    })
+}
+
+impl Item<'_> {
+    fn is_atom(&self) -> bool {
+        matches!(self.shape, Shape::Atom(..)) && self.comments.is_empty()
+    }
+
+    /// The item on a single line, unless it is always broken or comments
+    /// inside it need the line to end.
+    fn flat(&self) -> Option<String> {
+        let form = match &self.shape {
+            Shape::Atom(text) => return Some(text.clone()),
+            Shape::Form(form) => form,
+        };
+        if form.broken || !self.comments.inner.is_empty() {
+            return None;
+        }
+        let mut parts: Vec<String> = form.head.iter().cloned().collect();
+        for item in &form.items {
+            if !item.comments.leading.is_empty() || item.comments.trailing.is_some() {
+                return None;
+            }
+            parts.push(item.flat()?);
+        }
+        Some(format!("{}{}{}", form.open, parts.join(" "), form.close))
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    /// The source line of what was printed last, to keep blank lines.
+    line: usize,
+    /// Whether the current line ends in a comment, so nothing can follow.
+    commented: bool,
+}
+
+impl Printer {
+    fn column(&self) -> usize {
+        let start = self.out.rfind('\n').map_or(0, |newline| newline + 1);
+        self.out[start..].chars().count()
+    }
+
+    fn fits(&self, width: usize) -> bool {
+        self.column() + width <= WIDTH
+    }
+
+    /// Starts a new line at `indent` for what was read from source `line`,
+    /// keeping one blank line if the source had any.
+    fn break_line(&mut self, line: usize, indent: usize) {
+        if !self.out.is_empty() {
+            if line > self.line + 1 {
+                self.out.push('\n');
+            }
+            self.out.push('\n');
+            self.out.extend(std::iter::repeat_n(' ', indent));
+        }
+        self.commented = false;
+    }
+
+    fn comment(&mut self, comment: &Comment, indent: usize) {
+        self.break_line(comment.location.start.line, indent);
+        self.out.push_str(&comment.text);
+        self.line = comment.location.end.line;
+        self.commented = true;
+    }
+
+    /// Prints `item` with its comments, on the current line when `inline`
+    /// and nothing keeps it from there, otherwise on a line of its own.
+    /// `reserve` is the width of the delimiters that will close the line.
+    fn element(
+        &mut self,
+        item: &Item,
+        inline: bool,
+        indent: usize,
+        separate: bool,
+        reserve: usize,
+    ) {
+        if inline && item.comments.leading.is_empty() && !self.commented {
+            if separate {
+                self.out.push(' ');
+            }
+        } else {
+            for comment in &item.comments.leading {
+                self.comment(comment, indent);
+            }
+            self.break_line(item.location.start.line, indent);
+        }
+        self.item(item, reserve);
+        self.line = item.location.end.line;
+        if let Some(comment) = &item.comments.trailing {
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+            self.commented = true;
+        }
+    }
+
+    fn item(&mut self, item: &Item, reserve: usize) {
+        let column = self.column();
+        if let Some(flat) = item.flat() {
+            if column + flat.chars().count() + reserve <= WIDTH {
+                self.out.push_str(&flat);
+                return;
+            }
+        }
+        match &item.shape {
+            Shape::Atom(text) => self.out.push_str(text),
+            Shape::Form(form) => self.form(item, form, column, reserve),
+        }
+    }
+
+    fn form(&mut self, item: &Item, form: &Form, column: usize, reserve: usize) {
+        self.out.push_str(form.open);
+        let indent = match (form.indent, &form.head) {
+            (Indent::Body, _) => column + 2,
+            (Indent::Align, Some(head)) => column + form.open.len() + head.chars().count() + 1,
+            (Indent::Align, None) => column + form.open.len(),
+        };
+        if let Some(head) = &form.head {
+            self.out.push_str(head);
+        }
+        self.line = item.location.start.line;
+        // Arguments that are all atoms fill the lines rather than taking one
+        // each.
+        let fill = form.indent == Indent::Align
+            && !form.pairs
+            && form.items.iter().all(|child| child.is_atom());
+        let mut separate = form.head.is_some();
+        for (i, child) in form.items.iter().enumerate() {
+            let closing = if i + 1 == form.items.len() {
+                reserve + form.close.len()
+            } else {
+                0
+            };
+            let inline = i < form.inline
+                || (form.pairs && i % 2 == 1)
+                || (fill && self.fits(child.flat().unwrap_or_default().len() + 1 + closing));
+            self.element(child, inline, indent, separate, closing);
+            separate = true;
+        }
+        for comment in &item.comments.inner {
+            self.comment(comment, indent);
+        }
+        if self.commented {
+            self.break_line(self.line, indent);
+        }
+        self.out.push_str(form.close);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lays_out_special_forms() {
+        let source = "(def fibonacci-number (fn [n] (if (= n 0) 0 (if (or (= n 1) (= n 2)) 1 (+ (fibonacci-number (- n 1)) (fibonacci-number (- n 2)))))))\n\n\n(fibonacci-number 9)";
+        let expected = "\
+(def fibonacci-number
+  (fn [n]
+    (if (= n 0)
+      0
+      (if (or (= n 1) (= n 2))
+        1
+        (+ (fibonacci-number (- n 1)) (fibonacci-number (- n 2)))))))
+
+(fibonacci-number 9)
+";
+        assert_eq!(format(source).unwrap(), expected);
+        let source = "(let* [first-binding (some-long-function-name 1 2 3) second-binding (another-function first-binding)] (println first-binding second-binding))";
+        let expected = "\
+(let* [first-binding (some-long-function-name 1 2 3)
+       second-binding (another-function first-binding)]
+  (println first-binding second-binding))
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format("(let [a 1] a)").unwrap(), "(let [a 1] a)\n");
+        let source =
+            "(println \"a fairly long string\" \"another fairly long string\" \"and one more that goes past the width\")";
+        let expected = "\
+(println \"a fairly long string\" \"another fairly long string\"
+         \"and one more that goes past the width\")
+";
+        assert_eq!(format(source).unwrap(), expected);
+        let source = "(while (> x 0) (println x) (def x (- x 1)))";
+        let expected = "\
+(while (> x 0)
+  (println x)
+  (def x (- x 1)))
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(
+            format("(while (> x 0) (def x (- x 1)))").unwrap(),
+            "(while (> x 0) (def x (- x 1)))\n"
+        );
+        assert_eq!(format("(:a m)  ::b :ns/c").unwrap(), "(:a m)\n::b\n:ns/c\n");
+        let source = "(def config {:name \"a fairly long name\" :description \"a description that is long enough to wrap\"})";
+        let expected = "\
+(def config
+  {:name \"a fairly long name\"
+   :description \"a description that is long enough to wrap\"})
+";
+        assert_eq!(format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_numbers_as_written() {
+        let source = "(+ 049817234 1.50 1.0 12345678901234567890)";
+        assert_eq!(format(source).unwrap(), format!("{}\n", source));
+    }
+
+    #[test]
+    fn prints_quoted_data() {
+        assert_eq!(format("'(if a b)  ''x").unwrap(), "'(if a b)\n''x\n");
+        let source =
+            "(def names '(alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu))";
+        let expected = "\
+(def names
+  '(alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu))
+";
+        assert_eq!(format(source).unwrap(), expected);
+        let source = "`(let [x# ~a]  (+ x# ~@(rest  b)))";
+        let expected = "`(let [x# ~a] (+ x# ~@(rest b)))\n";
+        assert_eq!(format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let source = "\
+;; header
+
+(def x 1) ; the answer
+(while (> x 0) ; counting down
+    ;; step
+    (def x (- x 1))
+    ;; done
+)
+;; the end";
+        let expected = "\
+;; header
+
+(def x 1) ; the answer
+(while (> x 0) ; counting down
+  ;; step
+  (def x (- x 1))
+  ;; done
+  )
+;; the end
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format(";; only a comment").unwrap(), ";; only a comment\n");
+    }
+
+    #[test]
+    fn is_stable_on_the_examples() {
+        let examples = [
+            include_str!("../collatz.nd"),
+            include_str!("../fact.nd"),
+            include_str!("../fib.nd"),
+            include_str!("../reference.nd"),
+            include_str!("../simple.nd"),
+            "(println \"λ\\t\\\"quoted\\\"\" 1.5 -2 nomad.core/+)",
+        ];
+        for example in &examples {
+            let formatted = format(example).unwrap();
+            assert_eq!(format(&formatted).unwrap(), formatted, "{}", formatted);
+        }
+        // The examples are kept formatted, so `nomad fmt --check` passes on
+        // them.
+        for example in &examples[..5] {
+            assert_eq!(format(example).unwrap(), *example);
+        }
+        assert_eq!(
+            format("(println \"λ\\t\\\"quoted\\\"\" 1.50 nomad.core/+)").unwrap(),
+            "(println \"λ\\t\\\"quoted\\\"\" 1.50 nomad.core/+)\n"
+        );
+    }
+}
@@ -1,5 +1,12 @@
 use crate::result::Result;
+use prettytable::Table;
 
+/// Renders live interpreter state as a table for inspection.
 pub trait Dump {
-    fn dump(&self) -> Result<()>;
+    fn table(&self) -> Result<Table>;
+
+    fn dump(&self) -> Result<()> {
+        self.table()?.printstd();
+        Ok(())
+    }
 }
@@ -1,6 +1,8 @@
 use crate::interpreter::value::{Symbol, Value, Var};
+use crate::interpreter::MAX_DEPTH;
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind as Error;
+use crate::result::runtime::ErrorKind;
+use prettytable::Table;
 use std::collections::HashMap;
 use std::sync::Mutex;
 use std::sync::PoisonError;
@@ -12,11 +14,11 @@ mod scope;
 pub use debug::Dump;
 use namespace::Namespace;
 use pointers::Pointers;
-use scope::Scope;
+pub use scope::Scope;
 
 impl<Guard> From<PoisonError<Guard>> for Error {
-    fn from(error: PoisonError<Guard>) -> Self {
-        Error::StorageIssue
+    fn from(_: PoisonError<Guard>) -> Self {
+        ErrorKind::Internal("a lock was poisoned").into()
     }
 }
 
@@ -29,15 +31,6 @@ mod pointers {
     }
 
     impl Pointers {
-        pub fn set_namespace(&mut self, symbol: Symbol) {
-            if symbol.is_qualified() {
-                panic!("Namespaces should not be qualified.");
-            }
-            self.namespace = symbol;
-        }
-
-        pub fn dump(&self) {}
-
         pub fn new() -> Pointers {
             Pointers {
                 namespace: Symbol::from("nomad.core"),
@@ -51,15 +44,16 @@ pub struct Context {
     namespaces: Mutex<HashMap<Symbol, Arc<Namespace>>>,
     pointers: Mutex<Pointers>,
     scope: Mutex<Scope>,
+    calls: Mutex<usize>,
 }
 
 impl Context {
-    fn current_namespace(&self) -> Result<Arc<Namespace>> {
-        let mut namespaces = self.namespaces.lock()?;
-        let mut pointers = self.pointers.lock()?;
+    pub fn current_namespace(&self) -> Result<Arc<Namespace>> {
+        let namespaces = self.namespaces.lock()?;
+        let pointers = self.pointers.lock()?;
         let namespace = namespaces
             .get(&pointers.namespace)
-            .ok_or(Error::InvalidNamespace)?;
+            .ok_or_else(|| ErrorKind::InvalidNamespace(pointers.namespace.clone()))?;
         Ok(namespace.clone())
     }
 
@@ -70,12 +64,12 @@ impl Context {
     }
 
     pub fn new() -> Context {
-        let mut context = Context {
+        Context {
             namespaces: Mutex::new(HashMap::new()),
             pointers: Mutex::new(Pointers::new()),
             scope: Mutex::new(Scope::new()),
-        };
-        context
+            calls: Mutex::new(0),
+        }
     }
 
     pub fn define<S, V>(&self, symbol: S, value: V) -> Result<Var>
@@ -101,6 +95,7 @@ impl Context {
         Ok(())
     }
 
+    #[cfg(test)]
     pub fn scope_depth(&self) -> Result<usize> {
         let scope = self.scope.lock()?;
         Ok(scope.len())
@@ -112,26 +107,78 @@ impl Context {
         Ok(())
     }
 
+    /// Counts one more nested call, unless calls already nest `MAX_DEPTH`
+    /// deep.
+    pub fn enter(&self) -> Result<()> {
+        let mut calls = self.calls.lock()?;
+        if *calls >= MAX_DEPTH {
+            return Err(ErrorKind::StackOverflow(MAX_DEPTH).into());
+        }
+        *calls += 1;
+        Ok(())
+    }
+
+    pub fn leave(&self) -> Result<()> {
+        let mut calls = self.calls.lock()?;
+        *calls -= 1;
+        Ok(())
+    }
+
+    /// Returns a handle to the current scope chain.
+    pub fn capture(&self) -> Result<Scope> {
+        let scope = self.scope.lock()?;
+        Ok(scope.clone())
+    }
+
+    /// Installs `scope` as the current scope chain and returns the previous one.
+    pub fn swap_scope(&self, scope: Scope) -> Result<Scope> {
+        let mut current = self.scope.lock()?;
+        Ok(std::mem::replace(&mut *current, scope))
+    }
+
     pub fn set(&self, name: Symbol, value: Value) -> Result<()> {
         let mut scope = self.scope.lock()?;
         scope.define(name, value)?;
         Ok(())
     }
 
-    pub fn get(&self, name: &Symbol) -> Result<Value> {
+    pub fn get(&self, name: &Symbol) -> Result<Option<Value>> {
         let scope = self.scope.lock()?;
         scope.resolve(name)
     }
 
     pub fn resolve(&self, name: &Symbol) -> Result<Value> {
-        let namespace = self.current_namespace()?;
-        namespace.resolve(name.clone())
+        let value = match name.namespace() {
+            Some(namespace) => {
+                let namespace = Symbol::from(namespace);
+                let namespaces = self.namespaces.lock()?;
+                let namespace = namespaces
+                    .get(&namespace)
+                    .ok_or(ErrorKind::InvalidNamespace(namespace))?;
+                namespace.resolve(Symbol::from(name.name()))
+            }
+            None => {
+                let namespace = self.current_namespace()?;
+                namespace.resolve(name.clone())
+            }
+        }?;
+        value.ok_or_else(|| ErrorKind::NotDefined(name.clone()).into())
     }
 }
 
 impl Dump for Context {
-    fn dump(&self) -> Result<()> {
-        println!("dumping context");
-        Ok(())
+    /// The loaded namespaces, with the current one marked.
+    fn table(&self) -> Result<Table> {
+        let namespaces = self.namespaces.lock()?;
+        let pointers = self.pointers.lock()?;
+        let mut names: Vec<_> = namespaces.keys().collect();
+        names.sort_by_key(|name| name.to_string());
+        let mut table = Table::new();
+        table.add_row(row!["namespace", "bindings", "current"]);
+        for name in names {
+            let current = if *name == pointers.namespace { "*" } else { "" };
+            table.add_row(row![name.to_string(), namespaces[name].len()?, current]);
+        }
+        Ok(table)
     }
 }
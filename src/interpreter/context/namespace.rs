@@ -1,24 +1,18 @@
+use super::debug::Dump;
 use crate::interpreter::value::{Symbol, Value};
-use crate::result::runtime::ErrorKind as Error;
 use crate::result::Result;
 
-use prettytable::{Cell, Row, Table};
+use prettytable::Table;
+
 use shared_map::SharedMap;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::Mutex;
-use std::sync::PoisonError;
 
 mod shared_map {
     use std::cmp::Eq;
-    use std::collections::hash_map::Iter;
     use std::collections::HashMap;
     use std::hash::Hash;
     use std::sync::Arc;
     use std::sync::Mutex;
-    use std::sync::PoisonError;
 
-    use crate::result::runtime::ErrorKind as Error;
     use crate::result::Result;
 
     #[derive(Debug)]
@@ -38,16 +32,32 @@ mod shared_map {
             Ok(())
         }
 
-        pub fn get(&self, key: &Key) -> Result<Value>
+        pub fn get(&self, key: &Key) -> Result<Option<Value>>
         where
             Value: Clone,
         {
             let root = self.0.clone();
-            let mut root = root.lock()?;
-            match root.get(&key) {
-                Some(value) => Ok(value.clone()),
-                None => Err(Error::NotDefined),
-            }
+            let root = root.lock()?;
+            Ok(root.get(key).cloned())
+        }
+
+        pub fn entries(&self) -> Result<Vec<(Key, Value)>>
+        where
+            Key: Clone,
+            Value: Clone,
+        {
+            let root = self.0.clone();
+            let root = root.lock()?;
+            Ok(root
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect())
+        }
+
+        pub fn len(&self) -> Result<usize> {
+            let root = self.0.clone();
+            let root = root.lock()?;
+            Ok(root.len())
         }
     }
 }
@@ -55,7 +65,6 @@ mod shared_map {
 #[derive(Debug)]
 pub struct Namespace {
     name: Symbol,
-    aliases: SharedMap<Symbol, Namespace>,
     bindings: SharedMap<Symbol, Value>,
 }
 
@@ -67,7 +76,6 @@ impl Namespace {
     pub fn new(name: Symbol) -> Namespace {
         Namespace {
             name,
-            aliases: SharedMap::default(),
             bindings: SharedMap::default(),
         }
     }
@@ -76,18 +84,25 @@ impl Namespace {
         self.bindings.insert(key, value)
     }
 
-    pub fn resolve(&self, key: Symbol) -> Result<Value> {
+    pub fn resolve(&self, key: Symbol) -> Result<Option<Value>> {
         self.bindings.get(&key)
     }
 
-    pub fn dump(&self) {
-        todo!()
-        // let mut table = Table::new();
-        // table.add_row(row![format!("[namespace::{}]", self.name), ""]);
-        // table.add_row(row!["key", "value"]);
-        // for (key, val) in self.bindings.iter() {
-        //     table.add_row(row![key.to_string(), val.to_string()]);
-        // }
-        // table.printstd();
+    pub fn len(&self) -> Result<usize> {
+        self.bindings.len()
+    }
+}
+
+impl Dump for Namespace {
+    fn table(&self) -> Result<Table> {
+        let mut table = Table::new();
+        table.add_row(row![format!("[namespace::{}]", self.name), ""]);
+        table.add_row(row!["key", "value"]);
+        let mut bindings = self.bindings.entries()?;
+        bindings.sort_by_key(|(key, _)| key.to_string());
+        for (key, val) in bindings {
+            table.add_row(row![key.to_string(), val.to_string()]);
+        }
+        Ok(table)
     }
 }
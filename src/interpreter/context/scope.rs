@@ -1,21 +1,28 @@
 use super::debug::Dump;
 use crate::interpreter::value::{Symbol, Value};
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind as Error;
 
-use prettytable::{Cell, Row, Table};
+use prettytable::Table;
 
-use std::borrow::BorrowMut;
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-#[derive(Debug)]
+/// A chain of lexical frames. Cloning a scope is cheap and shares the
+/// frames, which is how closures capture their defining environment.
+#[derive(Clone)]
 pub struct Scope {
     root: Link,
 }
 
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Frames can hold closures that captured this very scope, so the
+        // bindings are not printed here.
+        f.debug_struct("Scope").field("depth", &self.len()).finish()
+    }
+}
+
 type Link = Option<Arc<Node>>;
 type Storage = HashMap<Symbol, Value>;
 
@@ -36,11 +43,7 @@ impl Scope {
     }
 
     pub fn len(&self) -> usize {
-        let mut output = 0;
-        for scope in self.iter() {
-            output += 1;
-        }
-        output
+        self.iter().count()
     }
 
     pub fn push(&mut self) {
@@ -53,9 +56,9 @@ impl Scope {
     }
 
     pub fn pop(&mut self) {
-        self.root.take().map(|node| {
+        if let Some(node) = self.root.take() {
             self.root = node.parent.clone();
-        });
+        }
     }
 
     pub fn define<S, V>(&mut self, name: S, value: V) -> Result<()>
@@ -69,20 +72,20 @@ impl Scope {
             .root
             .as_ref()
             .map(|root| &root.storage)
-            .ok_or(Error::MissingNode)?;
+            .ok_or(ErrorKind::Internal("no scope to define in"))?;
         let mut values = values.lock()?;
         values.insert(name, value);
         Ok(())
     }
 
-    pub fn resolve(&self, name: &Symbol) -> Result<Value> {
+    pub fn resolve(&self, name: &Symbol) -> Result<Option<Value>> {
         for scope in self.iter() {
             let storage = scope.lock()?;
             if let Some(value) = storage.get(name) {
-                return Ok(value.clone());
+                return Ok(Some(value.clone()));
             }
         }
-        Err(Error::BindingNotFound)
+        Ok(None)
     }
 
     fn iter(&self) -> Iter<'_> {
@@ -93,18 +96,19 @@ impl Scope {
 }
 
 impl Dump for Scope {
-    fn dump(&self) -> Result<()> {
+    /// One row per binding, innermost frame first.
+    fn table(&self) -> Result<Table> {
+        let mut table = Table::new();
+        table.add_row(row!["frame", "key", "value"]);
         for (i, storage) in self.iter().enumerate() {
             let storage = storage.lock()?;
-            let mut table = Table::new();
-            table.add_row(row![format!("[SCOPE::{}]", i), ""]);
-            table.add_row(row!["key", "value"]);
-            for (key, val) in storage.iter() {
-                table.add_row(row![key.to_string(), val.to_string()]);
+            let mut bindings: Vec<_> = storage.iter().collect();
+            bindings.sort_by_key(|(key, _)| key.to_string());
+            for (key, val) in bindings {
+                table.add_row(row![i, key.to_string(), val.to_string()]);
             }
-            table.printstd();
         }
-        Ok(())
+        Ok(table)
     }
 }
 
@@ -1,21 +1,69 @@
 use super::operation::*;
+use super::pattern::{Pattern, Patterns};
 use super::value::*;
 use super::Interpreter;
-use super::NodeQuery;
 use crate::ast::node::*;
-use crate::ast::tag::Partition;
 
 use crate::result::runtime::ErrorKind;
 use crate::result::Result;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 
 pub trait Execute {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value>;
 }
 
+/// The outcome of evaluating a node in tail position: either a value, or the
+/// arguments of a `recur` for the enclosing `loop` or `fn` to rebind.
+pub enum Tail {
+    Return(Value),
+    Recur(Vec<Value>),
+}
+
+trait ExecuteTail {
+    fn execute_tail(&self, interpreter: &Interpreter) -> Result<Tail>;
+}
+
+/// Evaluates a body, with its last expression in tail position.
+pub fn execute_body(body: &[Node], interpreter: &Interpreter) -> Result<Tail> {
+    match body.split_last() {
+        Some((last, init)) => {
+            for node in init {
+                node.execute(interpreter)?;
+            }
+            last.execute_tail(interpreter)
+        }
+        None => Ok(Tail::Return(Value::Nil)),
+    }
+}
+
+impl ExecuteTail for Node {
+    fn execute_tail(&self, interpreter: &Interpreter) -> Result<Tail> {
+        let result = match self {
+            Node::Recur(node) => {
+                let mut values = Vec::with_capacity(node.arguments().len());
+                for argument in node.arguments() {
+                    values.push(argument.execute(interpreter)?);
+                }
+                Ok(Tail::Recur(values))
+            }
+            Node::If(node) => {
+                if node.condition.execute(interpreter)?.truthy() {
+                    node.true_branch.execute_tail(interpreter)
+                } else {
+                    node.false_branch.execute_tail(interpreter)
+                }
+            }
+            Node::Do(node) => execute_body(node.expressions(), interpreter),
+            Node::Let(node) => node.execute_tail(interpreter),
+            node => node.execute(interpreter).map(Tail::Return),
+        };
+        result.map_err(|error| error.at(self.location()))
+    }
+}
+
 impl Execute for Node {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        match self {
+        let result = match self {
             Node::Boolean(node) => Ok(Value::make_bool(node.value())),
             Node::Decorator(node) => node.execute(interpreter),
             Node::Definition(node) => node.execute(interpreter),
@@ -23,63 +71,87 @@ impl Execute for Node {
             Node::Function(node) => node.execute(interpreter),
             Node::FunctionCall(node) => node.execute(interpreter),
             Node::If(node) => node.execute(interpreter),
-            Node::Keyword(..) => todo!("keywords are no done"),
-            Node::Let(node) => todo!("need to implement let"),
-            Node::List(node) => todo!(),
-            Node::Loop(node) => todo!(),
+            Node::Keyword(node) => node.execute(interpreter),
+            Node::Let(node) => node.execute(interpreter),
+            Node::List(..) => Err(ErrorKind::Unsupported("lists").into()),
+            Node::Loop(node) => node.execute(interpreter),
             Node::Meta(node) => node.execute(interpreter),
-            Node::Nil => Ok(Value::Nil),
+            Node::Nil(..) => Ok(Value::Nil),
             Node::Number(number) => Ok(Value::make_number(number.value())),
             Node::Program(node) => node.execute(interpreter),
             Node::Quote(node) => node.execute(interpreter),
-            Node::Recur(node) => todo!(),
+            Node::Recur(..) => {
+                Err(ErrorKind::InvalidRecur("recur must be in tail position").into())
+            }
             Node::String(node) => Ok(Value::String(String {
                 value: node.value().to_string(),
             })),
             Node::Symbol(node) => node.execute(interpreter),
             Node::Vector(node) => node.execute(interpreter),
+            Node::Map(node) => node.execute(interpreter),
             Node::While(node) => node.execute(interpreter),
             Node::Macro(node) => node.execute(interpreter),
             Node::QuasiQuote(node) => node.execute(interpreter),
-        }
+            Node::Unquote(..) => {
+                Err(ErrorKind::Internal("an unquote outside a syntax quote").into())
+            }
+        };
+        result.map_err(|error| error.at(self.location()))
     }
 }
 
 impl Execute for SymbolNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        Ok(Value::Symbol(Symbol::from_node(self.clone())))
+        interpreter.resolve(&Symbol::from_node(self.clone()))
+    }
+}
+
+impl Execute for KeywordNode {
+    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
+        let keyword = Keyword::from_node(self);
+        if self.is_expanding() {
+            interpreter.expand(&keyword).map(Value::Keyword)
+        } else {
+            Ok(Value::Keyword(keyword))
+        }
     }
 }
 
 impl Execute for QuasiQuoteNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        println!("{:#?}", self);
-        todo!()
+        quote(self.expression(), self.gensyms(), interpreter)
     }
 }
 
 impl Execute for MacroNode {
-    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        println!("{:#?}", self);
-        todo!()
+    fn execute(&self, _: &Interpreter) -> Result<Value> {
+        Err(ErrorKind::Unsupported("macros").into())
     }
 }
 
 impl Execute for DecoratorNode {
-    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        todo!("decoration nodes are not defined");
+    fn execute(&self, _: &Interpreter) -> Result<Value> {
+        Err(ErrorKind::Unsupported("decorators").into())
     }
 }
 
 impl Execute for VectorNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        let mut vector = Vector::new();
-        for tag in self.items() {
-            let value = tag.execute(interpreter)?;
-            todo!("resolve symbols in vector");
-            vector = vector.push(value);
+        self.items()
+            .iter()
+            .map(|item| item.execute(interpreter))
+            .collect()
+    }
+}
+
+impl Execute for MapNode {
+    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
+        let mut map = Map::new();
+        for (key, value) in self.entries() {
+            let key = key.execute(interpreter)?.into_key()?;
+            map = map.insert(key, value.execute(interpreter)?);
         }
-        Ok(Value::Vector(vector))
+        Ok(Value::Map(map))
     }
 }
 
@@ -105,13 +177,62 @@ impl Execute for IfNode {
 
 impl Execute for QuoteNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        interpreter.interpret_tag(self.expression())
+        quote(self.expression(), &Gensyms::new(), interpreter)
+    }
+}
+
+/// The data `node` reads as. Symbols and lists stay as they were written,
+/// and `'x` inside a quote reads as `(quote x)`. In a syntax quote `~x` is
+/// the value of `x`, `~@xs` splices the items of `xs` into the enclosing
+/// list or vector, and `name#` is the symbol in `gensyms` for it.
+fn quote(node: &Node, gensyms: &Gensyms, interpreter: &Interpreter) -> Result<Value> {
+    let items = |items: &[Node]| -> Result<Vec<Value>> {
+        let mut values = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Node::Unquote(item) if item.is_splicing() => {
+                    for value in item.expression().execute(interpreter)?.items()? {
+                        values.push(value?);
+                    }
+                }
+                item => values.push(quote(item, gensyms, interpreter)?),
+            }
+        }
+        Ok(values)
+    };
+    match node {
+        Node::Symbol(node) => match gensyms.get(node.name()) {
+            Some(name) => Ok(Value::Symbol(Symbol::from(&name[..]))),
+            None => Ok(Value::Symbol(Symbol::from_node(node.clone()))),
+        },
+        Node::List(node) => Ok(Value::List(items(node.items())?.into_iter().collect())),
+        Node::Vector(node) => Ok(items(node.items())?.into_iter().collect()),
+        Node::Map(node) => {
+            let mut map = Map::new();
+            for (key, value) in node.entries() {
+                let key = quote(key, gensyms, interpreter)?;
+                map = map.insert(key, quote(value, gensyms, interpreter)?);
+            }
+            Ok(Value::Map(map))
+        }
+        Node::Quote(node) => {
+            let expression = quote(node.expression(), gensyms, interpreter)?;
+            let list = List::new().conj(expression);
+            Ok(Value::List(list.conj(Value::Symbol(Symbol::from("quote")))))
+        }
+        Node::Unquote(unquote) if unquote.is_splicing() => Err(ErrorKind::InvalidForm {
+            form: "unquote-splicing",
+            reason: "`~@` can only splice into a list or vector",
+        }
+        .at(node.location())),
+        Node::Unquote(node) => node.expression().execute(interpreter),
+        node => node.execute(interpreter),
     }
 }
 
 impl Execute for MetaNode {
-    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        todo!("meta data node not defined");
+    fn execute(&self, _: &Interpreter) -> Result<Value> {
+        Err(ErrorKind::Unsupported("metadata forms").into())
     }
 }
 
@@ -132,16 +253,81 @@ impl Execute for WhileNode {
 
 impl Execute for DefinitionNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        let ident = self.ident().execute(interpreter)?;
-        if !ident.is_local_identifier() {
-            panic!("invalid identifier")
+        if self.ident().is_qualified() {
+            return Err(ErrorKind::InvalidForm {
+                form: "def",
+                reason: "the name can not be qualified",
+            }
+            .into());
         }
-        let ident = ident.take_symbol().ok_or(ErrorKind::InvalidNode)?;
-        let value = self.value().execute(interpreter)?;
+        let ident = Symbol::from_node(self.ident().clone());
+        let value = match self.value() {
+            Node::Function(function) if function.name().is_none() => {
+                make_function(function, interpreter, Some(ident.clone()))?.into()
+            }
+            node => node.execute(interpreter)?,
+        };
         interpreter.define(ident, value)
     }
 }
 
+impl ExecuteTail for LetNode {
+    fn execute_tail(&self, interpreter: &Interpreter) -> Result<Tail> {
+        interpreter.with_frame(|| {
+            for (pattern, value) in self.bindings() {
+                let pattern = Pattern::from_node(pattern)?;
+                pattern.bind(value.execute(interpreter)?, interpreter)?;
+            }
+            execute_body(self.body(), interpreter)
+        })
+    }
+}
+
+impl Execute for LetNode {
+    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
+        match self.execute_tail(interpreter)? {
+            Tail::Return(value) => Ok(value),
+            Tail::Recur(..) => {
+                Err(ErrorKind::InvalidRecur("recur must be in tail position").into())
+            }
+        }
+    }
+}
+
+impl Execute for LoopNode {
+    fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
+        let mut patterns = Vec::with_capacity(self.bindings.items().len() / 2);
+        for (pattern, _) in self.bindings() {
+            patterns.push(Pattern::from_node(pattern)?);
+        }
+        let mut values: Option<Vec<Value>> = None;
+        // Each iteration runs in its own frame, `recur` hands the next values
+        // back here instead of calling into the body again.
+        loop {
+            let current = values.take();
+            let tail = interpreter.with_frame(|| {
+                match current {
+                    None => {
+                        for (pattern, (_, value)) in patterns.iter().zip(self.bindings()) {
+                            pattern.bind(value.execute(interpreter)?, interpreter)?;
+                        }
+                    }
+                    Some(current) => {
+                        for (pattern, value) in patterns.iter().zip(current) {
+                            pattern.bind(value, interpreter)?;
+                        }
+                    }
+                }
+                execute_body(self.body(), interpreter)
+            })?;
+            match tail {
+                Tail::Return(value) => return Ok(value),
+                Tail::Recur(next) => values = Some(next),
+            }
+        }
+    }
+}
+
 impl Execute for DoNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
         let mut result = Value::Nil;
@@ -154,66 +340,36 @@ impl Execute for DoNode {
 
 impl Execute for FunctionCallNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        let function = interpreter.interpret_and_resolve_tag(self.function())?;
-        match function {
-            Value::Function(function) => {
-                todo!()
-                // let parameters = function.parameters.into_iter();
-                // let arguments = {
-                //     let mut arguments = vec![];
-                //     for tag in self.arguments() {
-                //         arguments.push(interpreter.interpret_and_resolve_tag(tag)?);
-                //     }
-                //     arguments.into_iter()
-                // };
-                //
-                // let mut result = Value::Nil;
-                // for (s, v) in parameters.zip(arguments) {
-                //     interpreter.set(s, v);
-                // }
-                // // a new scope must be defined after the parameters are set
-                // interpreter.context.push_scope();
-                // for b in function.body {
-                //     result = interpreter.interpret_tag(b)?;
-                // }
-                // interpreter.context.pop_scope();
-                // Ok(result)
-            }
-            value => panic!("{:?} is not callable", value),
+        let function = self.function().execute(interpreter)?.callable()?;
+        let mut arguments = Vec::with_capacity(self.arguments().len());
+        for node in self.arguments() {
+            arguments.push(node.execute(interpreter)?);
         }
+        apply(&function, arguments, interpreter)
     }
 }
 
-// fn all_symbols(parameters: &VectorNode) -> bool {
-//     parameters.items().iter().all(|item| item.is_symbol())
-// }
+/// Builds the closure for `node`. Anonymous functions bound with `def` are
+/// labelled with the var name so errors can refer to them, but only `(fn name
+/// [..] ..)` binds its own name inside the body.
+fn make_function(
+    node: &FunctionNode,
+    interpreter: &Interpreter,
+    label: Option<Symbol>,
+) -> Result<UserFunction> {
+    let parameters = Patterns::from_nodes(node.parameters().items())?;
+    let name = node.name().cloned().map(Symbol::from_node);
+    Ok(UserFunction {
+        recursive: name.is_some(),
+        name: name.or(label),
+        parameters,
+        body: Arc::new(node.body().clone()),
+        scope: interpreter.capture_scope()?,
+    })
+}
 
 impl Execute for FunctionNode {
     fn execute(&self, interpreter: &Interpreter) -> Result<Value> {
-        todo!()
-        // let parameter_nodes = interpreter.get_vector(self.parameters())?;
-        // if all_symbols(&parameter_nodes) {
-        //     let parameters: Vec<_> = interpreter
-        //         .get_symbols(&parameter_nodes.items())?
-        //         .iter()
-        //         .flat_map(|node| {
-        //             node.execute(interpreter)
-        //                 .ok()
-        //                 .and_then(|value| value.take_symbol())
-        //         })
-        //         .collect();
-        //     Ok(Value::Function(Function {
-        //         name: {
-        //             self.name()
-        //                 .and_then(|name| interpreter.get_symbol(name).ok())
-        //                 .and_then(|symbol_node| symbol_node.execute(interpreter).ok())
-        //                 .and_then(|value| value.take_symbol())
-        //         },
-        //         parameters,
-        //         body: self.body(),
-        //     }))
-        // } else {
-        //     todo!("handle destructing and variadic parameters")
-        // }
+        Ok(make_function(self, interpreter, None)?.into())
     }
 }
@@ -1,4 +1,20 @@
-use std::sync::Arc;
-use std::sync::Mutex;
+use crate::bytecode::Closure;
 
-pub struct Frame {}
+/// A call in progress on the virtual machine. `base` is the index of the
+/// operand stack slot holding the callee, the frame's locals follow it.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub closure: Closure,
+    pub ip: usize,
+    pub base: usize,
+}
+
+impl Frame {
+    pub fn new(closure: Closure, base: usize) -> Frame {
+        Frame {
+            closure,
+            ip: 0,
+            base,
+        }
+    }
+}
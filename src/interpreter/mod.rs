@@ -1,23 +1,17 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::Mutex;
-
 use crate::ast::node::Node;
-use crate::ast::node::SymbolNode;
-use crate::ast::node::VectorNode;
-use crate::ast::parser::AST;
-use crate::ast::Tag;
 use crate::prelude::*;
-use crate::result::runtime::ErrorKind;
-use crate::result::Result;
+use prettytable::Table;
 
 mod context;
 mod execution;
-mod frame;
+pub(crate) mod frame;
 mod operation;
+pub(crate) mod pattern;
 mod value;
 
 use context::Context;
 use context::Dump;
+use context::Scope;
 use execution::Execute;
 
 pub use operation::Compare;
@@ -25,65 +19,35 @@ pub use operation::Concat;
 pub use operation::Introspection;
 pub use operation::Length;
 pub use operation::Math;
+pub use operation::Seq;
 
+pub use value::apply;
 use value::nf;
-use value::Symbol;
+pub use value::Arity;
+pub use value::Function;
+pub use value::Keyword;
+pub use value::List;
+pub use value::Map;
+pub use value::Symbol;
 pub use value::Value;
 
-pub trait NodeQuery {
-    fn get_nodes(&self, tags: Vec<Tag>) -> Result<Vec<Node>>;
-    fn get_node(&self, tag: Tag) -> Result<Node>;
-    fn get_vector(&self, tag: Tag) -> Result<VectorNode>;
-    fn get_symbol(&self, tags: Tag) -> Result<SymbolNode>;
-    fn get_symbols(&self, tags: &Vec<Tag>) -> Result<Vec<SymbolNode>>;
-}
-
-impl NodeQuery for Interpreter {
-    fn get_nodes(&self, tags: Vec<Tag>) -> Result<Vec<Node>> {
-        let mut nodes = vec![];
-        for tag in tags {
-            nodes.push(self.get_node(tag)?);
-        }
-        Ok(nodes)
-    }
+/// The native stack to evaluate on.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
 
-    fn get_node(&self, tag: Tag) -> Result<Node> {
-        self.ast
-            .as_ref()
-            .and_then(|ast| ast.get(&tag).cloned())
-            .ok_or(ErrorKind::NodeNotFound)
-    }
+/// How much of the stack a call may take. Debug builds take about 10KiB for
+/// a call whose body nests a few forms deep.
+const FRAME_SIZE: usize = 32 * 1024;
 
-    fn get_vector(&self, tag: Tag) -> Result<VectorNode> {
-        if let Node::Vector(node) = self.get_node(tag)? {
-            Ok(node)
-        } else {
-            Err(ErrorKind::MissingNode)
-        }
-    }
+/// How deep calls may nest. The tree walker recurses on the native stack, so
+/// past this a call fails instead of overflowing it. The virtual machine
+/// keeps to the same limit so that both engines accept the same programs.
+pub const MAX_DEPTH: usize = STACK_SIZE / FRAME_SIZE;
 
-    fn get_symbol(&self, tag: Tag) -> Result<SymbolNode> {
-        if let Node::Symbol(node) = self.get_node(tag)? {
-            Ok(node)
-        } else {
-            Err(ErrorKind::MissingNode)
-        }
-    }
-
-    fn get_symbols(&self, tags: &Vec<Tag>) -> Result<Vec<SymbolNode>> {
-        let mut symbols = vec![];
-        for tag in tags {
-            symbols.push(self.get_symbol(*tag)?);
-        }
-        Ok(symbols)
-    }
-}
-
-#[derive(Debug)]
+/// Clones share the same context, so a value that calls back into the
+/// interpreter later, such as a lazy sequence, can keep its own handle.
+#[derive(Debug, Clone)]
 pub struct Interpreter {
-    ast: Option<AST>,
-    context: Context,
-    values: Mutex<HashMap<value::Symbol, value::Value>>,
+    context: Arc<Context>,
 }
 
 impl Interpreter {
@@ -104,79 +68,795 @@ impl Interpreter {
             context.define("get", Get)?;
             context.define("conj", Conj)?;
             context.define("count", Count)?;
+            context.define("assoc", Assoc)?;
+            context.define("dissoc", Dissoc)?;
+            context.define("pop", Pop)?;
+            context.define("list", MakeList)?;
+            context.define("first", First)?;
+            context.define("rest", Rest)?;
+            context.define("cons", Cons)?;
+            context.define("nth", Nth)?;
+            context.define("seq", ToSeq)?;
+            context.define("next", Next)?;
+            context.define("map", MapSeq)?;
+            context.define("filter", Filter)?;
+            context.define("reduce", Reduce)?;
+            context.define("take", Take)?;
+            context.define("drop", DropSeq)?;
+            context.define("range", Range)?;
+            context.define("into", IntoSeq)?;
+            context.define("concat", ConcatSeq)?;
+            context.define("reverse", Reverse)?;
+            context.define("contains?", Contains)?;
+            context.define("keys", Keys)?;
+            context.define("vals", Vals)?;
             context.define("print", Print)?;
             context.define("println", Println)?;
+            context.define("not", Not)?;
+            context.define("and", And)?;
+            context.define("or", Or)?;
             context.define("*version*", Value::make_number(0.into()))?;
             context
         };
         Ok(Interpreter {
-            ast: None,
-            context,
-            values: Mutex::new(HashMap::new()),
+            context: Arc::new(context),
         })
     }
 
-    pub fn dump_context(&self) {
-        self.context.dump();
+    pub fn dump_context(&self) -> Result<()> {
+        self.context.dump()
+    }
+
+    /// The loaded namespaces, with the current one marked.
+    pub fn namespaces_table(&self) -> Result<Table> {
+        self.context.table()
+    }
+
+    /// The bindings of the current namespace.
+    pub fn namespace_table(&self) -> Result<Table> {
+        self.context.current_namespace()?.table()
     }
 
-    pub(crate) fn put(&self, symbol: value::Symbol, atom: value::Value) {
-        let mut values = self.values.lock().unwrap();
-        values.insert(symbol, atom);
+    /// The bindings of the current scope chain, innermost frame first.
+    pub fn scope_table(&self) -> Result<Table> {
+        self.context.capture()?.table()
     }
 
-    pub fn interpret_tag(&self, tag: Tag) -> Result<Value> {
-        let node = self.get_node(tag)?;
-        node.execute(&self)
+    /// Evaluates a node produced by `parse`. A `ProgramNode` evaluates each
+    /// top-level form in order and yields the value of the last one.
+    pub fn eval(&self, node: &Node) -> Result<Value> {
+        node.execute(self)
     }
 
+    /// Looks a symbol up in the scope chain first, then in the namespaces.
     pub fn resolve(&self, symbol: &Symbol) -> Result<Value> {
-        if let Ok(value) = self.context.get(symbol) {
-            Ok(value)
-        } else {
-            self.context.resolve(symbol)
+        match self.context.get(symbol)? {
+            Some(value) => Ok(value),
+            None => self.context.resolve(symbol),
         }
     }
 
-    pub fn interpret_and_resolve_tags(&self, tags: Vec<Tag>) -> Result<Vec<Value>> {
-        let mut values = vec![];
-        for tag in tags {
-            values.push(self.interpret_and_resolve_tag(tag)?);
+    /// Qualifies a keyword written `::name` with the current namespace.
+    pub fn expand(&self, keyword: &Keyword) -> Result<Keyword> {
+        Ok(keyword.qualify(self.context.current_namespace()?.name()))
+    }
+
+    pub fn define(&self, symbol: Symbol, value: Value) -> Result<Value> {
+        self.context.define(symbol, value).map(Value::Var)
+    }
+
+    pub fn set(&self, symbol: Symbol, value: Value) -> Result<()> {
+        self.context.set(symbol, value)
+    }
+
+    pub(crate) fn capture_scope(&self) -> Result<Scope> {
+        self.context.capture()
+    }
+
+    /// Runs `body` as one more nested call, failing instead once calls nest
+    /// `MAX_DEPTH` deep.
+    pub(crate) fn with_call<T, F>(&self, body: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        self.context.enter()?;
+        let result = body();
+        self.context.leave()?;
+        result
+    }
+
+    /// Runs `body` in a fresh frame on top of the current scope, popping the
+    /// frame afterwards whether or not `body` succeeded.
+    pub(crate) fn with_frame<T, F>(&self, body: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        self.context.push_scope()?;
+        let result = body();
+        self.context.pop_scope()?;
+        result
+    }
+
+    /// Runs `body` in a fresh frame on top of `scope`, restoring the caller's
+    /// scope chain afterwards whether or not `body` succeeded.
+    pub(crate) fn with_scope<T, F>(&self, scope: Scope, body: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        let previous = self.context.swap_scope(scope)?;
+        let result = self.context.push_scope().and_then(|_| body());
+        self.context.swap_scope(previous)?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval_located(source: &str) -> Result<Value> {
+        let tokens = Scanner::scan(source.to_string())?;
+        let program = parse(tokens)?;
+        Interpreter::boot()?.eval(&program)
+    }
+
+    fn eval(source: &str) -> std::result::Result<Value, ErrorKind> {
+        eval_located(source).map_err(|error| *error.kind)
+    }
+
+    #[test]
+    fn program_yields_last_form() {
+        let value = eval("(def a 1) (def b 2) b").unwrap();
+        assert_eq!(value.to_string(), "2");
+    }
+
+    #[test]
+    fn def_returns_var() {
+        let value = eval("(def a 1)").unwrap();
+        assert_eq!(value.to_string(), "#'nomad.core/a");
+    }
+
+    #[test]
+    fn qualified_symbols_resolve_through_namespace() {
+        let value = eval("(def a 1) nomad.core/a").unwrap();
+        assert_eq!(value.to_string(), "1");
+    }
+
+    #[test]
+    fn unknown_symbols_are_errors() {
+        assert!(eval("missing").is_err());
+    }
+
+    #[test]
+    fn functions_bind_their_parameters() {
+        let value = eval("(def second (fn [a b] a b)) (second 1 2)").unwrap();
+        assert_eq!(value.to_string(), "2");
+    }
+
+    #[test]
+    fn functions_close_over_their_defining_scope() {
+        let source = "(def constantly (fn [x] (fn [y] x))) ((constantly 1) 2)";
+        assert_eq!(eval(source).unwrap().to_string(), "1");
+    }
+
+    #[test]
+    fn parameters_do_not_leak_into_the_caller() {
+        assert!(eval("(def id (fn [x] x)) (id 1) x").is_err());
+    }
+
+    #[test]
+    fn named_functions_can_refer_to_themselves() {
+        let value = eval("((fn me [x] me) 1)").unwrap();
+        assert_eq!(value.to_string(), "[fn:me]");
+    }
+
+    #[test]
+    fn arithmetic_folds_left_to_right() {
+        assert_eq!(eval("(+)").unwrap().to_string(), "0");
+        assert_eq!(eval("(+ 1 2 3)").unwrap().to_string(), "6");
+        assert_eq!(eval("(- 10 2 3)").unwrap().to_string(), "5");
+        assert_eq!(eval("(- 2)").unwrap().to_string(), "-2");
+        assert_eq!(eval("(* 2 3 4)").unwrap().to_string(), "24");
+        assert_eq!(eval("(/ 12 2 3)").unwrap().to_string(), "2");
+        assert_eq!(eval("(/ 2)").unwrap().to_string(), "0.5");
+        assert_eq!(eval("(mod 10 4)").unwrap().to_string(), "2");
+        assert!(eval("(+ 1 \"two\")").is_err());
+    }
+
+    #[test]
+    fn comparisons_chain() {
+        assert_eq!(eval("(> 8 7 6)").unwrap().to_string(), "true");
+        assert_eq!(eval("(> 8 7 9)").unwrap().to_string(), "false");
+        assert_eq!(eval("(< 1 2 3)").unwrap().to_string(), "true");
+        assert_eq!(eval("(= 0 (mod 15 3) (mod 15 5))").unwrap().to_string(), "true");
+        assert_eq!(eval("(= 0 (mod 9 3) (mod 9 5))").unwrap().to_string(), "false");
+        assert_eq!(eval("(= \"a\" \"a\")").unwrap().to_string(), "true");
+    }
+
+    #[test]
+    fn logic_follows_truthiness() {
+        assert_eq!(eval("(or nil false 3)").unwrap().to_string(), "3");
+        assert_eq!(eval("(and 1 nil 2)").unwrap().to_string(), "nil");
+        assert_eq!(eval("(not nil)").unwrap().to_string(), "true");
+    }
+
+    #[test]
+    fn count_measures_strings() {
+        assert_eq!(eval("(count \"nomad\")").unwrap().to_string(), "5");
+        assert_eq!(eval("(count \"héllo\")").unwrap().to_string(), "5");
+        assert_eq!(eval("(count nil)").unwrap().to_string(), "0");
+    }
+
+    #[test]
+    fn arity_is_checked_before_native_dispatch() {
+        match eval("(mod 1 2 3)") {
+            Err(InvalidArgumentArity {
+                function,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(function, "mod");
+                assert_eq!(expected, Arity::Curried(2));
+                assert_eq!(actual, 3);
+            }
+            other => panic!("expected an arity error, got {:?}", other),
         }
-        Ok(values)
+        assert!(eval("(now 1)").is_err());
+        assert!(eval("(get nil)").is_err());
+        assert!(eval("(get nil 1 2)").is_ok());
     }
 
-    pub fn interpret_and_resolve_tag(&self, tag: Tag) -> Result<Value> {
-        let value = self.interpret_tag(tag)?;
-        if let Some(symbol) = value.as_symbol() {
-            println!("{:?}", symbol);
-            self.resolve(symbol)
-        } else {
-            Ok(value)
+    #[test]
+    fn arity_is_checked_before_user_dispatch() {
+        match eval("(def pair (fn [a b] a)) (pair 1 2 3)") {
+            Err(InvalidArgumentArity {
+                function,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(function, "pair");
+                assert_eq!(expected, Arity::Fixed(2));
+                assert_eq!(actual, 3);
+            }
+            other => panic!("expected an arity error, got {:?}", other),
         }
     }
 
-    pub fn root(&self) -> Option<Tag> {
-        self.ast
-            .as_ref()
-            .and_then(|ast| ast.root)
-            .map(|tag| tag.clone())
+    #[test]
+    fn under_applied_user_functions_are_curried() {
+        let source = "(def add3 (fn [a b c] (+ a b c))) (def add1 (add3 1))";
+        assert_eq!(eval(&format!("{} add1", source)).unwrap().to_string(), "[partial:add3 1]");
+        assert_eq!(eval(&format!("{} ((add1 2) 3)", source)).unwrap().to_string(), "6");
+        assert_eq!(eval(&format!("{} (add1 2 3)", source)).unwrap().to_string(), "6");
+        assert!(eval(&format!("{} (add1 2 3 4)", source)).is_err());
     }
 
-    pub fn eval(&mut self, ast: AST) -> Result<Value> {
-        self.ast = Some(ast);
-        if let Some(root) = self.root() {
-            self.interpret_and_resolve_tag(root)
-        } else {
-            Ok(Value::Nil)
+    #[test]
+    fn natives_opt_into_currying() {
+        assert_eq!(eval("((mod 10) 4)").unwrap().to_string(), "2");
+        assert!(eval("(count)").is_err());
+    }
+
+    #[test]
+    fn rest_parameters_collect_into_a_vector() {
+        let source = "(def tail (fn [a & more] more)) (tail 1 2 3)";
+        assert_eq!(eval(source).unwrap().to_string(), "( 2 3 )");
+        let source = "(def tail (fn [a & more] more)) (tail 1)";
+        assert_eq!(eval(source).unwrap().to_string(), "( )");
+        assert!(eval("(def tail (fn [a & more] more)) (tail)").is_err());
+    }
+
+    #[test]
+    fn parameters_destructure_nested_vectors() {
+        let source = "(def v (fn [& xs] xs))
+                      (def f (fn [[a [b & c]] d] (+ a b d (count c))))
+                      (f (v 1 (v 2 3 4)) 5)";
+        assert_eq!(eval(source).unwrap().to_string(), "10");
+    }
+
+    #[test]
+    fn destructuring_rejects_mismatched_shapes() {
+        let prelude = "(def v (fn [& xs] xs)) (def f (fn [[a b]] a))";
+        match eval(&format!("{} (f 1)", prelude)) {
+            Err(BindingMismatch(..)) => {}
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+        match eval(&format!("{} (f (v 1))", prelude)) {
+            Err(BindingMismatch(..)) => {}
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+        match eval("(let [{a :a} [1]] a)") {
+            Err(BindingMismatch(..)) => {}
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+        match eval("(fn [a &] a)") {
+            Err(InvalidBinding(..)) => {}
+            other => panic!("expected an invalid binding, got {:?}", other),
         }
     }
 
-    pub fn define(&self, symbol: Symbol, value: Value) -> Result<Value> {
-        self.context.define(symbol.clone(), value).map(Value::Var)
+    #[test]
+    fn let_binds_sequentially() {
+        assert_eq!(eval("(let* [a 1 b (+ a 1)] b)").unwrap().to_string(), "2");
+        assert_eq!(eval("(let [a 1] (let [a 2] a))").unwrap().to_string(), "2");
+        assert_eq!(eval("(let [] 1 2)").unwrap().to_string(), "2");
+        assert!(eval("(let [a] a)").is_err());
+    }
+
+    #[test]
+    fn let_destructures() {
+        let source = "(def v (fn [& xs] xs)) (let [[a & b] (v 1 2 3)] (+ a (count b)))";
+        assert_eq!(eval(source).unwrap().to_string(), "3");
+    }
+
+    #[test]
+    fn maps_destructure_by_key() {
+        let source = "(let [{a :a [b c] :b} {:a 1 :b [2 3]}] (+ a b c))";
+        assert_eq!(eval(source).unwrap().to_string(), "6");
+        let source = "(def k \"n\") (def f (fn [{{c :c} :b} {n k}] [c n]))
+                      (f {:b {:c 1}} {\"n\" 2})";
+        assert_eq!(eval(source).unwrap().to_string(), "( 1 2 )");
+        let source = "[(let [{a :a} {}] a) (let [{a :a} nil] a)]";
+        assert_eq!(eval(source).unwrap().to_string(), "( nil nil )");
+        let source = "(loop [{i :i} {:i 0}] (if (< i 3) (recur {:i (+ i 1)}) i))";
+        assert_eq!(eval(source).unwrap().to_string(), "3");
+    }
+
+    #[test]
+    fn let_pops_its_scope_even_on_error() {
+        let interpreter = Interpreter::boot().unwrap();
+        let run = |source: &str| {
+            let tokens = Scanner::scan(source.to_string()).unwrap();
+            interpreter.eval(&parse(tokens).unwrap())
+        };
+        let depth = interpreter.context.scope_depth().unwrap();
+        assert!(run("(let [a 1] missing)").is_err());
+        assert_eq!(interpreter.context.scope_depth().unwrap(), depth);
+        assert!(run("a").is_err());
+    }
+
+    #[test]
+    fn loop_rebinds_until_it_returns() {
+        let source = "(loop [i 1 acc 1] (if (> i 5) acc (recur (+ i 1) (* acc i))))";
+        assert_eq!(eval(source).unwrap().to_string(), "120");
+    }
+
+    #[test]
+    fn recur_runs_in_constant_stack() {
+        let source = "(loop [i 0] (if (< i 100000) (recur (+ i 1)) i))";
+        assert_eq!(eval(source).unwrap().to_string(), "100000");
+        let source = "(def count-down (fn [n] (if (= n 0) n (let [m (- n 1)] (recur m)))))
+                      (count-down 100000)";
+        assert_eq!(eval(source).unwrap().to_string(), "0");
+    }
+
+    #[test]
+    fn recur_targets_variadic_functions() {
+        let source = "(def f (fn [n & xs] (if (= n 0) (count xs) (recur (- n 1) xs))))
+                      (f 3 1 2)";
+        assert_eq!(eval(source).unwrap().to_string(), "2");
+    }
+
+    #[test]
+    fn recur_is_checked_when_parsing() {
+        let parse_source = |source: &str| {
+            parse(Scanner::scan(source.to_string()).unwrap()).map_err(|error| *error.kind)
+        };
+        match parse_source("(loop [i 0] (+ 1 (recur i)))") {
+            Err(InvalidRecur(..)) => {}
+            other => panic!("expected a tail position error, got {:?}", other),
+        }
+        match parse_source("(recur 1)") {
+            Err(InvalidRecur(..)) => {}
+            other => panic!("expected a missing target error, got {:?}", other),
+        }
+        match parse_source("(loop [i 0 j 1] (recur i))") {
+            Err(InvalidRecurArity { expected, actual }) => assert_eq!((expected, actual), (2, 1)),
+            other => panic!("expected an arity error, got {:?}", other),
+        }
+        match parse_source("(fn [a] (loop [b a] (fn [c d] (recur c d))) (recur a))") {
+            Ok(..) => {}
+            other => panic!("expected nested targets to parse, got {:?}", other),
+        }
+        assert!(parse_source("(loop [i 0] (while true (recur i)))").is_err());
+    }
+
+    #[test]
+    fn keywords_evaluate_to_themselves() {
+        assert_eq!(eval(":a").unwrap().to_string(), ":a");
+        assert_eq!(eval(":ns/a").unwrap().to_string(), ":ns/a");
+        assert_eq!(eval("::a").unwrap().to_string(), ":nomad.core/a");
+        assert_eq!(eval("(= :a :a)").unwrap().to_string(), "true");
+        assert_eq!(eval("(= :a :ns/a)").unwrap().to_string(), "false");
+        assert_eq!(eval("(= ::a :nomad.core/a)").unwrap().to_string(), "true");
+        assert_eq!(eval("(def k :a) (= k :a)").unwrap().to_string(), "true");
+        match eval(":a") {
+            Ok(Value::Keyword(keyword)) => {
+                assert_eq!(keyword, Keyword::intern(None, "a"));
+                assert_eq!(keyword.to_string(), Keyword::intern(None, "a").text());
+            }
+            other => panic!("expected a keyword, got {:?}", other),
+        }
+        for input in [":", "::", "::ns/a", ":/a"].iter() {
+            assert!(eval(input).is_err(), "{:?} should fail", input);
+        }
+    }
+
+    #[test]
+    fn keywords_look_themselves_up() {
+        assert_eq!(eval("(:a nil)").unwrap().to_string(), "nil");
+        assert_eq!(eval("(:a nil 1)").unwrap().to_string(), "1");
+        let source = "(def v (fn [& xs] xs)) (:a (v 1) 2)";
+        assert_eq!(eval(source).unwrap().to_string(), "2");
+        assert_eq!(eval("(get nil :a 3)").unwrap().to_string(), "3");
+        match eval("(:a)") {
+            Err(error @ InvalidArgumentArity { .. }) => assert_eq!(
+                error.to_string(),
+                "`:a` expects 1 or 2 arguments but was given 0"
+            ),
+            other => panic!("expected an arity error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn maps_are_persistent_values() {
+        assert_eq!(eval("{:a 1}").unwrap().to_string(), "{ :a 1 }");
+        assert_eq!(eval("(count {:a 1 :b 2 :a 3})").unwrap().to_string(), "2");
+        assert_eq!(eval("(get {:a 1} :a)").unwrap().to_string(), "1");
+        assert_eq!(eval("(:b {:a 1} 0)").unwrap().to_string(), "0");
+        let source = "(= {:a 1 :b 2} {:b 2 :a 1})";
+        assert_eq!(eval(source).unwrap().to_string(), "true");
+        assert_eq!(eval("(= {:a 1} {:a 2})").unwrap().to_string(), "false");
+        let source = "(def m {:a 1}) (def n (assoc m :b 2)) (+ (count m) (count n))";
+        assert_eq!(eval(source).unwrap().to_string(), "3");
+        assert_eq!(eval("(dissoc {:a 1} :a)").unwrap().to_string(), "{ }");
+        assert_eq!(eval("(assoc nil :a 1)").unwrap().to_string(), "{ :a 1 }");
+        assert_eq!(eval("(contains? {:a nil} :a)").unwrap().to_string(), "true");
+        assert_eq!(eval("(contains? {} :a)").unwrap().to_string(), "false");
+        assert_eq!(eval("(keys {:a 1})").unwrap().to_string(), "( :a )");
+        assert_eq!(eval("(vals {:a 1})").unwrap().to_string(), "( 1 )");
+        let source = "(def v (fn [& xs] xs)) (get (conj {(v 1) 2} (v {:a 1} 3)) {:a 1})";
+        assert_eq!(eval(source).unwrap().to_string(), "3");
+        match eval("(assoc (fn [] 1) :a 1)") {
+            Err(TypeMismatch { expected, .. }) => assert_eq!(expected, "a vector or map"),
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vectors_evaluate_their_items() {
+        assert_eq!(eval("[1 2 (+ 1 2)]").unwrap().to_string(), "( 1 2 3 )");
+        assert_eq!(eval("[]").unwrap().to_string(), "( )");
+        assert_eq!(eval("(= [1 [2]] [1 [2]])").unwrap().to_string(), "true");
+        assert_eq!(eval("(= [1 2] [2 1])").unwrap().to_string(), "false");
+        assert_eq!(eval("(get {[1 2] :a} [1 2])").unwrap().to_string(), ":a");
+        let source = "(def v [1 2 3]) (def w (assoc v 1 :b)) [v w (pop v) (assoc v 3 4)]";
+        assert_eq!(
+            eval(source).unwrap().to_string(),
+            "( ( 1 2 3 ) ( 1 :b 3 ) ( 1 2 ) ( 1 2 3 4 ) )"
+        );
+        assert_eq!(eval("(pop nil)").unwrap().to_string(), "nil");
+        match eval("(assoc [1] 5 2)") {
+            Err(KeyNotFound(key)) => assert_eq!(key.to_string(), "5"),
+            other => panic!("expected a missing key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn quoted_lists_are_data() {
+        assert_eq!(eval("'(1 2 3)").unwrap().to_string(), "( 1 2 3 )");
+        assert_eq!(eval("'()").unwrap().to_string(), "( )");
+        assert_eq!(eval("(count '(if a b c))").unwrap().to_string(), "4");
+        assert_eq!(eval("(first '(a b))").unwrap().type_name(), "symbol");
+        assert_eq!(eval("''a").unwrap().to_string(), "( quote a )");
+        assert_eq!(eval("'[a (b)]").unwrap().to_string(), "( a ( b ) )");
+        let source = "(= '(1 2) [1 2] (list 1 2))";
+        assert_eq!(eval(source).unwrap().to_string(), "true");
+        assert_eq!(eval("(get {'(1 2) :a} [1 2])").unwrap().to_string(), ":a");
+        assert_eq!(eval("(conj '(2 3) 1)").unwrap().to_string(), "( 1 2 3 )");
+        assert_eq!(eval("(cons 0 [1 2])").unwrap().to_string(), "( 0 1 2 )");
+        assert_eq!(eval("(rest '(1 2 3))").unwrap().to_string(), "( 2 3 )");
+        assert_eq!(eval("(rest nil)").unwrap().to_string(), "( )");
+        assert_eq!(eval("(first nil)").unwrap().to_string(), "nil");
+        assert_eq!(eval("(nth '(1 2 3) 2)").unwrap().to_string(), "3");
+        assert_eq!(eval("(nth [1 2 3] 5 :none)").unwrap().to_string(), ":none");
+        let source = "(let [[a & more] '(1 2 3)] (+ a (count more)))";
+        assert_eq!(eval(source).unwrap().to_string(), "3");
+        match eval("(first 1)") {
+            Err(TypeMismatch { expected, .. }) => assert_eq!(expected, "a collection or string"),
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sequences_work_across_collections() {
+        let cases = [
+            ("(first \"abc\")", "a"),
+            ("(rest [1 2 3])", "( 2 3 )"),
+            ("(next [1])", "nil"),
+            ("(seq [])", "nil"),
+            ("(seq \"ab\")", "( a b )"),
+            ("(first {:a 1})", "( :a 1 )"),
+            ("(map inc [1 2 3])", "( 2 3 4 )"),
+            ("(map :a [{:a 1} {:b 2}])", "( 1 nil )"),
+            ("(filter (fn [x] (> x 1)) '(1 2 3))", "( 2 3 )"),
+            ("(reduce + [1 2 3 4])", "10"),
+            ("(reduce + 10 [])", "10"),
+            ("(reduce + [])", "0"),
+            ("(take 2 (range 10))", "( 0 1 )"),
+            ("(drop 8 (range 10))", "( 8 9 )"),
+            ("(range 1 2 0.25)", "( 1 1.25 1.5 1.75 )"),
+            ("(range 3 0 (- 1))", "( 3 2 1 )"),
+            ("(into [] '(1 2))", "( 1 2 )"),
+            ("(into '() [1 2])", "( 2 1 )"),
+            ("(get (into {} [[:a 1]]) :a)", "1"),
+            ("(concat [1] '(2) nil \"c\")", "( 1 2 c )"),
+            ("(reverse [1 2 3])", "( 3 2 1 )"),
+            ("(nth \"abc\" 1)", "b"),
+            ("(count (range 1000))", "1000"),
+        ];
+        let prelude = "(def inc (fn [x] (+ x 1)))";
+        for (source, expected) in cases.iter() {
+            let value = eval(&format!("{} {}", prelude, source));
+            assert_eq!(value.unwrap().to_string(), *expected, "{}", source);
+        }
+        match eval("(map 1 [1])") {
+            Err(NotCallable(..)) => {}
+            other => panic!("expected a call error, got {:?}", other),
+        }
+        match eval("(range 1 2 3 4)") {
+            Err(error @ InvalidArgumentArity { .. }) => assert_eq!(
+                error.to_string(),
+                "`range` expects 0 to 3 arguments but was given 4"
+            ),
+            other => panic!("expected an arity error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn syntax_quotes_unquote_and_splice() {
+        let prelude = "(def b 2) (def c [3 4])";
+        let cases = [
+            ("`(a ~b ~@c)", "( a 2 3 4 )"),
+            ("`[x ~(+ b 1) ~@'(y z) ~@nil]", "( x 3 y z )"),
+            ("`{:k ~b ~'v (c ~@c)}", "{ :k 2 v ( c 3 4 ) }"),
+            ("`(a '~b)", "( a ( quote 2 ) )"),
+            ("`(~@(map (fn [x] (* x b)) c))", "( 6 8 )"),
+            ("(first `(if ~b))", "if"),
+            (
+                "(let [[_ [x y] z] `(let [a# a#] ~'a#)] [(= x y) (= x z)])",
+                "( true false )",
+            ),
+            ("(= `x# `x#)", "false"),
+            ("(def f (fn [] `y#)) (= (f) (f))", "true"),
+        ];
+        for (source, expected) in cases.iter() {
+            let value = eval(&format!("{} {}", prelude, source));
+            assert_eq!(value.unwrap().to_string(), *expected, "{}", source);
+        }
+        let symbol = eval("`name#").unwrap().to_string();
+        assert!(symbol.starts_with("name__") && symbol.ends_with("__auto__"));
+        match eval("(def b 1) ~b") {
+            Err(InvalidForm { form, .. }) => assert_eq!(form, "unquote"),
+            other => panic!("expected an invalid unquote, got {:?}", other),
+        }
+        match eval("(def b [1]) `{:a ~@b}") {
+            Err(InvalidForm { form, .. }) => assert_eq!(form, "unquote-splicing"),
+            other => panic!("expected an invalid splice, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lazy_sequences_realize_on_demand() {
+        let cases = [
+            ("(take 5 (map inc (range)))", "( 1 2 3 4 5 )"),
+            ("(first (filter (fn [x] (> x 100)) (range)))", "101"),
+            ("(take 3 (drop 5 (range)))", "( 5 6 7 )"),
+            ("(take 4 (concat [:a] (range)))", "( :a 0 1 2 )"),
+            ("(take 2 (cons :a (range)))", "( :a 0 )"),
+            ("(nth (map inc (range)) 10)", "11"),
+            ("(= (range 3) [0 1 2] '(0 1 2))", "true"),
+            ("(let [[a & more] (range 4)] [a (count more)])", "( 0 3 )"),
+            ("(get {(range 2) :found} [0 1])", ":found"),
+        ];
+        let prelude = "(def inc (fn [x] (+ x 1)))";
+        for (source, expected) in cases.iter() {
+            let value = eval(&format!("{} {}", prelude, source));
+            assert_eq!(value.unwrap().to_string(), *expected, "{}", source);
+        }
+        // `=` stops at the first difference, even in an infinite sequence.
+        let value = eval("[(= (range) [0 1]) (= [(range 2)] [[0 1]])]").unwrap();
+        assert_eq!(value.to_string(), "( false true )");
+        // Lazy keys find the entries of equal lists and vectors.
+        let source = "(def inc (fn [x] (+ x 1))) (def m {[1 2] :v (map inc [1 2]) :s})
+                      [(get m (map inc [0 1])) (get m '(2 3))]";
+        assert_eq!(eval(source).unwrap().to_string(), "( :v :s )");
+        // `take` realizes exactly the items it hands out.
+        let counted = "(def calls 0) (def noisy (fn [x] (def calls (+ calls 1)) x))";
+        let takes = [
+            ("(count (take 2 (map noisy (range))))", "2"),
+            ("(count (take 0 (map noisy (range))))", "0"),
+            (
+                "(first (take 1 (map noisy (map (fn [x] (if (= x 1) (missing) x)) [0 1]))))",
+                "1",
+            ),
+        ];
+        for (source, calls) in takes.iter() {
+            let value = eval(&format!("{} {} calls", counted, source));
+            assert_eq!(value.unwrap().to_string(), *calls, "{}", source);
+        }
+        // Nothing is realized until it is asked for, and then the error is an
+        // ordinary one.
+        let source = "(def xs (map (fn [x] (missing)) [1])) (take 1 xs)";
+        assert!(eval(source).is_ok());
+        let consumers = [
+            "(first xs)",
+            "(count xs)",
+            "(println xs)",
+            "(= xs [1])",
+            "(= xs xs)",
+            "(assoc {} xs 1)",
+            "(get {} xs)",
+            "(contains? {} xs)",
+            "{xs 1}",
+        ];
+        for consumer in consumers.iter() {
+            match eval(&format!("{} {}", source, consumer)) {
+                Err(NotDefined(symbol)) => assert_eq!(symbol.to_string(), "missing"),
+                other => panic!("expected an undefined symbol, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn recursion_resolves_through_the_namespace() {
+        let source = "(def fact (fn [n] (if (= n 1) n (* n (fact (- n 1)))))) (fact 10)";
+        assert_eq!(eval(source).unwrap().to_string(), "3628800");
     }
 
-    pub fn set(&self, symbol: Symbol, value: Value) {
-        self.context.set(symbol, value);
+    #[test]
+    fn deep_recursion_fails_instead_of_overflowing() {
+        let deep = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let f = "(def f (fn [n] (if (= n 0) 0 (+ 1 (f (- n 1))))))";
+                let shallow = eval(&format!("{} (f 500)", f)).map(|value| value.to_string());
+                let deep = eval(&format!("{} (f {})", f, MAX_DEPTH)).map(|value| value.to_string());
+                (shallow, deep)
+            })
+            .unwrap();
+        let (shallow, deep) = deep.join().unwrap();
+        assert_eq!(shallow.unwrap(), "500");
+        assert!(matches!(deep, Err(StackOverflow(MAX_DEPTH))), "{:?}", deep);
+    }
+
+    #[test]
+    fn deeply_nested_forms_fail_to_parse() {
+        use crate::ast::parser::MAX_NESTING;
+        let nested = |depth| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        let deep = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let shallow = eval(&nested(MAX_NESTING - 1)).map(|value| value.to_string());
+                (shallow, eval(&nested(5000)).map(|value| value.to_string()))
+            })
+            .unwrap();
+        let (shallow, deep) = deep.join().unwrap();
+        let depth = MAX_NESTING - 1;
+        let expected = format!("{}1{}", "( ".repeat(depth), " )".repeat(depth));
+        assert!(shallow.unwrap() == expected);
+        assert!(matches!(deep, Err(InvalidForm { .. })), "{:?}", deep);
+    }
+
+    #[test]
+    fn deeply_nested_values_print_and_compare() {
+        let nest = "(def nest (fn [n x] (loop [i 0 v x] (if (< i n) (recur (+ i 1) [v]) v))))";
+        let deep = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let run = |source: &str| eval(&format!("{} {}", nest, source));
+                let printed = run("(nest 100000 1)").map(|value| value.to_string());
+                let same = run("(= (nest 100000 1) (nest 100000 1))");
+                let different = run("(= (nest 100000 1) (nest 100000 2))");
+                (
+                    printed,
+                    same.map(|v| v.to_string()),
+                    different.map(|v| v.to_string()),
+                )
+            })
+            .unwrap();
+        let (printed, same, different) = deep.join().unwrap();
+        let expected = format!("{}1{}", "( ".repeat(100000), " )".repeat(100000));
+        assert!(printed.unwrap() == expected);
+        assert_eq!(same.unwrap(), "true");
+        assert_eq!(different.unwrap(), "false");
+    }
+
+    #[test]
+    fn errors_carry_the_innermost_location() {
+        let error =
+            eval_located("(def a 1)\n(def f (fn [x]\n  (+ x missing)))\n(f a)").unwrap_err();
+        let location = error.location().expect("a located error");
+        assert_eq!((location.start.line, location.start.column), (3, 8));
+        assert_eq!(location.end.column, 15);
+        match error.kind() {
+            NotDefined(symbol) => assert_eq!(symbol.to_string(), "missing"),
+            other => panic!("expected an undefined symbol, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_errors_carry_a_location() {
+        let tokens =
+            Scanner::scan("(def a 1)\n  (loop [i 0] (+ 1 (recur i)))".to_string()).unwrap();
+        let location = parse(tokens)
+            .unwrap_err()
+            .location()
+            .expect("a located error");
+        assert_eq!((location.start.line, location.start.column), (2, 20));
+    }
+
+    #[test]
+    fn reports_underline_the_location() {
+        let source = "(def a 1)\n  (foo a)";
+        let error = eval_located(source).unwrap_err();
+        assert_eq!(
+            report(&error, "test.nd", source),
+            "test.nd:2:4: error: `foo` is not defined\n2 |   (foo a)\n  |    ^^^"
+        );
+    }
+
+    #[test]
+    fn errors_carry_the_offending_value() {
+        match eval("(+ 1 \"b\")") {
+            Err(TypeMismatch { expected, actual }) => {
+                assert_eq!((expected, actual.type_name()), ("number", "string"))
+            }
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+        match eval("(1 2)") {
+            Err(error @ NotCallable(..)) => {
+                assert_eq!(error.to_string(), "number `1` is not callable")
+            }
+            other => panic!("expected a non callable value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_an_error_not_a_panic() {
+        let inputs = [
+            "@",
+            "\"open",
+            "()",
+            ")",
+            "(",
+            "(def)",
+            "(if)",
+            "(let 1)",
+            "(fn 1)",
+            "[1 2",
+            "(pop [])",
+            "(assoc [1] 2 1)",
+            "'",
+            "'(1 2",
+            "(first 1)",
+            "(nth '(1) 1)",
+            "(take (- 1) [1])",
+            "(range 0 1 0)",
+            "(reduce (fn [a b] (missing)) [1 2])",
+            "`",
+            "`(a ~)",
+            "`(a ~@1)",
+            "(defmacro m [] 1)",
+            "(get 1 2)",
+            "(count 1)",
+            "nomad.other/a",
+            "{:a}",
+            "{:a 1",
+            "{:a 1]",
+        ];
+        for input in inputs.iter() {
+            assert!(eval_located(input).is_err(), "{:?} should fail", input);
+        }
     }
 }
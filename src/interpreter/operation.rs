@@ -1,5 +1,6 @@
 use super::value::*;
 use crate::result::runtime::ErrorKind;
+use std::sync::Arc;
 
 pub trait Introspection {
     fn truthy(&self) -> bool;
@@ -47,8 +48,28 @@ pub trait Length {
     fn length(&self) -> usize;
 }
 
+/// The items of a sequence, each a `Result` so that sequences computing
+/// their items can fail part way through.
+pub type Items<'a, T, E> = Box<dyn Iterator<Item = Result<T, E>> + 'a>;
+
+/// A value walked one item after another. Every collection is a sequence,
+/// maps of their `[key value]` entries and strings of their characters.
+pub trait Seq: Sized {
+    type Item;
+    type Err;
+
+    fn items(&self) -> Result<Items<'_, Self::Item, Self::Err>, Self::Err>;
+
+    /// Everything after the first item, as a sequence.
+    fn rest(&self) -> Result<Self, Self::Err>;
+
+    /// The first item, or `None` when the sequence is empty.
+    fn first(&self) -> Result<Option<Self::Item>, Self::Err> {
+        self.items()?.next().transpose()
+    }
+}
+
 type Str = std::string::String;
-type STR = &'static str;
 
 impl Introspection for Value {
     fn truthy(&self) -> bool {
@@ -60,9 +81,113 @@ impl Introspection for Value {
     }
 }
 
+/// The pairs of items two collections are equal through, or `None` for
+/// collections that differ in length or keys.
+type Pairs = Box<dyn Iterator<Item = crate::result::Result<Option<(Value, Value)>>>>;
+
+/// Pairs up the items of two sequences as they are realized.
+fn sequential(left: Value, right: Value) -> crate::result::Result<Pairs> {
+    let (mut left, mut right) = (left.into_items()?, right.into_items()?);
+    Ok(Box::new(std::iter::from_fn(move || {
+        match (left.next(), right.next()) {
+            (None, None) => None,
+            (Some(Err(error)), _) | (_, Some(Err(error))) => Some(Err(error)),
+            (Some(Ok(left)), Some(Ok(right))) => Some(Ok(Some((left, right)))),
+            _ => Some(Ok(None)),
+        }
+    })))
+}
+
+/// Pairs up the values two maps hold under each key.
+fn entries(left: &Map<Value, Value>, right: &Map<Value, Value>) -> Pairs {
+    let pairs: Vec<_> = left
+        .iter()
+        .map(|(key, value)| Ok(right.get(key).map(|other| (value.clone(), other.clone()))))
+        .collect();
+    Box::new(pairs.into_iter())
+}
+
+/// `=`. Lazy sequences are realized only as far as it takes to tell the
+/// operands apart, and an error realizing one is returned rather than read as
+/// a mismatch. Nested collections are compared from a stack of the ones
+/// still open rather than by recursion.
+pub fn equal(left: &Value, right: &Value) -> crate::result::Result<bool> {
+    let mut open: Vec<Pairs> = vec![Box::new(std::iter::once(Ok(Some((
+        left.clone(),
+        right.clone(),
+    )))))];
+    while let Some(pairs) = open.last_mut() {
+        let (left, right) = match pairs.next().transpose()? {
+            Some(Some(pair)) => pair,
+            Some(None) => return Ok(false),
+            None => {
+                open.pop();
+                continue;
+            }
+        };
+        match (left, right) {
+            (Value::Map(left), Value::Map(right)) => {
+                if left.len() != right.len() {
+                    return Ok(false);
+                }
+                open.push(entries(&left, &right));
+            }
+            (
+                left @ (Value::Vector(..) | Value::List(..) | Value::Lazy(..)),
+                right @ (Value::Vector(..) | Value::List(..) | Value::Lazy(..)),
+            ) => open.push(sequential(left, right)?),
+            (left, right) if !scalar_eq(&left, &right) => return Ok(false),
+            _ => {}
+        }
+    }
+    Ok(true)
+}
+
+/// Compares values other than collections, which are never equal to
+/// collections.
+fn scalar_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::Boolean(left), Value::Boolean(right)) => left.value == right.value,
+        (Value::Number(left), Value::Number(right)) => left.eq(right),
+        (Value::String(left), Value::String(right)) => left.value == right.value,
+        (Value::Symbol(left), Value::Symbol(right)) => left == right,
+        (Value::Keyword(left), Value::Keyword(right)) => left == right,
+        (Value::Var(left), Value::Var(right)) => {
+            left.name == right.name && left.namespace == right.namespace
+        }
+        (Value::Function(left), Value::Function(right)) => Arc::ptr_eq(left, right),
+        _ => false,
+    }
+}
+
+impl Compare for Value {
+    /// Like `=`, except that a sequence that fails to realize is not equal
+    /// to anything.
+    fn eq(&self, other: &Self) -> bool {
+        equal(self, other).unwrap_or(false)
+    }
+
+    fn lt(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => left.lt(right),
+            (Value::String(left), Value::String(right)) => left.value < right.value,
+            _ => false,
+        }
+    }
+
+    fn gt(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => left.gt(right),
+            (Value::String(left), Value::String(right)) => left.value > right.value,
+            _ => false,
+        }
+    }
+}
+
 impl Introspection for Number {
     fn truthy(&self) -> bool {
-        return true;
+        true
     }
 }
 
@@ -123,13 +248,13 @@ impl Concat for String {
 
 impl Introspection for String {
     fn truthy(&self) -> bool {
-        return true;
+        true
     }
 }
 
 impl Length for String {
     fn length(&self) -> usize {
-        self.value.len()
+        self.value.chars().count()
     }
 }
 
@@ -139,7 +264,10 @@ impl Lookup for Vector<Value> {
     type Err = ErrorKind;
 
     fn lookup(&self, key: Self::Key) -> Result<&Self::Item, Self::Err> {
-        let key = key.value as usize;
-        self.get(key).ok_or(ErrorKind::BindingNotFound)
+        if key.value < 0.0 || key.value.fract() != 0.0 {
+            return Err(ErrorKind::KeyNotFound(Value::Number(key)));
+        }
+        self.get(key.value as usize)
+            .ok_or(ErrorKind::KeyNotFound(Value::Number(key)))
     }
 }
@@ -0,0 +1,205 @@
+use super::execution::Execute;
+use super::operation::Seq;
+use super::value::{step, Step, Symbol, Value};
+use super::Interpreter;
+use crate::ast::node::Node;
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+use std::iter::FromIterator;
+
+const REST: &str = "&";
+
+/// The left hand side of a binding. Symbols bind the whole value, vector
+/// patterns bind a vector element by element and map patterns bind the
+/// values of their keys.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Symbol(Symbol),
+    Vector(Patterns),
+    /// Each pattern with the expression for its key, `{a :a}`. Keys are
+    /// evaluated when the pattern is bound.
+    Map(Vec<(Pattern, Node)>),
+}
+
+/// A sequence of positional patterns with an optional `& rest` pattern that
+/// collects the remaining values into a vector. Used both for nested vector
+/// patterns and for the parameters of a `fn`.
+#[derive(Debug, Clone)]
+pub struct Patterns {
+    pub items: Vec<Pattern>,
+    pub rest: Option<Box<Pattern>>,
+}
+
+fn is_rest(node: &Node) -> bool {
+    node.as_symbol()
+        .map(|symbol| !symbol.is_qualified() && symbol.name() == REST)
+        .unwrap_or(false)
+}
+
+impl Patterns {
+    pub fn from_nodes(nodes: &[Node]) -> Result<Patterns> {
+        let mut items = Vec::with_capacity(nodes.len());
+        let mut nodes = nodes.iter();
+        while let Some(node) = nodes.next() {
+            if !is_rest(node) {
+                items.push(Pattern::from_node(node)?);
+                continue;
+            }
+            let rest = nodes.next().ok_or(ErrorKind::InvalidBinding(
+                "`&` must be followed by a pattern",
+            ))?;
+            if nodes.next().is_some() {
+                return Err(ErrorKind::InvalidBinding("only one pattern may follow `&`").into());
+            }
+            return Ok(Patterns {
+                items,
+                rest: Some(Box::new(Pattern::from_node(rest)?)),
+            });
+        }
+        Ok(Patterns { items, rest: None })
+    }
+
+    pub fn is_variadic(&self) -> bool {
+        self.rest.is_some()
+    }
+
+    /// Binds the values passed to `recur`. A rest pattern receives the last
+    /// value as is rather than collecting the remaining values.
+    pub fn rebind(&self, mut values: Vec<Value>, interpreter: &Interpreter) -> Result<()> {
+        match &self.rest {
+            Some(rest) => {
+                let last = values
+                    .pop()
+                    .ok_or(ErrorKind::BindingMismatch("too few values for pattern"))?;
+                if values.len() != self.items.len() {
+                    return Err(
+                        ErrorKind::BindingMismatch("wrong number of values for pattern").into(),
+                    );
+                }
+                for (pattern, value) in self.items.iter().zip(values) {
+                    pattern.bind(value, interpreter)?;
+                }
+                rest.bind(last, interpreter)
+            }
+            None => self.bind(values, interpreter),
+        }
+    }
+
+    /// Binds `values` positionally into the current scope.
+    pub fn bind(&self, values: Vec<Value>, interpreter: &Interpreter) -> Result<()> {
+        let mut values = values.into_iter();
+        for pattern in &self.items {
+            let value = values
+                .next()
+                .ok_or(ErrorKind::BindingMismatch("too few values for pattern"))?;
+            pattern.bind(value, interpreter)?;
+        }
+        match &self.rest {
+            Some(rest) => rest.bind(Value::from_iter(values), interpreter),
+            None if values.next().is_some() => {
+                Err(ErrorKind::BindingMismatch("too many values for pattern").into())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Pattern {
+    pub fn from_node(node: &Node) -> Result<Pattern> {
+        match node {
+            Node::Symbol(..) if is_rest(node) => {
+                Err(ErrorKind::InvalidBinding("`&` is only valid inside a binding vector").into())
+            }
+            Node::Symbol(symbol) if symbol.is_qualified() => {
+                Err(ErrorKind::InvalidBinding("qualified symbols can not be bound locally").into())
+            }
+            Node::Symbol(symbol) => Ok(Pattern::Symbol(Symbol::from_node(symbol.clone()))),
+            Node::Vector(vector) => Ok(Pattern::Vector(Patterns::from_nodes(vector.items())?)),
+            Node::Map(map) => {
+                let mut entries = Vec::with_capacity(map.items().len() / 2);
+                for (pattern, key) in map.entries() {
+                    entries.push((Pattern::from_node(pattern)?, key.clone()));
+                }
+                Ok(Pattern::Map(entries))
+            }
+            _ => Err(ErrorKind::InvalidBinding(
+                "binding patterns must be symbols, vectors or maps",
+            )
+            .into()),
+        }
+    }
+
+    pub fn bind(&self, value: Value, interpreter: &Interpreter) -> Result<()> {
+        match (self, value) {
+            (Pattern::Symbol(symbol), value) => interpreter.set(symbol.clone(), value),
+            (Pattern::Vector(patterns), Value::Vector(vector)) => {
+                patterns.bind(vector.iter().cloned().collect(), interpreter)
+            }
+            (Pattern::Vector(patterns), Value::List(list)) => {
+                patterns.bind(list.iter().cloned().collect(), interpreter)
+            }
+            (Pattern::Vector(patterns), seq @ Value::Lazy(..)) => {
+                let (values, rest) =
+                    destructure_lazy(seq, patterns.items.len(), patterns.is_variadic())?;
+                for (pattern, value) in patterns.items.iter().zip(values) {
+                    pattern.bind(value, interpreter)?;
+                }
+                match &patterns.rest {
+                    Some(pattern) => pattern.bind(rest, interpreter),
+                    None => Ok(()),
+                }
+            }
+            (Pattern::Vector(..), _) => Err(ErrorKind::BindingMismatch(
+                "vector patterns can only destructure sequences",
+            )
+            .into()),
+            (Pattern::Map(entries), value @ (Value::Map(..) | Value::Nil)) => {
+                for (pattern, key) in entries {
+                    let key = key.execute(interpreter)?;
+                    pattern.bind(entry(&value, key)?, interpreter)?;
+                }
+                Ok(())
+            }
+            (Pattern::Map(..), _) => {
+                Err(ErrorKind::BindingMismatch("map patterns can only destructure maps").into())
+            }
+        }
+    }
+}
+
+/// The value of `key` in the map a map pattern destructures, nil when it
+/// has none.
+pub(crate) fn entry(map: &Value, key: Value) -> Result<Value> {
+    match map {
+        Value::Map(map) => Ok(map.get(&key.into_key()?).cloned().unwrap_or(Value::Nil)),
+        Value::Nil => Ok(Value::Nil),
+        _ => Err(ErrorKind::BindingMismatch("map patterns can only destructure maps").into()),
+    }
+}
+
+/// Realizes only the items of a lazy sequence that `count` patterns need,
+/// returning them with the unrealized remainder for a rest pattern.
+pub(crate) fn destructure_lazy(
+    seq: Value,
+    count: usize,
+    variadic: bool,
+) -> Result<(Vec<Value>, Value)> {
+    let mut values = Vec::with_capacity(count);
+    let mut rest = seq;
+    while values.len() < count {
+        match step(&rest)? {
+            Step::Cons(first, next) => {
+                values.push(first);
+                rest = next;
+            }
+            Step::Empty => {
+                return Err(ErrorKind::BindingMismatch("too few values for pattern").into())
+            }
+        }
+    }
+    if !variadic && rest.first()?.is_some() {
+        return Err(ErrorKind::BindingMismatch("too many values for pattern").into());
+    }
+    Ok((values, rest))
+}
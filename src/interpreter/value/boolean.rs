@@ -13,16 +13,6 @@ impl From<bool> for Boolean {
     }
 }
 
-impl Boolean {
-    fn as_int(&self) -> i32 {
-        if self.value {
-            1
-        } else {
-            0
-        }
-    }
-}
-
 impl Introspection for Boolean {
     fn truthy(&self) -> bool {
         self.value
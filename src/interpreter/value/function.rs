@@ -1,45 +1,203 @@
+use super::hash_map::Map;
+use super::keyword::Keyword;
+use super::lazy_seq::{step, LazySeq, Step};
+use super::list::List;
+use super::number::Number;
 use super::symbol::Symbol;
-use crate::ast::Tag;
-use crate::defnode;
+use crate::bytecode::Closure;
+use crate::interpreter::context::Scope;
+use crate::interpreter::execution::{execute_body, Tail};
+use crate::interpreter::pattern::Patterns;
+use crate::interpreter::operation::{equal, Compare, Conj as _, Introspection, Lookup, Math, Seq};
 use crate::interpreter::Interpreter;
 use crate::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Arity {
     Fixed(usize),
+    /// Like `Fixed`, but calling with fewer arguments returns a partial
+    /// application instead of failing.
+    Curried(usize),
     Or(usize, usize),
+    /// From the first to the second number of arguments, inclusive.
+    Between(usize, usize),
     Any,
     MinOne,
+    AtLeast(usize),
     None,
 }
 
-pub trait Function: fmt::Debug {
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Fixed(n) | Arity::Curried(n) => count == n,
+            Arity::Or(a, b) => count == a || count == b,
+            Arity::Between(a, b) => (a..=b).contains(&count),
+            Arity::Any => true,
+            Arity::MinOne => count >= 1,
+            Arity::AtLeast(n) => count >= n,
+            Arity::None => count == 0,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Fixed(n) | Arity::Curried(n) => write!(f, "{}", n),
+            Arity::Or(a, b) => write!(f, "{} or {}", a, b),
+            Arity::Between(a, b) => write!(f, "{} to {}", a, b),
+            Arity::Any => write!(f, "any number of"),
+            Arity::MinOne => write!(f, "at least 1"),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::None => write!(f, "0"),
+        }
+    }
+}
+
+pub trait Function: fmt::Debug + Send + Sync {
     fn arity(&self) -> Arity;
     fn name(&self) -> &str;
     fn call(&self, parameters: Vec<Value>, interpreter: &Interpreter) -> Result<Value>;
+
+    /// Whether calling with too few arguments returns a partial application.
+    fn curried(&self) -> bool {
+        matches!(self.arity(), Arity::Curried(_))
+    }
+
+    /// Arguments already supplied through partial application.
+    fn bound(&self) -> &[Value] {
+        &[]
+    }
+
+    /// The compiled closure behind this function, which the virtual machine
+    /// calls in place instead of going through `call`.
+    fn closure(&self) -> Option<&Closure> {
+        None
+    }
+}
+
+/// The single entry point for calling a function value. The argument count is
+/// checked against the declared `Arity` before the function body runs, and
+/// curried functions given too few arguments are partially applied instead.
+pub fn apply(
+    function: &Arc<dyn Function>,
+    parameters: Vec<Value>,
+    interpreter: &Interpreter,
+) -> Result<Value> {
+    let expected = function.arity();
+    if let (true, Arity::Fixed(n) | Arity::Curried(n)) = (function.curried(), expected) {
+        if !parameters.is_empty() && parameters.len() < n {
+            return Ok(Partial {
+                function: function.clone(),
+                arguments: parameters,
+            }
+            .into());
+        }
+    }
+    if !expected.accepts(parameters.len()) {
+        return Err(InvalidArgumentArity {
+            function: function.name().to_string(),
+            expected,
+            actual: parameters.len(),
+        }
+        .into());
+    }
+    interpreter.with_call(|| function.call(parameters, interpreter))
+}
+
+/// A curried function that has been given some of its arguments.
+#[derive(Debug, Clone)]
+pub struct Partial {
+    pub function: Arc<dyn Function>,
+    pub arguments: Vec<Value>,
+}
+
+impl Function for Partial {
+    fn arity(&self) -> Arity {
+        match self.function.arity() {
+            Arity::Fixed(n) | Arity::Curried(n) => Arity::Curried(n - self.arguments.len()),
+            arity => arity,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.function.name()
+    }
+
+    fn call(&self, parameters: Vec<Value>, interpreter: &Interpreter) -> Result<Value> {
+        let mut arguments = self.arguments.clone();
+        arguments.extend(parameters);
+        apply(&self.function, arguments, interpreter)
+    }
+
+    fn curried(&self) -> bool {
+        true
+    }
+
+    fn bound(&self) -> &[Value] {
+        &self.arguments
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct UserFunction {
     pub name: Option<Symbol>,
-    pub parameters: Vec<Symbol>,
-    pub body: Vec<Node>,
+    pub recursive: bool,
+    pub parameters: Patterns,
+    pub body: Arc<Vec<Node>>,
+    pub scope: Scope,
 }
 
 impl Function for UserFunction {
     fn arity(&self) -> Arity {
-        Arity::Fixed(self.parameters.len())
+        let count = self.parameters.items.len();
+        if self.parameters.is_variadic() {
+            Arity::AtLeast(count)
+        } else {
+            Arity::Fixed(count)
+        }
     }
 
     fn name(&self) -> &str {
-        // if let Some(name) = &self.name {
-        //     &name[..]
-        // } else {
-        "anonymous"
-        // }
+        if let Some(name) = &self.name {
+            name.name()
+        } else {
+            "anonymous"
+        }
+    }
+
+    fn curried(&self) -> bool {
+        true
     }
 
     fn call(&self, parameters: Vec<Value>, interpreter: &Interpreter) -> Result<Value> {
-        todo!()
+        let mut arguments = parameters;
+        let mut recurring = false;
+        // `recur` unwinds to here and the body runs again in a fresh frame, so
+        // self recursion through `recur` does not grow the Rust stack.
+        loop {
+            let values = std::mem::take(&mut arguments);
+            let tail = interpreter.with_scope(self.scope.clone(), || {
+                if let (true, Some(name)) = (self.recursive, &self.name) {
+                    interpreter.set(name.clone(), self.clone().into())?;
+                }
+                if recurring {
+                    self.parameters.rebind(values, interpreter)?;
+                } else {
+                    self.parameters.bind(values, interpreter)?;
+                }
+                execute_body(&self.body, interpreter)
+            })?;
+            match tail {
+                Tail::Return(value) => return Ok(value),
+                Tail::Recur(values) => {
+                    arguments = values;
+                    recurring = true;
+                }
+            }
+        }
     }
 }
 
@@ -61,87 +219,596 @@ macro_rules! native_function {
     };
 }
 
+fn number(value: &Value) -> Result<&Number> {
+    match value {
+        Value::Number(number) => Ok(number),
+        value => Err(TypeMismatch {
+            expected: "number",
+            actual: value.clone(),
+        }
+        .into()),
+    }
+}
+
+/// Folds the arguments left to right, `(- 10 2 3)` is `(10 - 2) - 3`. A single
+/// argument is combined with `identity` first, so `(- 2)` is `-2`.
+fn fold(parameters: &[Value], identity: f64, f: fn(&Number, &Number) -> Number) -> Result<Value> {
+    match parameters {
+        [] => Ok(Value::make_number(identity)),
+        [only] => Ok(Value::Number(f(&identity.into(), number(only)?))),
+        [first, rest @ ..] => {
+            let mut result = number(first)?.clone();
+            for value in rest {
+                result = f(&result, number(value)?);
+            }
+            Ok(Value::Number(result))
+        }
+    }
+}
+
+/// True when `f` holds for every adjacent pair, `(< 1 2 3)` is `1 < 2 && 2 < 3`.
+fn chain(parameters: &[Value], f: fn(&Number, &Number) -> bool) -> Result<Value> {
+    for pair in parameters.windows(2) {
+        if !f(number(&pair[0])?, number(&pair[1])?) {
+            return Ok(Value::make_bool(false));
+        }
+    }
+    Ok(Value::make_bool(true))
+}
+
+fn join(parameters: &[Value]) -> std::string::String {
+    parameters
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+native_function! {
+    Plus(parameters, _interpreter) ("+", Arity::Any) : {
+        fold(&parameters, 0.0, Number::add)
+    }
+}
+native_function! {
+    Minus(parameters, _interpreter) ("-", Arity::Any) : {
+        fold(&parameters, 0.0, Number::sub)
+    }
+}
+native_function! {
+    Multiply(parameters, _interpreter) ("*", Arity::Any) : {
+        fold(&parameters, 1.0, Number::mul)
+    }
+}
+native_function! {
+    Divide(parameters, _interpreter) ("/", Arity::Any) : {
+        fold(&parameters, 1.0, Number::div)
+    }
+}
+native_function! {
+    Modulus(parameters, _interpreter) ("mod", Arity::Curried(2)) : {
+        fold(&parameters, 1.0, Number::modulus)
+    }
+}
+native_function! {
+    Equal(parameters, _interpreter) ("=", Arity::Any) : {
+        for pair in parameters.windows(2) {
+            if !equal(&pair[0], &pair[1])? {
+                return Ok(Value::make_bool(false));
+            }
+        }
+        Ok(Value::make_bool(true))
+    }
+}
+native_function! {
+    LessThan(parameters, _interpreter) ("<", Arity::Any) : {
+        chain(&parameters, Number::lt)
+    }
+}
+native_function! {
+    GreaterThan(parameters, _interpreter) (">", Arity::Any) : {
+        chain(&parameters, Number::gt)
+    }
+}
+native_function! {
+    Println(parameters, _interpreter) ("println", Arity::Any) : {
+        parameters.iter().try_for_each(Value::realize)?;
+        println!("{}", join(&parameters));
+        Ok(Value::Nil)
+    }
+}
+native_function! {
+    Print(parameters, _interpreter) ("print", Arity::Any) : {
+        parameters.iter().try_for_each(Value::realize)?;
+        print!("{}", join(&parameters));
+        Ok(Value::Nil)
+    }
+}
+native_function! {
+    Conj(parameters, _interpreter) ("conj", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let value = parameters.next().unwrap_or(Value::Nil);
+        collection.conj(value)
+    }
+}
+native_function! {
+    MakeList(parameters, _interpreter) ("list", Arity::Any) : {
+        Ok(Value::List(parameters.into_iter().collect()))
+    }
+}
+/// The items of a sequence as a list, which is the sequence itself for a
+/// list.
+fn list(value: Value) -> Result<List<Value>> {
+    match value {
+        Value::List(list) => Ok(list),
+        value => value.items()?.collect(),
+    }
+}
+
+/// `key` as an index into a sequence, when it is a whole, non-negative number.
+fn index(key: &Value) -> Option<usize> {
+    match key {
+        Value::Number(number) if number.value >= 0.0 && number.value.fract() == 0.0 => {
+            Some(number.value as usize)
+        }
+        _ => None,
+    }
+}
+
+fn whole(value: &Value) -> Result<usize> {
+    index(value).ok_or_else(|| {
+        TypeMismatch {
+            expected: "a whole, non-negative number",
+            actual: value.clone(),
+        }
+        .into()
+    })
+}
+
+/// A lazy sequence realized through `thunk`. Realizing a cell realizes the
+/// cells of the sequences it is built on first, recursing on the native
+/// stack, so each one counts as a call.
+fn lazy<F>(interpreter: Interpreter, thunk: F) -> Value
+where
+    F: Fn(&Interpreter) -> Result<Step> + Send + 'static,
+{
+    Value::Lazy(LazySeq::new(move || {
+        interpreter.with_call(|| thunk(&interpreter))
+    }))
+}
+
+/// `function` applied to each item of `collection`, as they are asked for.
+fn map_seq(function: Arc<dyn Function>, collection: Value, interpreter: Interpreter) -> Value {
+    lazy(interpreter, move |interpreter| match step(&collection)? {
+        Step::Cons(first, rest) => {
+            let value = apply(&function, vec![first], interpreter)?;
+            let rest = map_seq(function.clone(), rest, interpreter.clone());
+            Ok(Step::Cons(value, rest))
+        }
+        Step::Empty => Ok(Step::Empty),
+    })
+}
+
+/// The items of `collection` that satisfy `predicate`, as they are asked for.
+fn filter_seq(predicate: Arc<dyn Function>, collection: Value, interpreter: Interpreter) -> Value {
+    lazy(interpreter, move |interpreter| {
+        let mut rest = collection.clone();
+        while let Step::Cons(first, next) = step(&rest)? {
+            if apply(&predicate, vec![first.clone()], interpreter)?.truthy() {
+                let next = filter_seq(predicate.clone(), next, interpreter.clone());
+                return Ok(Step::Cons(first, next));
+            }
+            rest = next;
+        }
+        Ok(Step::Empty)
+    })
+}
+
+/// The first `count` items of `collection`. Nothing past them is realized.
+fn take_seq(count: usize, collection: Value, interpreter: Interpreter) -> Value {
+    lazy(interpreter, move |interpreter| {
+        if count == 0 {
+            return Ok(Step::Empty);
+        }
+        match step(&collection)? {
+            Step::Cons(first, rest) => {
+                let rest = take_seq(count - 1, rest, interpreter.clone());
+                Ok(Step::Cons(first, rest))
+            }
+            Step::Empty => Ok(Step::Empty),
+        }
+    })
+}
+
+/// The numbers from `start` by `step`, up to `end` when there is one. Each
+/// number is computed from the start so steps that are not exact in binary
+/// do not drift.
+fn range_seq(start: f64, end: Option<f64>, step: f64, index: usize) -> Value {
+    Value::Lazy(LazySeq::new(move || {
+        let number = start + index as f64 * step;
+        match end {
+            Some(end) if (step > 0.0 && number >= end) || (step < 0.0 && number <= end) => {
+                Ok(Step::Empty)
+            }
+            _ => {
+                let rest = range_seq(start, end, step, index + 1);
+                Ok(Step::Cons(Value::make_number(number), rest))
+            }
+        }
+    }))
+}
+
+/// The items of each collection in turn, as they are asked for.
+fn concat_seq(collections: List<Value>, interpreter: Interpreter) -> Value {
+    lazy(interpreter, move |interpreter| {
+        let mut collections = collections.clone();
+        while let Some(collection) = collections.head() {
+            match step(collection)? {
+                Step::Cons(first, rest) => {
+                    let rest = concat_seq(collections.tail().conj(rest), interpreter.clone());
+                    return Ok(Step::Cons(first, rest));
+                }
+                Step::Empty => collections = collections.tail(),
+            }
+        }
+        Ok(Step::Empty)
+    })
+}
+
+native_function! {
+    ToSeq(parameters, _interpreter) ("seq", Arity::Fixed(1)) : {
+        let collection = parameters.into_iter().next().unwrap_or(Value::Nil);
+        match (collection.first()?, collection) {
+            (None, _) => Ok(Value::Nil),
+            (Some(..), collection @ Value::Lazy(..)) => Ok(collection),
+            (Some(..), collection) => Ok(Value::List(list(collection)?)),
+        }
+    }
+}
+native_function! {
+    First(parameters, _interpreter) ("first", Arity::Fixed(1)) : {
+        let collection = parameters.into_iter().next().unwrap_or(Value::Nil);
+        Ok(collection.first()?.unwrap_or(Value::Nil))
+    }
+}
+native_function! {
+    Rest(parameters, _interpreter) ("rest", Arity::Fixed(1)) : {
+        parameters.into_iter().next().unwrap_or(Value::Nil).rest()
+    }
+}
+native_function! {
+    Next(parameters, _interpreter) ("next", Arity::Fixed(1)) : {
+        let rest = parameters.into_iter().next().unwrap_or(Value::Nil).rest()?;
+        match rest.first()? {
+            Some(..) => Ok(rest),
+            None => Ok(Value::Nil),
+        }
+    }
+}
+native_function! {
+    Cons(parameters, _interpreter) ("cons", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let value = parameters.next().unwrap_or(Value::Nil);
+        match parameters.next().unwrap_or(Value::Nil) {
+            collection @ Value::Lazy(..) => {
+                Ok(Value::Lazy(LazySeq::realized(Step::Cons(value, collection))))
+            }
+            collection => Ok(Value::List(list(collection)?.conj(value))),
+        }
+    }
+}
+native_function! {
+    Nth(parameters, _interpreter) ("nth", Arity::Or(2, 3)) : {
+        let has_default = parameters.len() == 3;
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let key = parameters.next().unwrap_or(Value::Nil);
+        let default = parameters.next().unwrap_or(Value::Nil);
+        let mut items = collection.items()?;
+        let value = match (&collection, index(&key)) {
+            (Value::Vector(vector), Some(index)) => vector.get(index).cloned(),
+            (_, Some(index)) => items.nth(index).transpose()?,
+            (_, None) => None,
+        };
+        match value {
+            Some(value) => Ok(value),
+            None if has_default => Ok(default),
+            None => Err(KeyNotFound(key).into()),
+        }
+    }
+}
+native_function! {
+    MapSeq(parameters, interpreter) ("map", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let function = parameters.next().unwrap_or(Value::Nil).callable()?;
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        Ok(map_seq(function, collection, interpreter.clone()))
+    }
+}
+native_function! {
+    Filter(parameters, interpreter) ("filter", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let predicate = parameters.next().unwrap_or(Value::Nil).callable()?;
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        Ok(filter_seq(predicate, collection, interpreter.clone()))
+    }
+}
+native_function! {
+    Reduce(parameters, interpreter) ("reduce", Arity::Or(2, 3)) : {
+        let mut parameters = parameters.into_iter();
+        let function = parameters.next().unwrap_or(Value::Nil).callable()?;
+        let (initial, collection) = match (parameters.next(), parameters.next()) {
+            (Some(initial), Some(collection)) => (Some(initial), collection),
+            (collection, _) => (None, collection.unwrap_or(Value::Nil)),
+        };
+        let mut items = collection.items()?;
+        let initial = match initial {
+            Some(initial) => initial,
+            None => match items.next() {
+                Some(first) => first?,
+                // Reducing nothing without an initial value is `(f)`.
+                None => return apply(&function, vec![], interpreter),
+            },
+        };
+        items.try_fold(initial, |result, item| {
+            apply(&function, vec![result, item?], interpreter)
+        })
+    }
+}
+native_function! {
+    Take(parameters, interpreter) ("take", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let count = whole(&parameters.next().unwrap_or(Value::Nil))?;
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        Ok(take_seq(count, collection, interpreter.clone()))
+    }
+}
+native_function! {
+    DropSeq(parameters, interpreter) ("drop", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let count = whole(&parameters.next().unwrap_or(Value::Nil))?;
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        Ok(lazy(interpreter.clone(), move |_| {
+            let mut rest = collection.clone();
+            for _ in 0..count {
+                match step(&rest)? {
+                    Step::Cons(_, next) => rest = next,
+                    Step::Empty => return Ok(Step::Empty),
+                }
+            }
+            step(&rest)
+        }))
+    }
+}
+native_function! {
+    Range(parameters, _interpreter) ("range", Arity::Between(0, 3)) : {
+        let numbers = parameters
+            .iter()
+            .map(|parameter| number(parameter).map(|number| number.value))
+            .collect::<Result<Vec<_>>>()?;
+        let (start, end, step) = match numbers[..] {
+            [start, end, step] => (start, Some(end), step),
+            [start, end] => (start, Some(end), 1.0),
+            [end] => (0.0, Some(end), 1.0),
+            [] => (0.0, None, 1.0),
+            _ => return Err(Internal("range takes up to three numbers").into()),
+        };
+        if step == 0.0 {
+            return Err(TypeMismatch {
+                expected: "a step other than 0",
+                actual: Value::make_number(step),
+            }
+            .into());
+        }
+        Ok(range_seq(start, end, step, 0))
+    }
+}
 native_function! {
-    Plus(parameters, int) ("+", Arity::Any) : {
-        Err(General("todo"))
+    IntoSeq(parameters, _interpreter) ("into", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let from = parameters.next().unwrap_or(Value::Nil);
+        let mut items = from.items()?;
+        items.try_fold(collection, |collection, item| collection.conj(item?))
     }
 }
 native_function! {
-    Minus(parameters, int) ("-", Arity::Any) : {
-        Err(General("todo"))
+    ConcatSeq(parameters, interpreter) ("concat", Arity::Any) : {
+        Ok(concat_seq(parameters.into_iter().collect(), interpreter.clone()))
+    }
+}
+native_function! {
+    Reverse(parameters, _interpreter) ("reverse", Arity::Fixed(1)) : {
+        let collection = parameters.into_iter().next().unwrap_or(Value::Nil);
+        let mut reversed = List::new();
+        for item in collection.items()? {
+            reversed = reversed.conj(item?);
+        }
+        Ok(Value::List(reversed))
+    }
+}
+/// The value under `key` in `collection`, or `default` when it is missing.
+fn get(collection: Value, key: Value, default: Value) -> Result<Value> {
+    match collection.lookup(key) {
+        Ok(value) => Ok(value.clone()),
+        Err(error) if matches!(error.kind(), KeyNotFound(..)) => Ok(default),
+        Err(error) => Err(error),
     }
 }
+
 native_function! {
-    Multiply(parameters, int) ("*", Arity::Any) : {
-        Err(General("todo"))
+    Get(parameters, _interpreter) ("get", Arity::Or(2, 3)) : {
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let key = parameters.next().unwrap_or(Value::Nil);
+        let default = parameters.next().unwrap_or(Value::Nil);
+        get(collection, key, default)
+    }
+}
+fn map(value: Value) -> Result<Map<Value, Value>> {
+    match value {
+        Value::Nil => Ok(Map::new()),
+        Value::Map(map) => Ok(map),
+        value => Err(TypeMismatch {
+            expected: "map",
+            actual: value,
+        }
+        .into()),
     }
 }
+
 native_function! {
-    Divide(parameters, int) ("/", Arity::Any) : {
-        Err(General("todo"))
+    Assoc(parameters, _interpreter) ("assoc", Arity::Curried(3)) : {
+        let mut parameters = parameters.into_iter();
+        match parameters.next().unwrap_or(Value::Nil) {
+            Value::Vector(vector) => {
+                let key = parameters.next().unwrap_or(Value::Nil);
+                let value = parameters.next().unwrap_or(Value::Nil);
+                index(&key)
+                    .and_then(|index| vector.assoc(index, value))
+                    .map(Value::Vector)
+                    .ok_or_else(|| KeyNotFound(key).into())
+            }
+            collection @ (Value::Nil | Value::Map(..)) => {
+                Value::Map(map(collection)?).conj(parameters.collect())
+            }
+            collection => Err(TypeMismatch {
+                expected: "a vector or map",
+                actual: collection,
+            }
+            .into()),
+        }
     }
 }
 native_function! {
-    Modulus(parameters, int) ("mod", Arity::Fixed(2)) : {
-        Err(General("todo"))
+    Pop(parameters, _interpreter) ("pop", Arity::Fixed(1)) : {
+        match parameters.into_iter().next().unwrap_or(Value::Nil) {
+            Value::Nil => Ok(Value::Nil),
+            Value::Vector(vector) => match vector.pop() {
+                Some(vector) => Ok(Value::Vector(vector)),
+                None => Err(TypeMismatch {
+                    expected: "a non-empty vector",
+                    actual: Value::Vector(vector),
+                }
+                .into()),
+            },
+            collection => Err(TypeMismatch {
+                expected: "a vector",
+                actual: collection,
+            }
+            .into()),
+        }
     }
 }
 native_function! {
-    Equal(parameters, int) ("=", Arity::Any) : {
-        Err(General("todo"))
+    Dissoc(parameters, _interpreter) ("dissoc", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let key = parameters.next().unwrap_or(Value::Nil);
+        match collection {
+            Value::Nil => Ok(Value::Nil),
+            collection => Ok(Value::Map(map(collection)?.remove(&key.into_key()?))),
+        }
     }
 }
 native_function! {
-    LessThan(parameters, int) ("<", Arity::Any) : {
-        Err(General("todo"))
+    Contains(parameters, _interpreter) ("contains?", Arity::Curried(2)) : {
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let key = parameters.next().unwrap_or(Value::Nil);
+        match collection.lookup(key) {
+            Ok(..) => Ok(Value::make_bool(true)),
+            Err(error) if matches!(error.kind(), KeyNotFound(..)) => Ok(Value::make_bool(false)),
+            Err(error) => Err(error),
+        }
     }
 }
 native_function! {
-    GreaterThan(parameters, int) (">", Arity::Any) : {
-        Err(General("todo"))
+    Keys(parameters, _interpreter) ("keys", Arity::Fixed(1)) : {
+        let map = map(parameters.into_iter().next().unwrap_or(Value::Nil))?;
+        Ok(map.keys().cloned().collect())
     }
 }
 native_function! {
-    Println(parameters, int) ("println", Arity::Any) : {
-        Err(General("todo"))
+    Vals(parameters, _interpreter) ("vals", Arity::Fixed(1)) : {
+        let map = map(parameters.into_iter().next().unwrap_or(Value::Nil))?;
+        Ok(map.values().cloned().collect())
     }
 }
 native_function! {
-    Print(parameters, int) ("print", Arity::Any) : {
-        Err(General("todo"))
+    Count(parameters, _interpreter) ("count", Arity::Fixed(1)) : {
+        let collection = parameters.first().unwrap_or(&Value::Nil);
+        Ok(Value::Number(collection.count()?.into()))
     }
 }
 native_function! {
-    Conj(parameters, int) ("conj", Arity::Fixed(2)) : {
-        Err(General("todo"))
+    Now(_parameters, _interpreter) ("now", Arity::None) : {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| Internal("the system clock is before the unix epoch"))?;
+        Ok(Value::Number(now.as_millis().into()))
     }
 }
 native_function! {
-    Get(parameters, int) ("get", Arity::Or(2, 3)) : {
-        Err(General("todo"))
+    Not(parameters, _interpreter) ("not", Arity::Fixed(1)) : {
+        let value = parameters.first().unwrap_or(&Value::Nil);
+        Ok(Value::make_bool(value.falsy()))
     }
 }
 native_function! {
-    Count(parameters, int) ("count", Arity::Fixed(1)) : {
-        Err(General("todo"))
+    And(parameters, _interpreter) ("and", Arity::Any) : {
+        let mut result = Value::make_bool(true);
+        for value in parameters {
+            if value.falsy() {
+                return Ok(value);
+            }
+            result = value;
+        }
+        Ok(result)
     }
 }
 native_function! {
-    Now(parameters, int) ("now", Arity::None) : {
-        Err(General("todo"))
+    Or(parameters, _interpreter) ("or", Arity::Any) : {
+        let mut result = Value::Nil;
+        for value in parameters {
+            if value.truthy() {
+                return Ok(value);
+            }
+            result = value;
+        }
+        Ok(result)
+    }
+}
+
+impl Function for Keyword {
+    fn arity(&self) -> Arity {
+        Arity::Or(1, 2)
+    }
+
+    fn name(&self) -> &str {
+        self.text()
+    }
+
+    fn call(&self, parameters: Vec<Value>, _interpreter: &Interpreter) -> Result<Value> {
+        let mut parameters = parameters.into_iter();
+        let collection = parameters.next().unwrap_or(Value::Nil);
+        let default = parameters.next().unwrap_or(Value::Nil);
+        get(collection, Value::Keyword(self.clone()), default)
     }
 }
 
 pub mod nf {
     pub use super::{
-        Conj, Count, Divide, Equal, Get, GreaterThan, LessThan, Minus, Modulus, Multiply, Now,
-        Plus, Print, Println,
+        And, Assoc, ConcatSeq, Conj, Cons, Contains, Count, Dissoc, Divide, DropSeq, Equal, Filter,
+        First, Get, GreaterThan, IntoSeq, Keys, LessThan, MakeList, MapSeq, Minus, Modulus,
+        Multiply, Next, Not, Now, Nth, Or, Plus, Pop, Print, Println, Range, Reduce, Rest, Reverse,
+        Take, ToSeq, Vals,
     };
 }
 
 impl<T> From<T> for Value
-    where
-        T: Function + 'static,
+where
+    T: Function + 'static,
 {
     fn from(f: T) -> Value {
         Value::Function(Arc::new(f))
@@ -1,180 +1,468 @@
-use super::Value;
-use std::cell::Cell;
-use std::cmp::max;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
 use std::fmt::Formatter;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::hash::{Hash, Hasher};
+use std::slice;
+use std::sync::Arc;
 
-const SIZE: usize = 4;
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
 
-lazy_static! {
-    static ref COUNT: Arc<Mutex<i32>> = Arc::new(Mutex::new(0));
+/// What the map needs of its keys besides a hash. Equality is separate from
+/// `Eq` so values can be keys under nomad's `=`.
+pub trait Key: Hash {
+    fn same(&self, other: &Self) -> bool;
 }
 
-fn make_id() -> i32 {
-    let mut count = COUNT.try_lock().unwrap();
-    *count += 1;
-    count.clone()
+fn hash<K: Hash>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
 }
 
-fn reset_id() {
-    let mut count = COUNT.try_lock().unwrap();
-    *count = 0;
+/// The slice of `hash` that picks a child at the level starting at `shift`.
+fn fragment(hash: u64, shift: u32) -> u32 {
+    ((hash >> shift) & MASK) as u32
 }
 
 #[derive(Debug, Clone)]
-struct Vector {
-    root: Link,
-}
-
-impl fmt::Display for Vector {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{{{}}}", self.root)
-    }
+enum Child<K, V> {
+    Entry { hash: u64, key: K, value: V },
+    Node(Arc<Node<K, V>>),
 }
 
 #[derive(Debug, Clone)]
-struct Node {
-    id: i32,
-    depth: usize,
-    value: Option<i32>,
-    links: Vec<Link>,
+enum Node<K, V> {
+    /// Children for the hash fragments set in `bitmap`, in fragment order.
+    Branch {
+        bitmap: u32,
+        children: Vec<Child<K, V>>,
+    },
+    /// Entries whose keys hash the same, kept when every bit is used up.
+    Collision { hash: u64, entries: Vec<(K, V)> },
 }
 
-impl fmt::Display for Node {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if let Some(value) = self.value {
-            write!(f, "({})", value)
+impl<K: Key + Clone, V: Clone> Node<K, V> {
+    fn empty() -> Node<K, V> {
+        Node::Branch {
+            bitmap: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn get(&self, hash: u64, key: &K, shift: u32) -> Option<&V> {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << fragment(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                match &children[(bitmap & (bit - 1)).count_ones() as usize] {
+                    Child::Entry {
+                        hash: found,
+                        key: candidate,
+                        value,
+                    } if *found == hash && candidate.same(key) => Some(value),
+                    Child::Entry { .. } => None,
+                    Child::Node(node) => node.get(hash, key, shift + BITS),
+                }
+            }
+            Node::Collision { entries, .. } => entries
+                .iter()
+                .find(|(candidate, _)| candidate.same(key))
+                .map(|(_, value)| value),
+        }
+    }
+
+    /// The node with `key` set to `value`, and whether the key is new.
+    fn insert(&self, hash: u64, key: K, value: V, shift: u32) -> (Node<K, V>, bool) {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << fragment(hash, shift);
+                let index = (bitmap & (bit - 1)).count_ones() as usize;
+                let mut children = children.clone();
+                if bitmap & bit == 0 {
+                    children.insert(index, Child::Entry { hash, key, value });
+                    let node = Node::Branch {
+                        bitmap: bitmap | bit,
+                        children,
+                    };
+                    return (node, true);
+                }
+                let added = match &children[index] {
+                    Child::Entry {
+                        hash: found,
+                        key: existing,
+                        ..
+                    } if *found == hash && existing.same(&key) => {
+                        children[index] = Child::Entry { hash, key, value };
+                        false
+                    }
+                    Child::Entry {
+                        hash: found,
+                        key: existing,
+                        value: other,
+                    } => {
+                        let node = Node::pair(
+                            shift + BITS,
+                            (*found, existing.clone(), other.clone()),
+                            (hash, key, value),
+                        );
+                        children[index] = Child::Node(Arc::new(node));
+                        true
+                    }
+                    Child::Node(node) => {
+                        let (node, added) = node.insert(hash, key, value, shift + BITS);
+                        children[index] = Child::Node(Arc::new(node));
+                        added
+                    }
+                };
+                let node = Node::Branch {
+                    bitmap: *bitmap,
+                    children,
+                };
+                (node, added)
+            }
+            Node::Collision {
+                hash: shared,
+                entries,
+            } if *shared == hash => {
+                let mut entries = entries.clone();
+                match entries.iter().position(|(existing, _)| existing.same(&key)) {
+                    Some(index) => {
+                        entries[index] = (key, value);
+                        (Node::Collision { hash, entries }, false)
+                    }
+                    None => {
+                        entries.push((key, value));
+                        (Node::Collision { hash, entries }, true)
+                    }
+                }
+            }
+            Node::Collision { hash: shared, .. } => {
+                // The new key differs in a later fragment, so the collision
+                // moves down under a branch that tells them apart.
+                let branch = Node::Branch {
+                    bitmap: 1 << fragment(*shared, shift),
+                    children: vec![Child::Node(Arc::new(self.clone()))],
+                };
+                branch.insert(hash, key, value, shift)
+            }
+        }
+    }
+
+    /// A node holding two entries whose hashes agree up to `shift`.
+    fn pair(shift: u32, first: (u64, K, V), second: (u64, K, V)) -> Node<K, V> {
+        if first.0 == second.0 {
+            return Node::Collision {
+                hash: first.0,
+                entries: vec![(first.1, first.2), (second.1, second.2)],
+            };
+        }
+        let (left, right) = (fragment(first.0, shift), fragment(second.0, shift));
+        if left == right {
+            return Node::Branch {
+                bitmap: 1 << left,
+                children: vec![Child::Node(Arc::new(Node::pair(
+                    shift + BITS,
+                    first,
+                    second,
+                )))],
+            };
+        }
+        let entry = |(hash, key, value)| Child::Entry { hash, key, value };
+        let children = if left < right {
+            vec![entry(first), entry(second)]
         } else {
-            write!(f, "[")?;
-            let last = self.links.len() - 1;
-            for (i, slot) in self.links.iter().enumerate() {
-                write!(f, "{}", slot)?;
-                if i != last {
-                    write!(f, " ")?;
+            vec![entry(second), entry(first)]
+        };
+        Node::Branch {
+            bitmap: (1 << left) | (1 << right),
+            children,
+        }
+    }
+
+    /// The node without `key`, or `None` when the key is not in it.
+    fn remove(&self, hash: u64, key: &K, shift: u32) -> Option<Node<K, V>> {
+        match self {
+            Node::Branch { bitmap, children } => {
+                let bit = 1 << fragment(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
                 }
+                let index = (bitmap & (bit - 1)).count_ones() as usize;
+                let child = match &children[index] {
+                    Child::Entry {
+                        hash: found,
+                        key: existing,
+                        ..
+                    } if *found == hash && existing.same(key) => None,
+                    Child::Entry { .. } => return None,
+                    Child::Node(node) => node.remove(hash, key, shift + BITS)?.lift(),
+                };
+                let mut children = children.clone();
+                let bitmap = match child {
+                    Some(child) => {
+                        children[index] = child;
+                        *bitmap
+                    }
+                    None => {
+                        children.remove(index);
+                        bitmap & !bit
+                    }
+                };
+                Some(Node::Branch { bitmap, children })
+            }
+            Node::Collision {
+                hash: shared,
+                entries,
+            } => {
+                let index = entries
+                    .iter()
+                    .position(|(existing, _)| existing.same(key))?;
+                let mut entries = entries.clone();
+                entries.remove(index);
+                Some(Node::Collision {
+                    hash: *shared,
+                    entries,
+                })
             }
-            write!(f, "]")
         }
     }
-}
 
-impl Default for Node {
-    fn default() -> Self {
-        Node {
-            id: make_id(),
-            depth: 0,
-            value: None,
-            links: Vec::with_capacity(SIZE),
+    /// The child that replaces this node in its parent: nothing when it is
+    /// empty and its only entry when it has one, so removals keep the trie
+    /// as shallow as insertions would have made it.
+    fn lift(self) -> Option<Child<K, V>> {
+        match self {
+            Node::Branch { children, .. } if children.is_empty() => None,
+            Node::Branch { mut children, .. }
+                if children.len() == 1 && matches!(children[0], Child::Entry { .. }) =>
+            {
+                children.pop()
+            }
+            Node::Collision { hash, mut entries } if entries.len() == 1 => {
+                let (key, value) = entries.pop().expect("one entry");
+                Some(Child::Entry { hash, key, value })
+            }
+            Node::Collision { entries, .. } if entries.is_empty() => None,
+            node => Some(Child::Node(Arc::new(node))),
         }
     }
 }
 
-impl Node {
-    fn push_node(&mut self, node: Rc<Node>) {
-        self.links.push(node);
+/// A persistent hash array mapped trie. Each level of branches consumes five
+/// bits of the key's hash, and updates copy only the path to the changed
+/// entry, sharing the rest with the map they were made from.
+#[derive(Debug, Clone)]
+pub struct Map<K, V> {
+    length: usize,
+    root: Arc<Node<K, V>>,
+}
+
+impl<K: Key + Clone, V: Clone> Map<K, V> {
+    pub fn new() -> Map<K, V> {
+        Map {
+            length: 0,
+            root: Arc::new(Node::empty()),
+        }
     }
 
-    fn is_leaf(&self) -> bool {
-        self.value.is_some()
+    pub fn len(&self) -> usize {
+        self.length
     }
 
-    fn push(&mut self, value: i32) {
-        self.links.push(Rc::new(Node {
-            id: make_id(),
-            depth: 0,
-            value: Some(value),
-            links: Vec::with_capacity(SIZE),
-        }));
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
     }
 
-    fn is_full(&self) -> bool {
-        if self.value.is_some() {
-            return true;
-        } else if self.links.len() < SIZE {
-            return false;
-        } else {
-            self.links[SIZE - 1].is_full()
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.root.get(hash(key), key, 0)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Map<K, V> {
+        let (root, added) = self.root.insert(hash(&key), key, value, 0);
+        Map {
+            length: self.length + added as usize,
+            root: Arc::new(root),
+        }
+    }
+
+    pub fn remove(&self, key: &K) -> Map<K, V> {
+        match self.root.remove(hash(key), key, 0) {
+            Some(root) => Map {
+                length: self.length - 1,
+                root: Arc::new(root),
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// The entries in hash order, which is stable for a given set of keys.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            stack: vec![self.root.items()],
         }
     }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
 }
 
-type Link = Rc<Node>;
-
-impl Vector {
-    fn empty() -> Vector {
-        Vector {
-            root: Rc::new(Node::default()),
-        }
-    }
-
-    fn conj(&self, value: i32) -> Vector {
-        let node = self.root.clone();
-        let result = if node.is_full() {
-            println!("splitting {}", value);
-            // use reference
-            let mut new_root = Node::default();
-            let max_depth = node.depth + 1;
-            new_root.depth = max_depth;
-            new_root.push_node(node);
-            let mut new_leaf = Node::default();
-            new_leaf.push(value);
-            for depth in 1..max_depth {
-                let mut inter = Node::default();
-                inter.depth = depth;
-                inter.push_node(Rc::new(new_leaf));
-                new_leaf = inter;
-            }
-            new_root.push_node(Rc::new(new_leaf));
-            Vector {
-                root: Rc::new(new_root),
-            }
-        } else {
-            println!("cloning {}", value);
-            // use clone
-            let mut new_root = node.as_ref().clone();
-            new_root.id = make_id();
-            new_root.push(value);
-            Vector {
-                root: Rc::new(new_root),
+impl<K: Key + Clone, V: Clone> Default for Map<K, V> {
+    fn default() -> Self {
+        Map::new()
+    }
+}
+
+enum Items<'a, K, V> {
+    Children(slice::Iter<'a, Child<K, V>>),
+    Entries(slice::Iter<'a, (K, V)>),
+}
+
+impl<K, V> Node<K, V> {
+    fn items(&self) -> Items<'_, K, V> {
+        match self {
+            Node::Branch { children, .. } => Items::Children(children.iter()),
+            Node::Collision { entries, .. } => Items::Entries(entries.iter()),
+        }
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    stack: Vec<Items<'a, K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match self.stack.last_mut()? {
+                Items::Children(children) => match children.next() {
+                    Some(Child::Entry { key, value, .. }) => return Some((key, value)),
+                    Some(Child::Node(node)) => Some(node.items()),
+                    None => None,
+                },
+                Items::Entries(entries) => match entries.next() {
+                    Some((key, value)) => return Some((key, value)),
+                    None => None,
+                },
+            };
+            match next {
+                Some(items) => self.stack.push(items),
+                None => {
+                    self.stack.pop();
+                }
             }
-        };
-        println!("v {:#?}", result);
-        result
+        }
+    }
+}
+
+impl<K: fmt::Display + Key + Clone, V: fmt::Display + Clone> fmt::Display for Map<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ ")?;
+        for (key, value) in self.iter() {
+            write!(f, "{} {} ", key, value)?;
+        }
+        write!(f, "}}")
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+
+    impl Key for u32 {
+        fn same(&self, other: &Self) -> bool {
+            self == other
+        }
+    }
+
+    /// A key whose hash only depends on `bucket`, to force collisions.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Colliding {
+        bucket: u32,
+        id: u32,
+    }
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.bucket.hash(state)
+        }
+    }
+
+    impl Key for Colliding {
+        fn same(&self, other: &Self) -> bool {
+            self == other
+        }
+    }
+
     #[test]
-    fn empty() {
-        reset_id();
-        let node = Node::default();
-        assert_eq!(node.is_full(), false);
-        assert_eq!(node.depth, 0);
+    fn agrees_with_std() {
+        let mut map = Map::new();
+        let mut model = std::collections::HashMap::new();
+        let mut seed = 7u32;
+        for step in 0..20000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (seed >> 8) % 3000;
+            if step % 3 == 0 {
+                map = map.remove(&key);
+                model.remove(&key);
+            } else {
+                map = map.insert(key, step);
+                model.insert(key, step);
+            }
+            assert_eq!(map.len(), model.len());
+        }
+        for key in 0..3000 {
+            assert_eq!(map.get(&key), model.get(&key));
+        }
+        let mut entries: Vec<_> = map.iter().map(|(key, value)| (*key, *value)).collect();
+        entries.sort_unstable();
+        let mut expected: Vec<_> = model.into_iter().collect();
+        expected.sort_unstable();
+        assert_eq!(entries, expected);
     }
+
     #[test]
-    fn conj() {
-        reset_id();
-        let mut node = Node::default();
-        node.push(23);
-        println!("node {:?}", node);
-        assert_eq!(node.is_full(), false);
-        assert_eq!(node.depth, 0);
+    fn updates_are_persistent() {
+        let empty = Map::new();
+        let one = empty.insert(1, "one");
+        let two = one.insert(2, "two").insert(1, "uno");
+        assert_eq!((empty.len(), one.len(), two.len()), (0, 1, 2));
+        assert_eq!(one.get(&1), Some(&"one"));
+        assert_eq!(two.get(&1), Some(&"uno"));
+        assert_eq!(two.remove(&1).get(&1), None);
+        assert_eq!(two.remove(&3).len(), 2);
+        assert_eq!(two.len(), 2);
     }
 
     #[test]
-    fn vec() {
-        reset_id();
-        let list = Vector::empty()
-            .conj(1)
-            .conj(2)
-            .conj(3)
-            .conj(1)
-            .conj(2)
-            .conj(3);
-        println!("{}", list);
+    fn keeps_colliding_keys_apart() {
+        let key = |bucket, id| Colliding { bucket, id };
+        let mut map = Map::new();
+        for id in 0..4 {
+            map = map.insert(key(0, id), id).insert(key(1, id), id + 10);
+        }
+        assert_eq!(map.len(), 8);
+        assert_eq!(map.get(&key(0, 2)), Some(&2));
+        assert_eq!(map.get(&key(1, 2)), Some(&12));
+        assert_eq!(map.get(&key(0, 9)), None);
+        for id in 0..4 {
+            map = map.remove(&key(0, id));
+        }
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.iter().count(), 4);
+        assert_eq!(map.get(&key(1, 3)), Some(&13));
     }
 }
@@ -1,7 +1,89 @@
-use crate::interpreter::Value;
+use crate::ast::node::KeywordNode;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Name {
+    namespace: Option<String>,
+    name: String,
+    /// The keyword as it prints, kept so it can be borrowed as a name.
+    text: String,
+}
+
+lazy_static! {
+    static ref KEYWORDS: Mutex<HashSet<Arc<Name>>> = Mutex::new(HashSet::new());
+}
+
+/// A keyword evaluates to itself. Keywords are interned, so every `:name`
+/// shares one allocation and comparing two is a pointer comparison. Interned
+/// names live for the rest of the process.
+#[derive(Debug, Clone)]
 pub struct Keyword {
-    pub name: String,
-    pub namespace: Option<String>,
+    name: Arc<Name>,
+}
+
+impl Keyword {
+    pub fn intern(namespace: Option<&str>, name: &str) -> Keyword {
+        let text = match namespace {
+            Some(namespace) => format!(":{}/{}", namespace, name),
+            None => format!(":{}", name),
+        };
+        let key = Name {
+            namespace: namespace.map(String::from),
+            name: String::from(name),
+            text,
+        };
+        let mut keywords = KEYWORDS.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(name) = keywords.get(&key) {
+            return Keyword { name: name.clone() };
+        }
+        let name = Arc::new(key);
+        keywords.insert(name.clone());
+        Keyword { name }
+    }
+
+    /// The keyword written by `node`, leaving `::name` unqualified until it
+    /// is given a namespace by `qualify`.
+    pub fn from_node(node: &KeywordNode) -> Keyword {
+        Keyword::intern(node.namespace(), node.name())
+    }
+
+    pub fn qualify(&self, namespace: &str) -> Keyword {
+        Keyword::intern(Some(namespace), self.name())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name.name[..]
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.name.namespace.as_deref()
+    }
+
+    /// The keyword with its leading colon, `:namespace/name`.
+    pub fn text(&self) -> &str {
+        &self.name.text[..]
+    }
+}
+
+impl PartialEq for Keyword {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.name, &other.name)
+    }
+}
+
+impl Eq for Keyword {}
+
+impl Hash for Keyword {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
 }
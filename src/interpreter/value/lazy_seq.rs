@@ -0,0 +1,307 @@
+use super::Value;
+use crate::interpreter::operation::Seq;
+use crate::result::runtime::ErrorKind;
+use crate::result::Result;
+
+use std::fmt;
+use std::fmt::Formatter;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, ThreadId};
+use std::vec;
+
+/// A realized cell of a sequence.
+#[derive(Debug, Clone)]
+pub enum Step {
+    Empty,
+    /// The first item, and the rest of the sequence, which may be lazy too.
+    Cons(Value, Value),
+}
+
+type Thunk = Box<dyn Fn() -> Result<Step> + Send>;
+
+enum State {
+    Unrealized(Thunk),
+    /// The thunk is running on the thread. Reaching the cell again from
+    /// inside it would never finish, so that fails instead, while other
+    /// threads wait for it.
+    Realizing(ThreadId),
+    Realized(Step),
+}
+
+struct Cell {
+    state: Mutex<State>,
+    /// Signalled whenever a thunk finishes.
+    done: Condvar,
+}
+
+/// A sequence whose cells are computed the first time they are asked for and
+/// then kept, so every value sharing a cell sees it realized at most once.
+/// The lock is not held while a thunk runs, which may realize other cells or
+/// call back into the interpreter. A thunk that fails is kept, and asking for
+/// the cell again runs it again.
+#[derive(Clone)]
+pub struct LazySeq {
+    cell: Arc<Cell>,
+}
+
+impl LazySeq {
+    pub fn new<F>(thunk: F) -> LazySeq
+    where
+        F: Fn() -> Result<Step> + Send + 'static,
+    {
+        LazySeq::from_state(State::Unrealized(Box::new(thunk)))
+    }
+
+    /// A cell that is already realized.
+    pub fn realized(step: Step) -> LazySeq {
+        LazySeq::from_state(State::Realized(step))
+    }
+
+    fn from_state(state: State) -> LazySeq {
+        LazySeq {
+            cell: Arc::new(Cell {
+                state: Mutex::new(state),
+                done: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Realizes this cell, if it is not already. When another thread is
+    /// realizing it, waits for that thread to finish.
+    pub fn step(&self) -> Result<Step> {
+        let current = thread::current().id();
+        let thunk = {
+            let mut state = self.cell.state.lock()?;
+            loop {
+                match mem::replace(&mut *state, State::Realizing(current)) {
+                    State::Realized(step) => {
+                        *state = State::Realized(step.clone());
+                        return Ok(step);
+                    }
+                    State::Realizing(thread) if thread == current => {
+                        return Err(ErrorKind::Internal(
+                            "a lazy sequence was realized from inside itself",
+                        )
+                        .into())
+                    }
+                    State::Realizing(thread) => {
+                        *state = State::Realizing(thread);
+                        state = self.cell.done.wait(state)?;
+                    }
+                    State::Unrealized(thunk) => break thunk,
+                }
+            }
+        };
+        let result = thunk();
+        *self.cell.state.lock()? = match &result {
+            Ok(step) => State::Realized(step.clone()),
+            Err(..) => State::Unrealized(thunk),
+        };
+        self.cell.done.notify_all();
+        result
+    }
+
+    pub fn items(&self) -> Items {
+        Items {
+            next: Some(Value::Lazy(self.clone())),
+            buffered: Vec::new().into_iter(),
+        }
+    }
+
+    /// The items of the cells already realized, without realizing any more.
+    pub fn realized_items(&self) -> Vec<Value> {
+        let mut items = Vec::new();
+        let mut next = Value::Lazy(self.clone());
+        loop {
+            match next {
+                Value::Lazy(seq) => match seq.cell.state.lock().as_deref() {
+                    Ok(State::Realized(Step::Cons(first, rest))) => {
+                        items.push(first.clone());
+                        next = rest.clone();
+                    }
+                    _ => return items,
+                },
+                rest => {
+                    items.extend(rest.items().into_iter().flatten().flatten());
+                    return items;
+                }
+            }
+        }
+    }
+
+    /// Takes the rest out of a realized cell nothing else holds.
+    fn take_rest(&mut self) -> Option<LazySeq> {
+        let state = Arc::get_mut(&mut self.cell)?.state.get_mut().ok()?;
+        match mem::replace(state, State::Realized(Step::Empty)) {
+            State::Realized(Step::Cons(_, Value::Lazy(rest))) => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+/// Unlinks the realized cells this sequence owns one at a time, so dropping
+/// a long sequence does not recurse once per cell.
+impl Drop for LazySeq {
+    fn drop(&mut self) {
+        let mut rest = self.take_rest();
+        while let Some(mut cell) = rest {
+            rest = cell.take_rest();
+        }
+    }
+}
+
+/// Walks a sequence, realizing each cell as it is reached. The rest of a
+/// cell that is not lazy is read all at once.
+pub struct Items {
+    next: Option<Value>,
+    buffered: vec::IntoIter<Value>,
+}
+
+impl Iterator for Items {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.buffered.next() {
+            return Some(Ok(item));
+        }
+        let step = match self.next.take()? {
+            Value::Lazy(cell) => cell.step(),
+            rest => rest.items().and_then(|items| {
+                self.buffered = items.collect::<Result<Vec<_>>>()?.into_iter();
+                Ok(Step::Empty)
+            }),
+        };
+        match step {
+            Ok(Step::Cons(first, rest)) => {
+                self.next = Some(rest);
+                Some(Ok(first))
+            }
+            Ok(Step::Empty) => self.buffered.next().map(Ok),
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+/// The first cell of any sequence.
+pub fn step(sequence: &Value) -> Result<Step> {
+    match sequence {
+        Value::Lazy(cell) => cell.step(),
+        sequence => match sequence.first()? {
+            Some(first) => Ok(Step::Cons(first, sequence.rest()?)),
+            None => Ok(Step::Empty),
+        },
+    }
+}
+
+impl fmt::Debug for LazySeq {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "LazySeq")
+    }
+}
+
+/// Prints as far as the sequence can be realized. Callers that need
+/// realization errors realize it first.
+impl fmt::Display for LazySeq {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "( ")?;
+        for item in self.items().map_while(Result::ok) {
+            write!(f, "{} ", item)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    fn count_from(start: usize, calls: Arc<AtomicUsize>) -> Value {
+        Value::Lazy(LazySeq::new(move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let rest = count_from(start + 1, calls.clone());
+            Ok(Step::Cons(Value::make_number(start as f64), rest))
+        }))
+    }
+
+    #[test]
+    fn realizes_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let numbers = count_from(0, calls.clone());
+        let taken: Value = numbers
+            .items()
+            .unwrap()
+            .take(3)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(taken.to_string(), "( 0 1 2 )");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        numbers.items().unwrap().take(3).for_each(drop);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn failures_can_be_retried() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let cell = LazySeq::new(move || match counted.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(ErrorKind::Unsupported("the first try").into()),
+            _ => Ok(Step::Empty),
+        });
+        assert!(cell.step().is_err());
+        assert!(matches!(cell.step(), Ok(Step::Empty)));
+        assert!(matches!(cell.step(), Ok(Step::Empty)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn other_threads_wait_for_a_realizing_cell() {
+        let (started, running) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let released = Mutex::new(released);
+        let cell = LazySeq::new(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            started.send(()).unwrap();
+            released.lock().unwrap().recv().unwrap();
+            Ok(Step::Empty)
+        });
+        let first = {
+            let cell = cell.clone();
+            thread::spawn(move || cell.step().map(|_| ()))
+        };
+        running.recv().unwrap();
+        let second = {
+            let cell = cell.clone();
+            thread::spawn(move || cell.step().map(|_| ()))
+        };
+        thread::sleep(std::time::Duration::from_millis(20));
+        release.send(()).unwrap();
+        assert!(first.join().unwrap().is_ok());
+        assert!(second.join().unwrap().is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reentry_fails() {
+        let cell: Arc<Mutex<Option<LazySeq>>> = Arc::new(Mutex::new(None));
+        let inner = cell.clone();
+        let seq = LazySeq::new(move || {
+            let seq = inner.lock().unwrap().clone().unwrap();
+            seq.step()
+        });
+        *cell.lock().unwrap() = Some(seq.clone());
+        assert!(seq.step().is_err());
+        *cell.lock().unwrap() = None;
+    }
+
+    #[test]
+    fn drops_long_chains() {
+        let numbers = count_from(0, Arc::new(AtomicUsize::new(0)));
+        assert_eq!(numbers.items().unwrap().take(1_000_000).count(), 1_000_000);
+        drop(numbers);
+    }
+}
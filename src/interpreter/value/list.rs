@@ -1,124 +1,113 @@
-use std::borrow::Borrow;
+use crate::interpreter::Length;
+
 use std::fmt;
 use std::fmt::Formatter;
-use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
 use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct Node<T> {
     value: T,
     next: Link<T>,
 }
 
-impl<T> Node<T> {
-    fn new(value: T) -> Node<T> {
-        Node { value, next: None }
+type Link<T> = Option<Arc<Node<T>>>;
+
+impl<T> Length for List<T> {
+    fn length(&self) -> usize {
+        self.len()
     }
 }
 
-type Link<T> = Option<Arc<Node<T>>>;
-
-#[derive(Debug, Clone)]
-struct List<T> {
+/// A persistent singly linked list. Adding to the front and taking the tail
+/// are O(1), and every list built from another shares its nodes.
+#[derive(Debug)]
+pub struct List<T> {
     length: usize,
     head: Link<T>,
 }
 
 impl<T> List<T> {
-    fn empty() -> List<T> {
+    pub fn new() -> List<T> {
         List {
             length: 0,
             head: None,
         }
     }
 
-    fn conj(&self, value: T) -> List<T> {
-        let new_head = Node {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The list with `value` in front of this one.
+    pub fn conj(&self, value: T) -> List<T> {
+        let head = Node {
             value,
             next: self.head.clone(),
         };
         List {
             length: self.length + 1,
-            head: Some(Arc::new(new_head)),
+            head: Some(Arc::new(head)),
         }
     }
 
-    fn head(&self) -> Option<&T> {
-        let head = self.head.as_deref()?;
-        Some(head.value.borrow())
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_deref().map(|head| &head.value)
     }
 
-    fn tail(&self) -> List<T> {
+    /// Everything after the head, which is the empty list for an empty list.
+    pub fn tail(&self) -> List<T> {
         List {
-            length: if self.length == 0 { 0 } else { self.length - 1 },
-            head: { self.head.as_deref().and_then(|head| head.next.clone()) },
+            length: self.length.saturating_sub(1),
+            head: self.head.as_deref().and_then(|head| head.next.clone()),
         }
     }
 
-    fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             current: self.head.as_deref(),
+            remaining: self.length,
         }
     }
+}
 
-    fn rev(&self) -> List<T>
-    where
-        T: Clone,
-    {
-        let mut list = List::empty();
-        for item in self.iter() {
-            list = list.conj(item.clone())
-        }
-        list
-    }
-
-    fn make(&self, node: Node<T>) -> List<T> {
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
         List {
-            length: self.length + 1,
-            head: Some(Arc::new(node)),
-        }
-    }
-
-    fn insert(&self, index: usize, value: T) -> Option<List<T>>
-    where
-        T: Clone + fmt::Debug,
-    {
-        println!("INSERT index={:?}, value={:?}", index, value);
-        if index == 0 {
-            Some(self.conj(value))
-        } else {
-            let head = self.head()?.clone();
-            let rest = self.tail().insert(index - 1, value)?;
-            Some(rest.conj(head))
+            length: self.length,
+            head: self.head.clone(),
         }
     }
 }
 
-impl<T: fmt::Display> fmt::Display for List<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for item in self.iter() {
-            write!(f, "{} ", item)?;
-        }
-        writeln!(f)?;
-        Ok(())
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
     }
 }
 
+/// Unlinks the nodes this list owns one at a time, so dropping a long list
+/// does not recurse once per node.
 impl<T> Drop for List<T> {
     fn drop(&mut self) {
         let mut head = self.head.take();
         while let Some(node) = head {
-            if let Ok(mut node) = Arc::try_unwrap(node) {
-                head = node.next.take();
-            } else {
-                break;
+            match Arc::try_unwrap(node) {
+                Ok(mut node) => head = node.next.take(),
+                Err(..) => break,
             }
         }
     }
 }
 
-struct Iter<'a, T> {
+pub struct Iter<'a, T> {
     current: Option<&'a Node<T>>,
+    remaining: usize,
 }
 
 impl<'a, T> Iterator for Iter<'a, T> {
@@ -127,7 +116,61 @@ impl<'a, T> Iterator for Iter<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         let node = self.current?;
         self.current = node.next.as_deref();
-        Some(node.value.borrow())
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Collects into a list in iteration order, so the first item is the head.
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        items
+            .into_iter()
+            .rev()
+            .fold(List::new(), |list, item| list.conj(item))
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.length == other.length && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.length.hash(state);
+        for value in self {
+            value.hash(state);
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for List<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "( ")?;
+        for value in self {
+            write!(f, "{} ", value)?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -137,12 +180,35 @@ mod test {
 
     #[test]
     fn conj() {
-        let mut list = List::empty();
+        let mut list = List::new();
         for value in 0..100 {
             list = list.conj(value);
         }
+        assert_eq!(list.len(), 100);
         for (value, key) in (0..100).rev().zip(list.iter()) {
             assert_eq!(value, *key);
         }
+        let collected: List<i32> = (0..3).collect();
+        assert_eq!(collected.to_string(), "( 0 1 2 )");
+    }
+
+    #[test]
+    fn shares_structure() {
+        let list: List<i32> = (1..4).collect();
+        let consed = list.conj(0);
+        let tail = consed.tail();
+        assert_eq!(tail, list);
+        assert!(Arc::ptr_eq(
+            tail.head.as_ref().unwrap(),
+            list.head.as_ref().unwrap()
+        ));
+        assert_eq!(list.head(), Some(&1));
+        assert_eq!(consed.head(), Some(&0));
+        assert!(List::<i32>::new().tail().is_empty());
+        // Dropping the only handle to a long list must not overflow the
+        // stack.
+        let long: List<usize> = (0..1_000_000).collect();
+        drop(long.tail());
+        drop(long);
     }
 }
@@ -1,7 +1,8 @@
+use crate::interpreter::operation::Compare;
 use crate::interpreter::operation::Conj;
-use crate::interpreter::operation::Introspection;
 use crate::interpreter::operation::Length;
 use crate::interpreter::operation::Lookup;
+use crate::interpreter::operation::{Items, Seq};
 use crate::result::runtime::ErrorKind as Error;
 use crate::result::Result;
 
@@ -9,6 +10,7 @@ mod boolean;
 mod function;
 mod hash_map;
 mod keyword;
+mod lazy_seq;
 mod list;
 mod number;
 mod string;
@@ -16,22 +18,29 @@ mod symbol;
 mod var;
 mod vector;
 
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
-use std::str::FromStr;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::mem;
 use std::sync::Arc;
 
 pub use boolean::Boolean;
+pub use function::apply;
 pub use function::nf;
+pub use function::Arity;
 pub use function::Function;
 pub use function::UserFunction;
+pub use hash_map::Map;
+pub use keyword::Keyword;
+pub use lazy_seq::{step, LazySeq, Step};
+pub use list::List;
 pub use number::Number;
 pub use string::String;
 pub use symbol::Symbol;
 pub use var::Var;
 pub use vector::Vector;
 
-pub const NIL: Value = Value::Nil;
-
 #[derive(Debug, Clone)]
 pub enum Value {
     Nil,
@@ -39,23 +48,88 @@ pub enum Value {
     Number(Number),
     String(String),
     Symbol(Symbol),
+    Keyword(Keyword),
     Var(Var),
     Function(Arc<dyn Function>),
     Vector(Vector<Value>),
+    List(List<Value>),
+    Lazy(LazySeq),
+    Map(Map<Value, Value>),
+}
+
+/// Consistent with `=`: values that compare equal hash the same.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Lists, vectors and lazy sequences with the same items are `=`, so
+        // none of them hashes its variant.
+        if !matches!(self, Value::Vector(..) | Value::List(..) | Value::Lazy(..)) {
+            mem::discriminant(self).hash(state);
+        }
+        match self {
+            Value::Nil => {}
+            Value::Boolean(boolean) => boolean.value.hash(state),
+            // `=` holds between 0 and -0, whose bits differ.
+            Value::Number(number) if number.value == 0.0 => 0u64.hash(state),
+            Value::Number(number) => number.value.to_bits().hash(state),
+            Value::String(string) => string.value.hash(state),
+            Value::Symbol(symbol) => symbol.hash(state),
+            Value::Keyword(keyword) => keyword.hash(state),
+            Value::Var(var) => {
+                var.namespace.hash(state);
+                var.name.hash(state);
+            }
+            Value::Function(function) => (Arc::as_ptr(function) as *const () as usize).hash(state),
+            Value::Vector(vector) => vector.hash(state),
+            Value::List(list) => list.hash(state),
+            // Hashed like a list. Keys are realized before they reach a
+            // map, see `into_key`, so hashing never realizes anything.
+            Value::Lazy(seq) => {
+                let items = seq.realized_items();
+                items.len().hash(state);
+                for item in &items {
+                    item.hash(state);
+                }
+            }
+            // Entries are summed so equal maps hash the same whatever order
+            // they hold them in.
+            Value::Map(map) => {
+                let entries = map.iter().fold(0u64, |sum, (key, value)| {
+                    let mut hasher = DefaultHasher::new();
+                    key.hash(&mut hasher);
+                    value.hash(&mut hasher);
+                    sum.wrapping_add(hasher.finish())
+                });
+                map.len().hash(state);
+                entries.hash(state);
+            }
+        }
+    }
+}
+
+impl hash_map::Key for Value {
+    fn same(&self, other: &Self) -> bool {
+        Compare::eq(self, other)
+    }
 }
 
 impl Lookup for Value {
     type Item = Value;
     type Key = Value;
-    type Err = Error;
+    type Err = crate::result::Error;
 
     fn lookup(&self, key: Self::Key) -> Result<&Self::Item> {
         match (self, key) {
-            (Value::Vector(vector), Value::Number(number)) => vector.lookup(number),
-            (left, right) => {
-                println!("left {} {}", left, right);
-                Err(Error::General("un supported lookup"))
+            (Value::Vector(vector), Value::Number(number)) => Ok(vector.lookup(number)?),
+            (Value::Map(map), key) => {
+                let key = key.into_key()?;
+                map.get(&key).ok_or_else(|| Error::KeyNotFound(key).into())
+            }
+            (Value::Nil, key) | (Value::Vector(..), key) => Err(Error::KeyNotFound(key).into()),
+            (collection, _) => Err(Error::TypeMismatch {
+                expected: "a vector or map",
+                actual: collection.clone(),
             }
+            .into()),
         }
     }
 }
@@ -63,16 +137,80 @@ impl Lookup for Value {
 impl Conj for Value {
     type Container = Value;
     type Value = Value;
-    type Err = Error;
+    type Err = crate::result::Error;
 
     fn conj(&self, value: Self::Value) -> Result<Self::Container> {
         match self {
             Value::Vector(vector) => Ok(Value::Vector(vector.push(value))),
-            value => Err(Error::General("Unsupported operation.")),
+            Value::List(list) => Ok(Value::List(list.conj(value))),
+            Value::Nil => Ok(Value::List(List::new().conj(value))),
+            Value::Map(map) => match value {
+                Value::Vector(entry) if entry.len() == 2 => {
+                    let key = entry.get(0).cloned().unwrap_or(Value::Nil);
+                    let value = entry.get(1).cloned().unwrap_or(Value::Nil);
+                    Ok(Value::Map(map.insert(key.into_key()?, value)))
+                }
+                entry => Err(Error::TypeMismatch {
+                    expected: "a vector of a key and a value",
+                    actual: entry,
+                }
+                .into()),
+            },
+            collection => Err(Error::TypeMismatch {
+                expected: "a list, vector or map",
+                actual: collection.clone(),
+            }
+            .into()),
         }
     }
 }
 
+impl Seq for Value {
+    type Item = Value;
+    type Err = crate::result::Error;
+
+    fn items(&self) -> Result<Items<'_, Value, Self::Err>> {
+        match self {
+            Value::Nil => Ok(Box::new(std::iter::empty())),
+            Value::List(list) => Ok(Box::new(list.iter().cloned().map(Ok))),
+            Value::Vector(vector) => Ok(Box::new(vector.iter().cloned().map(Ok))),
+            Value::Lazy(seq) => Ok(Box::new(seq.items())),
+            Value::Map(map) => {
+                Ok(Box::new(map.iter().map(|(key, value)| {
+                    Ok(Value::from_iter(vec![key.clone(), value.clone()]))
+                })))
+            }
+            Value::String(string) => {
+                Ok(Box::new(string.value.chars().map(|c| {
+                    Ok(Value::make_string(c.encode_utf8(&mut [0; 4])))
+                })))
+            }
+            value => Err(Error::TypeMismatch {
+                expected: "a collection or string",
+                actual: value.clone(),
+            }
+            .into()),
+        }
+    }
+
+    fn rest(&self) -> Result<Value> {
+        match self {
+            Value::List(list) => Ok(Value::List(list.tail())),
+            Value::Lazy(seq) => match seq.step()? {
+                Step::Cons(_, rest) => Ok(rest),
+                Step::Empty => Ok(Value::List(List::new())),
+            },
+            value => Ok(Value::List(value.items()?.skip(1).collect::<Result<_>>()?)),
+        }
+    }
+}
+
+impl FromIterator<Value> for Value {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        Value::Vector(iter.into_iter().collect())
+    }
+}
+
 impl From<bool> for Value {
     fn from(value: bool) -> Self {
         Value::Boolean(value.into())
@@ -85,17 +223,57 @@ impl From<f64> for Value {
     }
 }
 
+/// Nested collections are written from a stack of the ones still open rather
+/// than by recursion, so that deeply nested values print.
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Nil => write!(f, "nil"),
-            Value::Boolean(value) => write!(f, "{}", value),
-            Value::Number(value) => write!(f, "{}", value),
-            Value::String(value) => write!(f, "{}", value),
-            Value::Symbol(value) => write!(f, "{}", value),
-            Value::Var(value) => write!(f, "{}", value),
-            Value::Function(value) => write!(f, "[fn:{}]", value.name()),
-            Value::Vector(vector) => write!(f, "{}", vector),
+        // Each open collection with its remaining items and how it closes.
+        let mut open: Vec<(Items<'static, Value, crate::result::Error>, &str)> = Vec::new();
+        let mut next = Some(self.clone());
+        loop {
+            match next.take() {
+                Some(Value::Function(value)) if !value.bound().is_empty() => {
+                    write!(f, "[partial:{}", value.name())?;
+                    let bound = value.bound().to_vec();
+                    open.push((Box::new(bound.into_iter().map(Ok)), "]"));
+                }
+                Some(Value::Map(map)) => {
+                    write!(f, "{{")?;
+                    let entries: Vec<_> = map
+                        .iter()
+                        .flat_map(|(key, value)| vec![key.clone(), value.clone()])
+                        .collect();
+                    open.push((Box::new(entries.into_iter().map(Ok)), " }"));
+                }
+                Some(value @ (Value::Vector(..) | Value::List(..) | Value::Lazy(..))) => {
+                    write!(f, "(")?;
+                    open.push((value.into_items().map_err(|_| fmt::Error)?, " )"));
+                }
+                Some(Value::Nil) => write!(f, "nil")?,
+                Some(Value::Boolean(value)) => write!(f, "{}", value)?,
+                Some(Value::Number(value)) => write!(f, "{}", value)?,
+                Some(Value::String(value)) => write!(f, "{}", value)?,
+                Some(Value::Symbol(value)) => write!(f, "{}", value)?,
+                Some(Value::Keyword(value)) => write!(f, "{}", value)?,
+                Some(Value::Var(value)) => write!(f, "{}", value)?,
+                Some(Value::Function(value)) => write!(f, "[fn:{}]", value.name())?,
+                None => {}
+            }
+            let (items, close) = match open.last_mut() {
+                Some(top) => top,
+                None => return Ok(()),
+            };
+            // Printing stops at an item that fails to realize.
+            match items.next() {
+                Some(Ok(item)) => {
+                    write!(f, " ")?;
+                    next = Some(item);
+                }
+                _ => {
+                    write!(f, "{}", close)?;
+                    open.pop();
+                }
+            }
         }
     }
 }
@@ -119,9 +297,92 @@ impl Value {
         Value::Boolean(Boolean { value })
     }
 
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(..) => "boolean",
+            Value::Number(..) => "number",
+            Value::String(..) => "string",
+            Value::Symbol(..) => "symbol",
+            Value::Keyword(..) => "keyword",
+            Value::Var(..) => "var",
+            Value::Function(..) => "function",
+            Value::Vector(..) => "vector",
+            Value::List(..) => "list",
+            Value::Lazy(..) => "sequence",
+            Value::Map(..) => "map",
+        }
+    }
+
     pub fn show(self) -> Self {
         println!("value {}", self);
-        return self;
+        self
+    }
+
+    /// The number of items in a collection, or characters in a string.
+    pub fn count(&self) -> Result<usize> {
+        match self {
+            Value::Nil => Ok(0),
+            Value::String(string) => Ok(string.length()),
+            Value::Vector(vector) => Ok(vector.length()),
+            Value::List(list) => Ok(list.length()),
+            Value::Lazy(seq) => seq
+                .items()
+                .try_fold(0, |count, item| item.map(|_| count + 1)),
+            Value::Map(map) => Ok(map.len()),
+            value => Err(Error::TypeMismatch {
+                expected: "a collection or string",
+                actual: value.clone(),
+            }
+            .into()),
+        }
+    }
+
+    /// Realizes every lazy sequence in the value, so that an error one of
+    /// them hits surfaces before the value is printed. Nested collections are
+    /// walked from a stack rather than by recursion.
+    pub fn realize(&self) -> Result<()> {
+        let mut open: Vec<Items<'static, Value, crate::result::Error>> =
+            vec![Box::new(std::iter::once(Ok(self.clone())))];
+        while let Some(items) = open.last_mut() {
+            match items.next().transpose()? {
+                Some(
+                    item @ (Value::Vector(..) | Value::List(..) | Value::Lazy(..) | Value::Map(..)),
+                ) => open.push(item.into_items()?),
+                Some(_) => {}
+                None => {
+                    open.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The items of the value like `Seq::items`, owning what they walk so
+    /// they can outlive the value.
+    pub fn into_items(self) -> Result<Items<'static, Value, crate::result::Error>> {
+        match self {
+            Value::Lazy(seq) => Ok(Box::new(seq.items())),
+            Value::Vector(vector) => {
+                Ok(Box::new((0..vector.len()).filter_map(move |index| {
+                    vector.get(index).cloned().map(Ok)
+                })))
+            }
+            Value::List(mut list) => Ok(Box::new(std::iter::from_fn(move || {
+                let head = list.head()?.clone();
+                list = list.tail();
+                Some(Ok(head))
+            }))),
+            value => Ok(Box::new(value.items()?.collect::<Vec<_>>().into_iter())),
+        }
+    }
+
+    /// The value, ready to be a map key. Keys are hashed through their
+    /// items, so the lazy sequences in one are realized here, where an error
+    /// can still be returned.
+    pub fn into_key(self) -> Result<Value> {
+        self.realize()?;
+        Ok(self)
     }
 
     pub fn is_local_identifier(&self) -> bool {
@@ -137,6 +398,16 @@ impl Value {
         }
     }
 
+    /// The function to call when the value is in call position. Keywords
+    /// look themselves up in their argument, `(:name m)` is `(get m :name)`.
+    pub fn callable(&self) -> Result<Arc<dyn Function>> {
+        match self {
+            Value::Function(function) => Ok(function.clone()),
+            Value::Keyword(keyword) => Ok(Arc::new(keyword.clone())),
+            value => Err(Error::NotCallable(value.clone()).into()),
+        }
+    }
+
     pub fn take_function(self) -> Option<Arc<dyn Function>> {
         match self {
             Value::Function(function) => Some(function),
@@ -1,4 +1,3 @@
-use super::super::{Compare, Introspection, Math};
 use std::fmt;
 use std::fmt::Formatter;
 
@@ -1,11 +1,6 @@
-use super::super::Concat;
-use super::super::Introspection;
-use super::super::Length;
 use std::fmt;
 use std::fmt::Formatter;
 
-type Str = std::string::String;
-
 #[derive(Debug, Clone)]
 pub struct String {
     pub value: std::string::String,
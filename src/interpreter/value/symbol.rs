@@ -1,7 +1,7 @@
 use crate::ast::node::SymbolNode;
 use std::fmt;
 
-type STR = &'static str;
+type Str = &'static str;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Symbol {
@@ -44,8 +44,8 @@ impl Symbol {
     }
 }
 
-impl From<(STR, STR)> for Symbol {
-    fn from((ns, n): (STR, STR)) -> Self {
+impl From<(Str, Str)> for Symbol {
+    fn from((ns, n): (Str, Str)) -> Self {
         Symbol {
             name: n.into(),
             namespace: Some(ns.into()),
@@ -53,8 +53,8 @@ impl From<(STR, STR)> for Symbol {
     }
 }
 
-impl From<(STR)> for Symbol {
-    fn from((n): (STR)) -> Self {
+impl From<&str> for Symbol {
+    fn from(n: &str) -> Self {
         Symbol {
             name: n.into(),
             namespace: None,
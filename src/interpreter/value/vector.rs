@@ -1,16 +1,14 @@
 use crate::interpreter::Length;
 
-use std::borrow::Borrow;
-use std::cmp::max;
 use std::fmt;
 use std::fmt::Formatter;
-use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
 use std::sync::Arc;
-use Node::*;
-use View::*;
 
-const INTERNAL_LINK_ERROR: &str = "Links should have at least one child.";
-const LINK_INVARIANT: &str = "Expected an internal link";
+const BITS: usize = 5;
+const SLOTS: usize = 1 << BITS;
+const MASK: usize = SLOTS - 1;
 
 impl<T> Length for Vector<T> {
     fn length(&self) -> usize {
@@ -18,342 +16,419 @@ impl<T> Length for Vector<T> {
     }
 }
 
-enum Operation<Ok, Value> {
-    Success(Ok),
-    WrongNodeOperation(Value),
-    LeafFull(Value),
-}
-
+/// Every leaf in the trie is full. The last, possibly partial, chunk lives
+/// in the vector's tail until it fills.
 #[derive(Debug, Clone)]
-enum Issue<T> {
-    WrongNodeOperation,
-    LeafFull(T),
+enum Node<T> {
+    Leaf(Vec<T>),
+    Branch(Vec<Arc<Node<T>>>),
 }
 
-type Result<T, K> = std::result::Result<T, Issue<K>>;
-
-const BITS: usize = 5;
-const SLOTS: usize = 1 << BITS;
-const MASK: usize = SLOTS - 1;
+impl<T> Node<T> {
+    fn empty() -> Node<T> {
+        Node::Branch(vec![])
+    }
 
-#[derive(Debug, Clone)]
-enum Node<T> {
-    Leaf { values: Vec<T> },
-    Internal { depth: usize, links: Vec<Link<T>> },
-}
+    fn children(&self) -> &[Arc<Node<T>>] {
+        match self {
+            Node::Branch(children) => children,
+            Node::Leaf(..) => &[],
+        }
+    }
 
-enum View<'a, T> {
-    Values(&'a Vec<T>),
-    Links(&'a Vec<Link<T>>),
+    /// `node` hung below `level` bits of otherwise empty branches.
+    fn path(level: usize, node: Arc<Node<T>>) -> Arc<Node<T>> {
+        if level == 0 {
+            node
+        } else {
+            Arc::new(Node::Branch(vec![Node::path(level - BITS, node)]))
+        }
+    }
 }
 
-enum Info {
-    RightMostRoom,
-    RoomInSelf,
-    RoomInSubtree,
-    Full,
+/// A persistent vector: a 32-way trie of full leaves plus a tail buffer of
+/// the last chunk. Appends copy only the tail until it fills, and updates
+/// copy the path to one leaf, sharing everything else with the original.
+#[derive(Debug, Clone)]
+pub struct Vector<T> {
+    length: usize,
+    /// The bits of an index consumed above the root's children.
+    shift: usize,
+    root: Arc<Node<T>>,
+    tail: Arc<Vec<T>>,
 }
 
-impl<T> Node<T> {
-    fn len(&self) -> usize {
-        match self.view() {
-            Values(vs) => vs.len(),
-            Links(ls) => ls.len(),
+impl<T> Vector<T> {
+    pub fn new() -> Vector<T> {
+        Vector {
+            length: 0,
+            shift: BITS,
+            root: Arc::new(Node::empty()),
+            tail: Arc::new(vec![]),
         }
     }
 
-    fn gen_leaf() -> Node<T> {
-        Leaf { values: vec![] }
+    pub fn len(&self) -> usize {
+        self.length
     }
 
-    fn gen_internal(depth: usize) -> Node<T> {
-        Internal {
-            depth,
-            links: vec![],
-        }
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
     }
 
-    fn decrease_depth(self) -> Node<T> {
-        match self {
-            Node::Leaf { values } => Leaf { values },
-            Node::Internal { depth, links } => Internal {
-                depth: max(depth - 1, 1),
-                links,
-            },
-        }
+    /// The index of the first value in the tail.
+    fn tail_offset(&self) -> usize {
+        self.length - self.tail.len()
     }
 
-    fn info(&self) -> Info {
-        match self.view() {
-            Values(values) => {
-                if values.len() < SLOTS {
-                    Info::RightMostRoom
-                } else {
-                    Info::Full
-                }
-            }
-            Links(links) => {
-                let link = links.last().expect(INTERNAL_LINK_ERROR).as_ref();
-                match link.info() {
-                    Info::RightMostRoom => Info::RightMostRoom,
-                    Info::RoomInSelf => Info::RoomInSubtree,
-                    Info::RoomInSubtree => Info::RoomInSubtree,
-                    Info::Full => {
-                        if links.len() < SLOTS {
-                            Info::RoomInSelf
-                        } else {
-                            Info::Full
-                        }
-                    }
+    /// The chunk of 32 values holding `index`, which must be in bounds.
+    fn chunk(&self, index: usize) -> &[T] {
+        if index >= self.tail_offset() {
+            return &self.tail;
+        }
+        let mut node = &*self.root;
+        let mut level = self.shift;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = &children[(index >> level) & MASK];
+                    level -= BITS;
                 }
+                Node::Leaf(values) => return values,
             }
         }
     }
 
-    fn links(&self) -> Option<&Vec<Link<T>>> {
-        match self.view() {
-            Links(links) => Some(links),
-            _ => None,
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.length {
+            Some(&self.chunk(index)[index & MASK])
+        } else {
+            None
         }
     }
 
-    fn view(&self) -> View<T> {
-        match self {
-            Node::Leaf { values } => Values(values),
-            Node::Internal { links, .. } => Links(links),
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            vector: self,
+            index: 0,
+            chunk: &[],
         }
     }
 
-    fn depth(&self) -> usize {
-        match self {
-            Node::Leaf { .. } => 0,
-            Node::Internal { depth, .. } => *depth,
+    pub fn push(&self, value: T) -> Vector<T>
+    where
+        T: Clone,
+    {
+        if self.tail.len() < SLOTS {
+            let mut tail = Vec::with_capacity(SLOTS);
+            tail.extend(self.tail.iter().cloned());
+            tail.push(value);
+            return Vector {
+                length: self.length + 1,
+                shift: self.shift,
+                root: self.root.clone(),
+                tail: Arc::new(tail),
+            };
+        }
+        let leaf = Arc::new(Node::Leaf(self.tail.to_vec()));
+        let (root, shift) = if (self.length >> BITS) > (1 << self.shift) {
+            let children = vec![self.root.clone(), Node::path(self.shift, leaf)];
+            (Arc::new(Node::Branch(children)), self.shift + BITS)
+        } else {
+            (self.push_leaf(self.shift, &self.root, leaf), self.shift)
+        };
+        Vector {
+            length: self.length + 1,
+            shift,
+            root,
+            tail: Arc::new(vec![value]),
         }
     }
 
-    fn mask(&self, key: usize) -> usize {
-        let offset = BITS * self.depth();
-        let mask = MASK << offset;
-        (key & mask) >> offset
+    /// Copies the rightmost path of `node` with the full tail `leaf` added.
+    fn push_leaf(&self, level: usize, node: &Node<T>, leaf: Arc<Node<T>>) -> Arc<Node<T>> {
+        let mut children = node.children().to_vec();
+        let index = ((self.length - 1) >> level) & MASK;
+        if level == BITS {
+            children.push(leaf);
+        } else if index < children.len() {
+            let child = children[index].clone();
+            children[index] = self.push_leaf(level - BITS, &child, leaf);
+        } else {
+            children.push(Node::path(level - BITS, leaf));
+        }
+        Arc::new(Node::Branch(children))
     }
 
-    fn get(&self, key: usize) -> Option<&T> {
-        let index = self.mask(key);
-        match self {
-            Node::Leaf { values } => {
-                let value = values.get(index)?;
-                Some(value)
-            }
-            Node::Internal { links, .. } => {
-                let link = links.get(index)?;
-                link.get(key)
-            }
+    /// The vector with `value` at `index`, or `None` past the end. Setting
+    /// the index one past the last value appends it.
+    pub fn assoc(&self, index: usize, value: T) -> Option<Vector<T>>
+    where
+        T: Clone,
+    {
+        if index > self.length {
+            return None;
+        }
+        if index == self.length {
+            return Some(self.push(value));
+        }
+        let mut vector = self.clone();
+        if index >= self.tail_offset() {
+            let mut tail = self.tail.to_vec();
+            tail[index & MASK] = value;
+            vector.tail = Arc::new(tail);
+        } else {
+            vector.root = Vector::assoc_node(self.shift, &self.root, index, value);
         }
+        Some(vector)
     }
 
-    fn anchor(&self, value: T) -> Self
+    fn assoc_node(level: usize, node: &Node<T>, index: usize, value: T) -> Arc<Node<T>>
     where
         T: Clone,
     {
-        match self {
-            Node::Leaf { values } => {
+        Arc::new(match node {
+            Node::Leaf(values) => {
                 let mut values = values.clone();
-                values.push(value);
-                Leaf { values }
+                values[index & MASK] = value;
+                Node::Leaf(values)
             }
-            Node::Internal { depth, .. } => {
-                let node = Arc::new(if *depth == 1 {
-                    Node::gen_leaf().anchor(value)
-                } else {
-                    Node::gen_internal(depth - 1).anchor(value)
-                });
-                Internal {
-                    depth: *depth,
-                    links: vec![node],
-                }
+            Node::Branch(children) => {
+                let mut children = children.clone();
+                let slot = (index >> level) & MASK;
+                children[slot] = Vector::assoc_node(level - BITS, &children[slot], index, value);
+                Node::Branch(children)
             }
-        }
+        })
     }
 
-    fn push(&self, value: T) -> Self
+    /// The vector without its last value, or `None` when it is empty.
+    pub fn pop(&self) -> Option<Vector<T>>
     where
         T: Clone,
     {
-        match self.info() {
-            Info::RightMostRoom => match self.view() {
-                Values(vs) => {
-                    let mut vs = vs.clone();
-                    vs.push(value);
-                    Leaf { values: vs }
-                }
-                Links(ls) => {
-                    let last = ls.last().unwrap().as_ref();
-                    let node = last.push(value);
-                    let mut ls = ls.clone();
-                    let last = ls.last_mut().unwrap();
-                    *last = Arc::new(node);
-                    Internal {
-                        depth: self.depth(),
-                        links: ls,
-                    }
-                }
-            },
-            Info::RoomInSelf => {
-                let mut links = self.links().expect(LINK_INVARIANT).clone();
-                if self.depth() == 1 {
-                    links.push(Arc::new(Node::gen_leaf().anchor(value)))
-                } else {
-                    links.push(Arc::new(
-                        Node::gen_internal(self.depth())
-                            .decrease_depth()
-                            .anchor(value),
-                    ))
-                }
-                Internal {
-                    depth: self.depth(),
-                    links,
-                }
-            }
-            Info::RoomInSubtree => {
-                let mut links = self.links().expect(LINK_INVARIANT).clone();
-                let new_link = links.pop().expect(INTERNAL_LINK_ERROR).push(value);
-                links.push(Arc::new(new_link));
-                Internal {
-                    depth: self.depth(),
-                    links,
-                }
+        match self.length {
+            0 => return None,
+            1 => return Some(Vector::new()),
+            _ => {}
+        }
+        let mut vector = self.clone();
+        vector.length -= 1;
+        if self.tail.len() > 1 {
+            vector.tail = Arc::new(self.tail[..self.tail.len() - 1].to_vec());
+            return Some(vector);
+        }
+        vector.tail = Arc::new(self.chunk(self.length - 2).to_vec());
+        let mut root = self
+            .pop_leaf(self.shift, &self.root)
+            .unwrap_or_else(|| Arc::new(Node::empty()));
+        if self.shift > BITS && root.children().len() == 1 {
+            root = root.children()[0].clone();
+            vector.shift -= BITS;
+        }
+        vector.root = root;
+        Some(vector)
+    }
+
+    /// Copies the rightmost path of `node` without its last leaf, or `None`
+    /// when nothing is left below it.
+    fn pop_leaf(&self, level: usize, node: &Node<T>) -> Option<Arc<Node<T>>> {
+        let index = ((self.length - 2) >> level) & MASK;
+        let mut children = node.children().to_vec();
+        if level > BITS {
+            match self.pop_leaf(level - BITS, &children[index]) {
+                Some(child) => children[index] = child,
+                None if index == 0 => return None,
+                None => children.truncate(index),
             }
-            Info::Full => match self {
-                Leaf { values: vs } => {
-                    let links = vec![
-                        Arc::new(Leaf { values: vs.clone() }),
-                        Arc::new(Leaf {
-                            values: vec![value],
-                        }),
-                    ];
-                    Internal { depth: 1, links }
-                }
-                Internal { depth, links: ls } => {
-                    let links = vec![
-                        Arc::new(Internal {
-                            depth: self.depth(),
-                            links: ls.clone(),
-                        }),
-                        Arc::new(Node::gen_internal(*depth).anchor(value)),
-                    ];
-                    Internal {
-                        depth: depth + 1,
-                        links,
-                    }
-                }
-            },
+        } else if index == 0 {
+            return None;
+        } else {
+            children.truncate(index);
         }
+        Some(Arc::new(Node::Branch(children)))
     }
 }
 
-impl<T: fmt::Display> fmt::Display for Vector<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "( ")?;
-        for i in 0..self.length {
-            write!(f, "{} ", self.get(i).unwrap())?;
-        }
-        write!(f, ")")?;
-        Ok(())
+impl<T> Default for Vector<T> {
+    fn default() -> Self {
+        Vector::new()
     }
 }
 
-impl<T: fmt::Display> fmt::Display for Node<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Leaf { values } => {
-                let end = values.len();
-                write!(f, "(")?;
-                for (i, value) in values.iter().enumerate() {
-                    write!(f, "{}", value)?;
-                    if i + 1 != end {
-                        write!(f, ",")?;
-                    }
-                }
-                write!(f, ")")?;
-            }
-            Internal { depth: _, links } => {
-                let end = links.len();
-                write!(f, "(")?;
-                for (i, link) in links.iter().enumerate() {
-                    write!(f, "{}", link)?;
-                    if i + 1 != end {
-                        write!(f, " ")?;
-                    }
-                }
-                write!(f, ")")?;
-            }
+/// Walks the vector a chunk at a time, descending the trie once per 32
+/// values.
+pub struct Iter<'a, T> {
+    vector: &'a Vector<T>,
+    index: usize,
+    chunk: &'a [T],
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.vector.length {
+            return None;
         }
-        Ok(())
+        if self.index & MASK == 0 {
+            self.chunk = self.vector.chunk(self.index);
+        }
+        let value = &self.chunk[self.index & MASK];
+        self.index += 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.vector.length - self.index;
+        (remaining, Some(remaining))
     }
 }
 
-type Link<T> = Arc<Node<T>>;
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
 
-#[derive(Debug, Clone)]
-pub struct Vector<T> {
-    length: usize,
-    root: Node<T>,
+impl<'a, T> IntoIterator for &'a Vector<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
-impl<T> Vector<T> {
-    pub fn len(&self) -> usize {
-        self.length
+impl<T: Clone> FromIterator<T> for Vector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Vector::new(), |vector, value| vector.push(value))
     }
+}
 
-    pub fn new() -> Vector<T> {
-        let primary = Leaf { values: vec![] };
-        Vector {
-            length: 0,
-            root: primary,
-        }
+impl<T: PartialEq> PartialEq for Vector<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.length == other.length && self.iter().eq(other.iter())
     }
-    pub fn push(&self, value: T) -> Vector<T>
-    where
-        T: Clone,
-    {
-        Vector {
-            length: self.length + 1,
-            root: self.root.push(value),
+}
+
+impl<T: Eq> Eq for Vector<T> {}
+
+impl<T: Hash> Hash for Vector<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.length.hash(state);
+        for value in self {
+            value.hash(state);
         }
     }
+}
 
-    pub fn get(&self, index: usize) -> Option<&T> {
-        self.root.get(index)
+impl<T: fmt::Display> fmt::Display for Vector<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "( ")?;
+        for value in self {
+            write!(f, "{} ", value)?;
+        }
+        write!(f, ")")
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    #[derive(Debug, Clone)]
+    enum Edit {
+        Push(i32),
+        Pop,
+        Assoc(usize, i32),
+    }
 
-    #[test]
-    fn update_leaf() -> Result<(), i32> {
-        let mut leaf1 = Leaf { values: vec![] };
-        let n: i32 = 100;
-        for value in 0..n {
-            leaf1 = leaf1.push(value);
+    fn edit() -> impl Strategy<Value = Edit> {
+        prop_oneof![
+            3 => any::<i32>().prop_map(Edit::Push),
+            1 => Just(Edit::Pop),
+            1 => (any::<usize>(), any::<i32>()).prop_map(|(index, value)| Edit::Assoc(index, value)),
+        ]
+    }
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    proptest! {
+        #[test]
+        fn agrees_with_std(edits in prop::collection::vec(edit(), 0..2000)) {
+            let mut vector = Vector::new();
+            let mut model = vec![];
+            for edit in edits {
+                match edit {
+                    Edit::Push(value) => {
+                        vector = vector.push(value);
+                        model.push(value);
+                    }
+                    Edit::Pop => {
+                        prop_assert_eq!(vector.pop().is_some(), model.pop().is_some());
+                        vector = vector.pop().unwrap_or(vector);
+                    }
+                    Edit::Assoc(index, value) => {
+                        let index = index % (model.len() + 2);
+                        match vector.assoc(index, value) {
+                            Some(updated) if index == model.len() => {
+                                model.push(value);
+                                vector = updated;
+                            }
+                            Some(updated) => {
+                                model[index] = value;
+                                vector = updated;
+                            }
+                            None => prop_assert!(index > model.len()),
+                        }
+                    }
+                }
+                prop_assert_eq!(vector.len(), model.len());
+            }
+            prop_assert!(vector.iter().eq(model.iter()));
+            for (index, value) in model.iter().enumerate() {
+                prop_assert_eq!(vector.get(index), Some(value));
+            }
+            prop_assert_eq!(vector.get(model.len()), None);
         }
-        for value in 0..n {
-            assert_eq!(Some(&value), leaf1.get(value as usize))
+
+        #[test]
+        fn builds_equal_vectors(values in prop::collection::vec(any::<i32>(), 0..1500)) {
+            let vector: Vector<i32> = values.iter().cloned().collect();
+            let mut popped = vector.push(0);
+            popped = popped.pop().unwrap();
+            prop_assert_eq!(&popped, &vector);
+            prop_assert_eq!(hash(&popped), hash(&vector));
+            if let Some(&first) = values.first() {
+                let changed = vector.assoc(0, first.wrapping_add(1)).unwrap();
+                prop_assert_ne!(&changed, &vector);
+            }
         }
-        Ok(())
     }
 
     #[test]
-    fn vector() {
-        let mut v: Vector<i32> = Vector::new();
-        let n: i32 = 100000;
-        for value in 0..n {
-            v = v.push(value);
-        }
-        println!("{}", v.root);
-        for value in 0..n {
-            assert_eq!(Some(&value), v.get(value as usize))
-        }
+    fn updates_are_persistent() {
+        let original: Vector<usize> = (0..100_000).collect();
+        let updated = original.assoc(500, 0).unwrap();
+        let popped = (0..40_000).fold(original.clone(), |vector, _| vector.pop().unwrap());
+        assert_eq!(original.get(500), Some(&500));
+        assert_eq!(updated.get(500), Some(&0));
+        assert_eq!(popped.len(), 60_000);
+        assert_eq!(popped.get(59_999), Some(&59_999));
+        assert!(original.iter().cloned().eq(0..100_000));
+        assert_eq!(
+            original
+                .iter()
+                .take(3)
+                .cloned()
+                .collect::<Vector<_>>()
+                .to_string(),
+            "( 0 1 2 )"
+        );
+        assert_eq!(Vector::<i32>::new().pop(), None);
     }
 }
@@ -1,10 +1,10 @@
-// #![allow(warnings, unused)]
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate prettytable;
 
-use std::fs::read_to_string;
+use std::fs;
+use std::path::Path;
 
 #[macro_use]
 pub mod ast;
@@ -12,27 +12,195 @@ pub mod interpreter;
 pub mod result;
 #[macro_use]
 pub mod prelude;
+pub mod bytecode;
 mod emitter;
+mod format;
+mod repl;
 
+use emitter::Emitter;
 use prelude::*;
 
-fn run_file(file: String) -> Result<()> {
-    let source = read_to_string(file).ok().ok_or(General("Fuck"))?;
-    let tokens = Scanner::scan(source).ok_or(General("Fuck"))?;
-    let ast = parse(tokens)?;
-    println!("{:#?}", ast);
-    // let mut interpreter = interpreter::Interpreter::new();
-    // let result = interpreter.eval(ast)?;
-    Ok(())
+fn run(source: String, engine: cli::Engine) -> Result<Value> {
+    let tokens = Scanner::scan(source)?;
+    let program = parse(tokens)?;
+    let interpreter = Interpreter::boot()?;
+    match engine {
+        cli::Engine::Tree => interpreter.eval(&program),
+        cli::Engine::Vm => bytecode::run(bytecode::compile(&program)?, &interpreter),
+    }
 }
 
-fn main() {
-    // win::main();
-    let file = cli::start();
-    match run_file(file) {
-        Ok(..) => {}
+fn compile(file: &str, source: &str) -> Result<bytecode::Module> {
+    let tokens = Scanner::scan(source.to_string())?;
+    let program = parse(tokens)?;
+    Ok(bytecode::Module {
+        source: file.to_string(),
+        prototype: bytecode::compile(&program)?,
+    })
+}
+
+fn is_module(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        == Some(bytecode::module::EXTENSION)
+}
+
+fn read_source(file: &str) -> String {
+    match fs::read_to_string(file) {
+        Ok(source) => source,
         Err(err) => {
-            println!("error {:?}", err)
+            eprintln!("{}: error: {}", file, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads `file` as a compiled module, or compiles it when it is source.
+/// Also returns the source the module was compiled from, when available,
+/// for error reports.
+fn load(file: &str) -> (String, Result<bytecode::Module>) {
+    if !is_module(file) {
+        let source = read_source(file);
+        let module = compile(file, &source);
+        return (source, module);
+    }
+    let module = fs::read(file)
+        .map_err(Error::from)
+        .and_then(|bytes| bytecode::Module::read(&bytes));
+    let source = module
+        .as_ref()
+        .ok()
+        .and_then(|module| fs::read_to_string(&module.source).ok())
+        .unwrap_or_default();
+    (source, module)
+}
+
+fn fail(err: Error, file: &str, source: &str) -> ! {
+    eprintln!("{}", report(&err, file, source));
+    std::process::exit(1);
+}
+
+fn finish(file: &str, source: &str, result: Result<Value>) {
+    match result.and_then(|value| value.realize().map(|_| value)) {
+        Ok(Value::Nil) => {}
+        Ok(value) => println!("{}", value),
+        Err(err) => fail(err, file, source),
+    }
+}
+
+fn run_file(file: &str, engine: cli::Engine) {
+    if is_module(file) {
+        let (source, module) = load(file);
+        let module = module.unwrap_or_else(|err| fail(err, file, &source));
+        let result = Interpreter::boot()
+            .and_then(|interpreter| bytecode::run(module.prototype.clone(), &interpreter));
+        return finish(&module.source, &source, result);
+    }
+    let source = read_source(file);
+    finish(file, &source, run(source.clone(), engine));
+}
+
+fn compile_file(file: &str, output: Option<String>) {
+    let source = read_source(file);
+    let module = compile(file, &source).unwrap_or_else(|err| fail(err, file, &source));
+    let output = output.unwrap_or_else(|| {
+        let path = Path::new(file).with_extension(bytecode::module::EXTENSION);
+        path.to_string_lossy().into_owned()
+    });
+    let result = module
+        .write()
+        .and_then(|bytes| Ok(fs::write(&output, bytes)?));
+    if let Err(err) = result {
+        fail(err, &output, "");
+    }
+}
+
+fn disassemble_file(file: &str) {
+    let (source, module) = load(file);
+    let module = module.unwrap_or_else(|err| fail(err, file, &source));
+    let source = Some(&source[..]).filter(|source| !source.is_empty());
+    match bytecode::disassemble(&module.prototype, source) {
+        Ok(output) => print!("{}", output),
+        Err(err) => fail(err, file, ""),
+    }
+}
+
+fn emit_file(file: &str, target: cli::Target) {
+    let source = read_source(file);
+    let result = Scanner::scan(source.clone())
+        .and_then(parse)
+        .and_then(|program| match target {
+            cli::Target::JavaScript => program.emit(),
+            cli::Target::C => emitter::c::Program(&program).emit(),
+        });
+    match result {
+        Ok(script) => print!("{}", script),
+        Err(err) => fail(err, file, &source),
+    }
+}
+
+fn build_file(file: &str, output: Option<String>) {
+    let source = read_source(file);
+    let program = Scanner::scan(source.clone())
+        .and_then(parse)
+        .unwrap_or_else(|err| fail(err, file, &source));
+    let output = output.unwrap_or_else(|| {
+        let path = Path::new(file).with_extension("");
+        path.to_string_lossy().into_owned()
+    });
+    if let Err(err) = emitter::c::build(&program, Path::new(&output)) {
+        fail(err, file, &source);
+    }
+}
+
+/// Formats `files` in place, or with `check` only reports the ones that
+/// are not formatted and exits with an error if there are any.
+fn format_files(files: &[String], check: bool) {
+    let mut unformatted = false;
+    for file in files {
+        let source = read_source(file);
+        let formatted = format::format(&source).unwrap_or_else(|err| fail(err, file, &source));
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            unformatted = true;
+        } else if let Err(err) = fs::write(file, formatted) {
+            fail(err.into(), file, "");
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    // The tree walker recurses on the native stack, which the main thread
+    // has too little of.
+    let main = std::thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(start)
+        .expect("spawning the interpreter thread");
+    if main.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn start() {
+    match cli::start() {
+        cli::Command::Run(file, engine) => run_file(&file, engine),
+        cli::Command::Compile(file, output) => compile_file(&file, output),
+        cli::Command::Disasm(file) => disassemble_file(&file),
+        cli::Command::Emit(file, target) => emit_file(&file, target),
+        cli::Command::Build(file, output) => build_file(&file, output),
+        cli::Command::Format(files, check) => format_files(&files, check),
+        cli::Command::Repl => {
+            if let Err(err) = repl::start() {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
         }
     }
 }
@@ -40,9 +208,34 @@ fn main() {
 mod cli {
     extern crate clap;
 
-    use clap::{App, Arg};
+    use clap::{App, Arg, SubCommand};
 
-    pub fn start() -> String {
+    /// How a file is evaluated: by walking the tree, or compiled to
+    /// bytecode and run on the virtual machine.
+    #[derive(Clone, Copy)]
+    pub enum Engine {
+        Tree,
+        Vm,
+    }
+
+    /// The languages `nomad emit` translates to.
+    #[derive(Clone, Copy)]
+    pub enum Target {
+        JavaScript,
+        C,
+    }
+
+    pub enum Command {
+        Run(String, Engine),
+        Compile(String, Option<String>),
+        Disasm(String),
+        Emit(String, Target),
+        Build(String, Option<String>),
+        Format(Vec<String>, bool),
+        Repl,
+    }
+
+    pub fn start() -> Command {
         let matches = App::new("nomad")
             .version("0.0.0")
             .author("Alexander Sanchez <the@mild.one>")
@@ -50,10 +243,112 @@ mod cli {
             .arg(
                 Arg::with_name("source")
                     .value_name("FILE")
-                    .help("The file to run"),
+                    .help("The file to run, starts a repl when omitted. Compiled .ndc modules run on the virtual machine"),
+            )
+            .arg(
+                Arg::with_name("vm")
+                    .long("vm")
+                    .help("Compiles the file to bytecode and runs it on the virtual machine"),
+            )
+            .subcommand(SubCommand::with_name("repl").about("Starts an interactive session"))
+            .subcommand(
+                SubCommand::with_name("compile")
+                    .about("Compiles a file to a .ndc module")
+                    .arg(Arg::with_name("file").value_name("FILE").required(true))
+                    .arg(
+                        Arg::with_name("output")
+                            .short("o")
+                            .value_name("OUTPUT")
+                            .help("Where to write the module, next to FILE by default"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("disasm")
+                    .about("Prints the bytecode of a source file or .ndc module")
+                    .arg(Arg::with_name("file").value_name("FILE").required(true)),
+            )
+            .subcommand(
+                SubCommand::with_name("emit")
+                    .about("Translates a file to another language")
+                    .arg(
+                        Arg::with_name("target")
+                            .long("target")
+                            .value_name("TARGET")
+                            .possible_values(&["js", "c"])
+                            .default_value("js")
+                            .help("The language to emit"),
+                    )
+                    .arg(Arg::with_name("file").value_name("FILE").required(true)),
+            )
+            .subcommand(
+                SubCommand::with_name("build")
+                    .about("Compiles a file to a native executable with the system C compiler")
+                    .arg(Arg::with_name("file").value_name("FILE").required(true))
+                    .arg(
+                        Arg::with_name("output")
+                            .short("o")
+                            .value_name("OUTPUT")
+                            .help("Where to write the executable, FILE without its extension by default"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("fmt")
+                    .about("Formats files in place")
+                    .arg(
+                        Arg::with_name("check")
+                            .long("check")
+                            .help("Lists the files that are not formatted instead, failing if there are any"),
+                    )
+                    .arg(
+                        Arg::with_name("files")
+                            .value_name("FILE")
+                            .multiple(true)
+                            .required(true),
+                    ),
             )
             .get_matches();
-        let config = matches.value_of("source").unwrap_or("core.nd");
-        String::from(config)
+        match matches.subcommand() {
+            ("compile", Some(compile)) => {
+                return Command::Compile(
+                    String::from(compile.value_of("file").unwrap_or_default()),
+                    compile.value_of("output").map(String::from),
+                )
+            }
+            ("disasm", Some(disasm)) => {
+                return Command::Disasm(String::from(disasm.value_of("file").unwrap_or_default()))
+            }
+            ("emit", Some(emit)) => {
+                let target = match emit.value_of("target") {
+                    Some("c") => Target::C,
+                    _ => Target::JavaScript,
+                };
+                return Command::Emit(
+                    String::from(emit.value_of("file").unwrap_or_default()),
+                    target,
+                );
+            }
+            ("fmt", Some(fmt)) => {
+                let files = fmt.values_of("files").into_iter().flatten();
+                return Command::Format(files.map(String::from).collect(), fmt.is_present("check"));
+            }
+            ("build", Some(build)) => {
+                return Command::Build(
+                    String::from(build.value_of("file").unwrap_or_default()),
+                    build.value_of("output").map(String::from),
+                )
+            }
+            _ => {}
+        }
+        match matches.value_of("source") {
+            Some(file) if matches.subcommand_name().is_none() => {
+                let engine = if matches.is_present("vm") {
+                    Engine::Vm
+                } else {
+                    Engine::Tree
+                };
+                Command::Run(String::from(file), engine)
+            }
+            _ => Command::Repl,
+        }
     }
 }
@@ -27,7 +27,7 @@ pub mod node {
 macro_rules! defnode {
     ($enum:path : $struct:ident :: $var:ident => $body:expr ) => {
         impl TryFrom<Vec<Node>> for $struct {
-            type Error = ErrorKind;
+            type Error = Error;
 
             fn try_from($var: Vec<Node>) -> Result<$struct> {
                 $body
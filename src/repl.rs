@@ -0,0 +1,208 @@
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use prettytable::Table;
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use crate::prelude::*;
+
+const PROMPT: &str = "nomad> ";
+const CONTINUATION: &str = "  ...> ";
+const SOURCE: &str = "<repl>";
+const HELP: &str = "\
+:env         bindings of the current namespace
+:scope       bindings of the current scope chain
+:ns          loaded namespaces
+:doc EXPR    type, arity and value of EXPR
+:type EXPR   type of the value of EXPR
+:time EXPR   evaluate EXPR and report how long it took
+:help        this message";
+
+/// The meta-commands. A line starting with anything else is evaluated, so
+/// that a keyword like `:a` is not taken for a command.
+const COMMANDS: &[&str] = &[":env", ":scope", ":ns", ":doc", ":type", ":time", ":help"];
+
+fn history() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".nomad_history"))
+}
+
+/// Whether `source` can be handed to the parser, or the reader should wait
+/// for more lines: open strings and unclosed parens, brackets or braces mean
+/// the form continues on the next line.
+fn is_complete(source: &str) -> bool {
+    let tokens = match Scanner::scan(source.to_string()) {
+        Ok(tokens) => tokens,
+        Err(error) => return !matches!(error.kind(), ErrorKind::UnterminatedString),
+    };
+    let mut depth = 0;
+    for token in tokens {
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            return true;
+        }
+    }
+    depth == 0
+}
+
+fn evaluate(interpreter: &Interpreter, source: &str) -> Result<Value> {
+    let tokens = Scanner::scan(source.to_string())?;
+    let program = parse(tokens)?;
+    interpreter.eval(&program)
+}
+
+fn doc(interpreter: &Interpreter, expression: &str) -> Result<Table> {
+    let value = evaluate(interpreter, expression)?;
+    let mut table = Table::new();
+    table.add_row(row!["name", expression]);
+    table.add_row(row!["type", value.type_name()]);
+    if let Value::Function(function) = &value {
+        table.add_row(row!["arity", function.arity()]);
+    }
+    table.add_row(row!["value", value]);
+    Ok(table)
+}
+
+/// Whether the first word of `source` names a meta-command.
+fn is_command(source: &str) -> bool {
+    let name = source.split_whitespace().next().unwrap_or_default();
+    COMMANDS.contains(&name)
+}
+
+/// Runs a `:command`, returning what it renders.
+fn command(interpreter: &Interpreter, line: &str) -> Result<String> {
+    let line = line.trim();
+    let (name, argument) = match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, ""),
+    };
+    match name {
+        ":env" => Ok(interpreter.namespace_table()?.to_string()),
+        ":scope" => Ok(interpreter.scope_table()?.to_string()),
+        ":ns" => Ok(interpreter.namespaces_table()?.to_string()),
+        ":doc" => Ok(doc(interpreter, argument)?.to_string()),
+        ":type" => Ok(evaluate(interpreter, argument)?.type_name().to_string()),
+        ":time" => {
+            let start = Instant::now();
+            let value = evaluate(interpreter, argument)?;
+            value.realize()?;
+            Ok(format!("{}\nelapsed {:?}", value, start.elapsed()))
+        }
+        ":help" => Ok(HELP.to_string()),
+        name => Err(ErrorKind::UnexpectedToken(name.to_string()).into()),
+    }
+}
+
+/// Reads forms from the terminal and evaluates them against a single
+/// interpreter, so definitions survive between lines and across errors.
+pub fn start() -> Result<()> {
+    let interpreter = Interpreter::boot()?;
+    let mut editor = Editor::<()>::new();
+    let history = history();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+    let mut source = String::new();
+    loop {
+        let prompt = if source.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                source.push_str(&line);
+                source.push('\n');
+                if !is_complete(&source) {
+                    continue;
+                }
+                if source.trim().is_empty() {
+                    source.clear();
+                    continue;
+                }
+                editor.add_history_entry(source.trim_end());
+                if is_command(&source) {
+                    match command(&interpreter, &source) {
+                        Ok(output) => println!("{}", output.trim_end()),
+                        Err(error) => eprintln!("error: {}", error.kind()),
+                    }
+                } else {
+                    let value = evaluate(&interpreter, &source)
+                        .and_then(|value| value.realize().map(|_| value));
+                    match value {
+                        Ok(value) => println!("{}", value),
+                        Err(error) => eprintln!("{}", report(&error, SOURCE, &source)),
+                    }
+                }
+                source.clear();
+            }
+            Err(ReadlineError::Interrupted) => source.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(io::Error::other(error).into()),
+        }
+    }
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn waits_for_balanced_forms() {
+        assert!(is_complete("(+ 1 2)"));
+        assert!(is_complete("1 2"));
+        assert!(!is_complete("(def f (fn [x]\n"));
+        assert!(!is_complete("(println \"open\n"));
+        assert!(is_complete("(println \"(\")"));
+        assert!(is_complete("(+ 1 2))"));
+    }
+
+    #[test]
+    fn keeps_definitions_across_errors() {
+        let interpreter = Interpreter::boot().unwrap();
+        evaluate(&interpreter, "(def a 1)").unwrap();
+        assert!(evaluate(&interpreter, "(+ a missing)").is_err());
+        assert!(evaluate(&interpreter, "(let [b 2] (+ a b missing))").is_err());
+        let value = evaluate(&interpreter, "(+ a 1)").unwrap();
+        assert_eq!(value.to_string(), "2");
+    }
+
+    #[test]
+    fn evaluates_keywords_instead_of_taking_them_for_commands() {
+        assert!(is_command(":env") && is_command("  :help\n"));
+        assert!(!is_command(":a") && !is_command(":environment"));
+        assert!(is_command(":doc mod") && is_command(":type :a") && is_command(":time (+ 1 2)"));
+        assert!(!is_command(":types") && !is_command(":timeout 5"));
+        let interpreter = Interpreter::boot().unwrap();
+        assert_eq!(evaluate(&interpreter, ":a").unwrap().to_string(), ":a");
+    }
+
+    #[test]
+    fn commands_inspect_the_live_interpreter() {
+        let interpreter = Interpreter::boot().unwrap();
+        evaluate(&interpreter, "(def answer 42)").unwrap();
+        let env = command(&interpreter, ":env").unwrap();
+        assert!(env.contains("[namespace::nomad.core]"));
+        assert!(env.contains("answer") && env.contains("42"));
+        assert!(command(&interpreter, ":ns").unwrap().contains("nomad.core"));
+        assert!(command(&interpreter, ":scope").unwrap().contains("frame"));
+        assert_eq!(command(&interpreter, ":type answer").unwrap(), "number");
+        let doc = command(&interpreter, ":doc mod").unwrap();
+        assert!(doc.contains("function") && doc.contains("arity"));
+        assert!(command(&interpreter, ":time (+ 1 2)")
+            .unwrap()
+            .starts_with("3\nelapsed"));
+        assert!(command(&interpreter, ":type missing").is_err());
+        assert!(command(&interpreter, ":nope").is_err());
+    }
+}
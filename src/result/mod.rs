@@ -1,45 +1,208 @@
-use crate::prelude::*;
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::ast::scanner::Location;
+use runtime::ErrorKind;
 
 pub mod runtime {
+    use std::io;
+
+    use crate::interpreter::{Arity, Symbol, Value};
+
     #[derive(Debug)]
     pub enum ErrorKind {
-        BindingNotFound,
-        CouldNotParseAtom,
-        ExpectedClosingParen,
-        IfMissingCondition,
-        IfMissingTrueBranch,
-        InvalidArgumentArity,
-        InvalidDefForm,
-        InvalidNamespace,
-        InvalidNode,
-        InvalidOperation,
-        MissingNode,
-        NodeNotFound,
-        NotDefined,
-        StorageIssue,
-        TagNodeMissMatch,
+        // Reading
+        UnexpectedCharacter(char),
+        UnterminatedString,
         UnexpectedEof,
-        General(&'static str),
+        UnexpectedToken(String),
+        InvalidNumber(String),
+        InvalidForm {
+            form: &'static str,
+            reason: &'static str,
+        },
+        InvalidBinding(&'static str),
+        InvalidRecur(&'static str),
+        InvalidRecurArity {
+            expected: usize,
+            actual: usize,
+        },
+        InvalidModule(&'static str),
+
+        // Evaluation
+        BindingMismatch(&'static str),
+        NotDefined(Symbol),
+        InvalidNamespace(Symbol),
+        NotCallable(Value),
+        TypeMismatch {
+            expected: &'static str,
+            actual: Value,
+        },
+        InvalidArgumentArity {
+            function: String,
+            expected: Arity,
+            actual: usize,
+        },
+        KeyNotFound(Value),
+        Unsupported(&'static str),
+        StackOverflow(usize),
+
+        // Host
+        Internal(&'static str),
+        Io(io::Error),
     }
 }
 
-pub trait MakeError {
-    type Item;
-    fn info(message: &'static str) -> Self::Item;
+/// An `ErrorKind` along with where in the source it happened, when known.
+/// The kind is boxed to keep `Result`s small.
+#[derive(Debug)]
+pub struct Error {
+    pub kind: Box<ErrorKind>,
+    pub location: Option<Location>,
 }
 
-pub type Result<T> = std::result::Result<T, runtime::ErrorKind>;
-impl<T> MakeError for Result<T> {
-    type Item = Result<T>;
-    fn info(message: &'static str) -> Self::Item {
-        Err(runtime::ErrorKind::General(message))
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl ErrorKind {
+    pub fn at(self, location: Location) -> Error {
+        Error {
+            kind: Box::new(self),
+            location: Some(location),
+        }
     }
 }
 
-use std::io;
+impl Error {
+    /// Attaches `location` unless the error already has one; the innermost
+    /// location is the most precise.
+    pub fn at(self, location: Location) -> Error {
+        Error {
+            location: self.location.or(Some(location)),
+            ..self
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            kind: Box::new(kind),
+            location: None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        ErrorKind::Io(error).into()
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ErrorKind::*;
+        match self {
+            UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c),
+            UnterminatedString => write!(f, "unterminated string"),
+            UnexpectedEof => write!(f, "unexpected end of input"),
+            UnexpectedToken(lexeme) => write!(f, "unexpected `{}`", lexeme),
+            InvalidNumber(lexeme) => write!(f, "invalid number `{}`", lexeme),
+            InvalidForm { form, reason } => write!(f, "invalid {} form: {}", form, reason),
+            InvalidBinding(reason) => write!(f, "invalid binding: {}", reason),
+            InvalidRecur(reason) => write!(f, "invalid recur: {}", reason),
+            InvalidRecurArity { expected, actual } => write!(
+                f,
+                "recur expects {} values but was given {}",
+                expected, actual
+            ),
+            InvalidModule(reason) => write!(f, "invalid module: {}", reason),
+            BindingMismatch(reason) => write!(f, "binding mismatch: {}", reason),
+            NotDefined(symbol) => write!(f, "`{}` is not defined", symbol),
+            InvalidNamespace(symbol) => write!(f, "namespace `{}` does not exist", symbol),
+            NotCallable(value) => write!(f, "{} `{}` is not callable", value.type_name(), value),
+            TypeMismatch { expected, actual } => write!(
+                f,
+                "expected {} but found {} `{}`",
+                expected,
+                actual.type_name(),
+                actual
+            ),
+            InvalidArgumentArity {
+                function,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "`{}` expects {} arguments but was given {}",
+                function, expected, actual
+            ),
+            KeyNotFound(key) => write!(f, "key `{}` not found", key),
+            Unsupported(feature) => write!(f, "{} are not supported yet", feature),
+            StackOverflow(depth) => write!(f, "stack overflow: calls nested over {} deep", depth),
+            Internal(reason) => write!(f, "internal error: {}", reason),
+            Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(location) => write!(f, "{}: {}", location, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.kind() {
+            ErrorKind::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
 
-impl From<io::ErrorKind> for ErrorKind {
-    fn from(_: io::ErrorKind) -> Self {
-        General("io operation failure")
+/// Formats `error` as `file:line:column`, followed by the offending source
+/// line with the located span underlined.
+pub fn report(error: &Error, file: &str, source: &str) -> String {
+    let location = match error.location() {
+        Some(location) => location,
+        None => return format!("{}: error: {}", file, error.kind()),
+    };
+    let start = location.start;
+    let mut report = format!(
+        "{}:{}:{}: error: {}",
+        file,
+        start.line,
+        start.column,
+        error.kind()
+    );
+    if let Some(line) = source.lines().nth(start.line - 1) {
+        let width = line.chars().count();
+        let length = if location.end.line == start.line {
+            location.end.column.saturating_sub(start.column)
+        } else {
+            width + 1 - start.column.min(width + 1)
+        };
+        let gutter = start.line.to_string();
+        report.push_str(&format!(
+            "\n{} | {}\n{} | {}{}",
+            gutter,
+            line,
+            " ".repeat(gutter.len()),
+            " ".repeat(start.column - 1),
+            "^".repeat(length.max(1))
+        ));
     }
+    report
 }